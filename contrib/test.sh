@@ -1,6 +1,6 @@
 #!/bin/sh -ex
 
-FEATURES="bitcoinconsensus use-serde serde-decimal"
+FEATURES="bitcoinconsensus use-serde serde-decimal non-constant-time-signing"
 
 # Use toolchain if explicitly specified
 if [ -n "$TOOLCHAIN" ]
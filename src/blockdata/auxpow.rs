@@ -0,0 +1,527 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//     Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Auxiliary proof-of-work
+//!
+//! Merge-mined blocks prove their work through a block of a parent chain
+//! whose coinbase commits to the merged-mining merkle root. This module
+//! contains the pieces needed to check that commitment.
+//!
+
+use std::{error, fmt};
+
+use bitcoin_hashes::{sha256d, Hash, HashEngine};
+use byteorder::{ByteOrder, LittleEndian};
+
+use blockdata::block::{BaseHeader, VERSION_CHAIN_START};
+use blockdata::script::Script;
+use blockdata::transaction::Transaction;
+use consensus::encode;
+use consensus::params::Params;
+
+/// The magic bytes which precede the merged-mining commitment in the parent coinbase
+pub const MERGED_MINING_HEADER: [u8; 4] = [0xfa, 0xbe, 0x6d, 0x6d];
+
+/// The longest merged-mining branch accepted, limiting the tree to 2^30 chains
+pub const MAX_CHAIN_MERKLE_BRANCH: usize = 30;
+
+/// The latest offset in a parent coinbase scriptSig at which a commitment
+/// without the merged-mining magic may start its merkle root
+pub const MAX_LEGACY_ROOT_OFFSET: usize = 20;
+
+/// An auxpow validation error
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The block version flags an auxpow but none is attached
+    MissingAuxPow,
+    /// An auxpow is attached but the block version does not flag it
+    UnexpectedAuxPow,
+    /// The block carries a chain ID other than ours
+    WrongChainId(u32),
+    /// The parent block carries our own chain ID
+    ParentHasOurChainId,
+    /// The transaction proven in the parent block is not its coinbase
+    NotCoinbase,
+    /// The merged-mining branch is longer than `MAX_CHAIN_MERKLE_BRANCH`
+    ChainMerkleBranchTooLong(usize),
+    /// The coinbase branch does not lead to the parent block's merkle root
+    CoinbaseMerkleRootMismatch,
+    /// The parent coinbase has no input to carry the commitment
+    NoCoinbaseInput,
+    /// The parent coinbase has no unique, complete merged-mining commitment
+    MissingMergedMiningHeader,
+    /// The commitment is not to the root of our merged-mining branch
+    MerkleRootMismatch,
+    /// The merkle root does not directly follow the merged-mining magic
+    MerkleRootNotAfterHeader,
+    /// A commitment without the magic starts its merkle root at this offset,
+    /// past `MAX_LEGACY_ROOT_OFFSET`
+    LegacyMerkleRootTooLate(usize),
+    /// The tree size in the commitment does not match the branch length
+    MerkleSizeMismatch,
+    /// The nonce in the commitment is not the expected one
+    MerkleNonceMismatch,
+    /// Our chain is not at the slot derived from the commitment nonce
+    WrongChainIndex,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::WrongChainId(id) => write!(f, "block has wrong chain ID {}", id),
+            Error::ChainMerkleBranchTooLong(ell) => write!(f, "merged-mining branch of length {} too long", ell),
+            Error::LegacyMerkleRootTooLate(pos) => write!(f, "merged-mining root at offset {} of parent coinbase", pos),
+            _ => f.write_str(error::Error::description(self)),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> { None }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::MissingAuxPow => "no auxpow on block with auxpow version",
+            Error::UnexpectedAuxPow => "auxpow on block with non-auxpow version",
+            Error::WrongChainId(_) => "block has wrong chain ID",
+            Error::ParentHasOurChainId => "auxpow parent has our chain ID",
+            Error::NotCoinbase => "auxpow is not a coinbase",
+            Error::ChainMerkleBranchTooLong(_) => "merged-mining branch too long",
+            Error::CoinbaseMerkleRootMismatch => "auxpow coinbase merkle branch does not match parent merkle root",
+            Error::NoCoinbaseInput => "auxpow coinbase has no inputs",
+            Error::MissingMergedMiningHeader => "no unique merged-mining commitment in parent coinbase",
+            Error::MerkleRootMismatch => "merged-mining commitment does not match chain merkle root",
+            Error::MerkleRootNotAfterHeader => "merged-mining root does not follow the magic",
+            Error::LegacyMerkleRootTooLate(_) => "merged-mining root too late in parent coinbase",
+            Error::MerkleSizeMismatch => "merged-mining tree size does not match branch length",
+            Error::MerkleNonceMismatch => "merged-mining nonce does not match",
+            Error::WrongChainIndex => "wrong index in merged-mining tree",
+        }
+    }
+}
+
+/// The auxiliary proof-of-work of a merge-mined block, which follows the
+/// header when its version has the auxpow flag set
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct AuxPow {
+    /// Coinbase transaction of the parent block, linking the auxpow block to it
+    pub coinbase_txn: Transaction,
+    /// Hash of the parent block header
+    pub block_hash: sha256d::Hash,
+    /// The merkle branch linking the coinbase transaction to the parent block's merkle root
+    pub coinbase_branch_hashes: Vec<sha256d::Hash>,
+    /// Index of the coinbase transaction in the parent block's merkle tree, whose
+    /// bits say which side each branch hash goes on. Always zero for a coinbase.
+    pub coinbase_branch_side_mask: u32,
+    /// The merkle branch linking this chain to the merged-mining root, when
+    /// several auxiliary chains are mined together
+    pub blockchain_branch_hashes: Vec<sha256d::Hash>,
+    /// Index of this chain in the merged-mining merkle tree
+    pub blockchain_branch_side_mask: u32,
+    /// The parent block header, whose proof-of-work is borrowed
+    pub parent_block: BaseHeader,
+}
+
+impl_consensus_encoding!(
+    AuxPow,
+    coinbase_txn,
+    block_hash,
+    coinbase_branch_hashes,
+    coinbase_branch_side_mask,
+    blockchain_branch_hashes,
+    blockchain_branch_side_mask,
+    parent_block
+);
+
+impl AuxPow {
+    /// Decodes an auxpow on its own, as it is serialized after a block header.
+    /// Bytes left over after the auxpow are an error.
+    pub fn from_slice(data: &[u8]) -> Result<AuxPow, encode::Error> {
+        encode::deserialize(data)
+    }
+
+    /// Checks that the parent block commits to the block with hash `aux_block_hash`
+    /// on chain `chain_id`. This does not check the parent block's proof-of-work.
+    pub fn check(&self, aux_block_hash: sha256d::Hash, chain_id: u32, params: &Params) -> Result<(), Error> {
+        if self.coinbase_branch_side_mask != 0 {
+            return Err(Error::NotCoinbase);
+        }
+        if params.strict_chain_id && self.parent_block.version / VERSION_CHAIN_START == chain_id {
+            return Err(Error::ParentHasOurChainId);
+        }
+        if self.blockchain_branch_hashes.len() > MAX_CHAIN_MERKLE_BRANCH {
+            return Err(Error::ChainMerkleBranchTooLong(self.blockchain_branch_hashes.len()));
+        }
+
+        let parent_root = verify_merkle_branch(self.coinbase_txn.txid(), &self.coinbase_branch_hashes, self.coinbase_branch_side_mask);
+        if parent_root != self.parent_block.merkle_root {
+            return Err(Error::CoinbaseMerkleRootMismatch);
+        }
+
+        let root = self.chain_merkle_root(aux_block_hash);
+        let nonce = LittleEndian::read_u32(&self.commitment(&root)?[4..8]);
+        self.check_coinbase_commitment(aux_block_hash, chain_id, self.blockchain_branch_hashes.len() as u32, nonce)
+    }
+
+    /// Checks that the parent coinbase commits to a merged-mining tree of height
+    /// `merkle_height` and nonce `merkle_nonce`, with the block with hash
+    /// `aux_block_hash` at the slot the nonce assigns to chain `chain_id`.
+    pub fn check_coinbase_commitment(&self, aux_block_hash: sha256d::Hash, chain_id: u32, merkle_height: u32, merkle_nonce: u32) -> Result<(), Error> {
+        if self.coinbase_txn.input.is_empty() {
+            return Err(Error::NoCoinbaseInput);
+        }
+        if self.blockchain_branch_hashes.len() != merkle_height as usize || merkle_height as usize > MAX_CHAIN_MERKLE_BRANCH {
+            return Err(Error::MerkleSizeMismatch);
+        }
+
+        let commitment = self.commitment(&self.chain_merkle_root(aux_block_hash))?;
+        if LittleEndian::read_u32(&commitment[..4]) != 1 << merkle_height {
+            return Err(Error::MerkleSizeMismatch);
+        }
+        if LittleEndian::read_u32(&commitment[4..8]) != merkle_nonce {
+            return Err(Error::MerkleNonceMismatch);
+        }
+
+        if Some(self.blockchain_branch_side_mask) != get_expected_index(merkle_nonce, chain_id, merkle_height) {
+            return Err(Error::WrongChainIndex);
+        }
+        Ok(())
+    }
+
+    /// The merged-mining root of the block with hash `aux_block_hash`, as the
+    /// parent coinbase commits to it: in display order, i.e. byte-reversed
+    fn chain_merkle_root(&self, aux_block_hash: sha256d::Hash) -> [u8; 32] {
+        let mut root = verify_merkle_branch(aux_block_hash, &self.blockchain_branch_hashes, self.blockchain_branch_side_mask).into_inner();
+        root.reverse();
+        root
+    }
+
+    /// The tree size and nonce which follow the merged-mining root `root` in
+    /// the parent coinbase.
+    ///
+    /// The root must directly follow the merged-mining magic. Parent coinbases
+    /// from before the magic was introduced have none, and as in Namecoin are
+    /// accepted if the root starts within their first `MAX_LEGACY_ROOT_OFFSET`
+    /// bytes instead, which leaves no room for a second commitment.
+    fn commitment(&self, root: &[u8; 32]) -> Result<&[u8], Error> {
+        let script = match self.coinbase_txn.input.first() {
+            Some(input) => &input.script_sig,
+            None => return Err(Error::NoCoinbaseInput),
+        };
+        let bytes = script.as_bytes();
+        let pos = bytes.windows(32).position(|w| w == &root[..]).ok_or(Error::MerkleRootMismatch)?;
+
+        if bytes.windows(MERGED_MINING_HEADER.len()).any(|w| w == &MERGED_MINING_HEADER[..]) {
+            let header = find_merged_mining_header(script).ok_or(Error::MissingMergedMiningHeader)?;
+            if header + MERGED_MINING_HEADER.len() != pos {
+                return Err(Error::MerkleRootNotAfterHeader);
+            }
+        } else {
+            if pos > MAX_LEGACY_ROOT_OFFSET {
+                return Err(Error::LegacyMerkleRootTooLate(pos));
+            }
+            if bytes.len() - pos < 32 + 8 {
+                return Err(Error::MissingMergedMiningHeader);
+            }
+        }
+        Ok(&bytes[pos + 32..pos + 40])
+    }
+}
+
+/// The slot of chain `chain_id` in a merged-mining tree of height `merkle_height`
+/// built with nonce `nonce`. Fixing the slot means a miner cannot place the same
+/// chain at several slots and grind through different blocks at once. A tree
+/// of 32 or more levels has more slots than a `u32` can index, so gives `None`.
+pub fn get_expected_index(nonce: u32, chain_id: u32, merkle_height: u32) -> Option<u32> {
+    // A linear congruential generator, seeded with the nonce and chain ID
+    let mut rand = nonce;
+    rand = rand.wrapping_mul(1103515245).wrapping_add(12345);
+    rand = rand.wrapping_add(chain_id);
+    rand = rand.wrapping_mul(1103515245).wrapping_add(12345);
+    1u32.checked_shl(merkle_height).map(|size| rand % size)
+}
+
+/// Recomputes a merkle root from a leaf and the branch linking it to the root,
+/// following the Namecoin merged-mining convention: bit `i` of `index` (least
+/// significant first) set means the `i`-th branch hash is on the left.
+pub fn verify_merkle_branch(hash: sha256d::Hash, branch: &[sha256d::Hash], index: u32) -> sha256d::Hash {
+    let mut hash = hash;
+    let mut index = index;
+    for node in branch {
+        let mut engine = sha256d::Hash::engine();
+        if index & 1 == 1 {
+            engine.input(&node[..]);
+            engine.input(&hash[..]);
+        } else {
+            engine.input(&hash[..]);
+            engine.input(&node[..]);
+        }
+        hash = sha256d::Hash::from_engine(engine);
+        index >>= 1;
+    }
+    hash
+}
+
+/// Locates the merged-mining magic in a parent coinbase scriptSig, returning
+/// the offset of the magic bytes. The magic must appear exactly once, and be
+/// followed by room for the 32-byte merkle root, 4-byte tree size and 4-byte
+/// nonce; otherwise a second commitment could be smuggled into the coinbase.
+pub fn find_merged_mining_header(coinbase_script: &Script) -> Option<usize> {
+    let script = coinbase_script.as_bytes();
+    let mut positions = script.windows(MERGED_MINING_HEADER.len())
+        .enumerate()
+        .filter(|&(_, w)| w == &MERGED_MINING_HEADER[..])
+        .map(|(pos, _)| pos);
+
+    let pos = positions.next()?;
+    if positions.next().is_some() {
+        return None;
+    }
+    if script.len() - pos < MERGED_MINING_HEADER.len() + 32 + 8 {
+        return None;
+    }
+    Some(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin_hashes::hex::FromHex;
+    use bitcoin_hashes::sha256d;
+
+    use blockdata::script::Builder;
+    use consensus::encode::deserialize;
+    use network::constants::Network;
+    use util::hash::BitcoinHash;
+    use super::*;
+
+    // A hand-built auxpow, not taken from the chain, of a block on chain 14
+    // merge-mined with one other chain under merged-mining nonce 7, which puts
+    // chain 14 at slot 1
+    fn aux_pow() -> AuxPow {
+        let coinbase: Transaction = deserialize(&::hex::decode("01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff380320a1072cfabe6d6da29a10cbaebc3003653932be477f93031b6cfa06d87ae58a0f50dad241da62c60200000007000000062f706f6f6c2fffffffff0100f2052a01000000015100000000").unwrap()).unwrap();
+        let other_tx = sha256d::Hash::from_hex("3f5e156d388e31205e46f689e1928c092be27e1d4e0d74e3ba9077bc201d4d17").unwrap();
+        let parent = BaseHeader {
+            version: 2,
+            prev_blockhash: Default::default(),
+            merkle_root: verify_merkle_branch(coinbase.txid(), &[other_tx], 0),
+            time: 1500000000,
+            bits: 0x1d00ffff,
+            nonce: 148,
+        };
+        AuxPow {
+            coinbase_txn: coinbase,
+            block_hash: Default::default(),
+            coinbase_branch_hashes: vec![other_tx],
+            coinbase_branch_side_mask: 0,
+            blockchain_branch_hashes: vec![sha256d::Hash::from_hex("6c8378a7b33aa81549806e7fcdffd7f4df35b37a2c4554f1a44be582b2713056").unwrap()],
+            blockchain_branch_side_mask: 1,
+            parent_block: parent,
+        }
+    }
+
+    fn coinbase_script(commitments: usize) -> Script {
+        let mut commitment = vec![];
+        for _ in 0..commitments {
+            commitment.extend(&MERGED_MINING_HEADER);
+            // merkle root, then tree size and nonce
+            commitment.extend(&[0x11; 32][..]);
+            commitment.extend(&[0x01, 0, 0, 0, 0, 0, 0, 0]);
+        }
+        Builder::new()
+            .push_int(500_000)
+            .push_slice(&commitment)
+            .push_slice(b"/pool/")
+            .into_script()
+    }
+
+    #[test]
+    fn test_verify_merkle_branch() {
+        // The transactions of Bitcoin block 100000
+        let txids: Vec<sha256d::Hash> = [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ].iter().map(|h| sha256d::Hash::from_hex(h).unwrap()).collect();
+        let left = sha256d::Hash::from_hex("ccdafb73d8dcd0173d5d5c3c9a0770d0b3953db889dab99ef05b1907518cb815").unwrap();
+        let right = sha256d::Hash::from_hex("8e30899078ca1813be036a073bbf80b86cdddde1c96e9e9c99e9e3782df4ae49").unwrap();
+        let root = sha256d::Hash::from_hex("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766").unwrap();
+
+        assert_eq!(verify_merkle_branch(txids[0], &[txids[1], right], 0), root);
+        assert_eq!(verify_merkle_branch(txids[1], &[txids[0], right], 1), root);
+        assert_eq!(verify_merkle_branch(txids[2], &[txids[3], left], 2), root);
+        assert_eq!(verify_merkle_branch(txids[3], &[txids[2], left], 3), root);
+
+        // the wrong side gives a different root
+        assert!(verify_merkle_branch(txids[2], &[txids[3], left], 0) != root);
+        // an empty branch is the leaf itself
+        assert_eq!(verify_merkle_branch(root, &[], 0), root);
+    }
+
+    #[test]
+    fn test_check_coinbase_commitment() {
+        let aux_pow = aux_pow();
+        let aux_hash = sha256d::Hash::from_hex("c3bba57a058de7b1da6d29b36622e9237220f980bbcfadb7c5ff3116a560242f").unwrap();
+        assert_eq!(aux_pow.check_coinbase_commitment(aux_hash, 14, 1, 7), Ok(()));
+        assert_eq!(aux_pow.check(aux_hash, 14, &Params::new(Network::Bitcoin)), Ok(()));
+
+        assert_eq!(aux_pow.check_coinbase_commitment(Default::default(), 14, 1, 7), Err(Error::MerkleRootMismatch));
+        assert_eq!(aux_pow.check_coinbase_commitment(aux_hash, 14, 2, 7), Err(Error::MerkleSizeMismatch));
+        assert_eq!(aux_pow.check_coinbase_commitment(aux_hash, 14, 1, 8), Err(Error::MerkleNonceMismatch));
+        // nonce 7 puts chain 15 at slot 0
+        assert_eq!(aux_pow.check_coinbase_commitment(aux_hash, 15, 1, 7), Err(Error::WrongChainIndex));
+
+        let mut no_input = aux_pow.clone();
+        no_input.coinbase_txn.input.clear();
+        assert_eq!(no_input.check_coinbase_commitment(aux_hash, 14, 1, 7), Err(Error::NoCoinbaseInput));
+        let mut no_commitment = aux_pow.clone();
+        no_commitment.coinbase_txn.input[0].script_sig = coinbase_script(0);
+        assert_eq!(no_commitment.check_coinbase_commitment(aux_hash, 14, 1, 7), Err(Error::MerkleRootMismatch));
+    }
+
+    #[test]
+    fn test_check_legacy_commitment() {
+        let aux_hash = sha256d::Hash::from_hex("c3bba57a058de7b1da6d29b36622e9237220f980bbcfadb7c5ff3116a560242f").unwrap();
+        let root = ::hex::decode("a29a10cbaebc3003653932be477f93031b6cfa06d87ae58a0f50dad241da62c6").unwrap();
+        let size_and_nonce = [0x02, 0, 0, 0, 0x07, 0, 0, 0];
+        let with_script = |script: Script| {
+            let mut aux_pow = aux_pow();
+            aux_pow.coinbase_txn.input[0].script_sig = script;
+            aux_pow.parent_block.merkle_root = verify_merkle_branch(aux_pow.coinbase_txn.txid(), &aux_pow.coinbase_branch_hashes, 0);
+            aux_pow
+        };
+        let legacy = |prefix: &[u8], size_and_nonce: &[u8]| {
+            let mut data = prefix.to_vec();
+            data.extend(&root);
+            data.extend(size_and_nonce);
+            with_script(Builder::new().push_slice(&data).into_script())
+        };
+
+        // without the magic, the root may start in the first 20 bytes
+        let aux_pow = legacy(&[], &size_and_nonce);
+        assert_eq!(aux_pow.check_coinbase_commitment(aux_hash, 14, 1, 7), Ok(()));
+        assert_eq!(aux_pow.check(aux_hash, 14, &Params::new(Network::Bitcoin)), Ok(()));
+        assert_eq!(aux_pow.check_coinbase_commitment(aux_hash, 14, 1, 8), Err(Error::MerkleNonceMismatch));
+        // after the push opcode
+        assert_eq!(legacy(&[0x33; 19], &size_and_nonce).check_coinbase_commitment(aux_hash, 14, 1, 7), Ok(()));
+        assert_eq!(legacy(&[0x33; 20], &size_and_nonce).check_coinbase_commitment(aux_hash, 14, 1, 7),
+                   Err(Error::LegacyMerkleRootTooLate(21)));
+        assert_eq!(legacy(&[], &size_and_nonce[..4]).check_coinbase_commitment(aux_hash, 14, 1, 7),
+                   Err(Error::MissingMergedMiningHeader));
+
+        // with the magic, the root must follow it, wherever it is
+        let mut data = MERGED_MINING_HEADER.to_vec();
+        data.extend(&[0x44; 40][..]);
+        let mut commitment = root.clone();
+        commitment.extend(&size_and_nonce);
+        let aux_pow = with_script(Builder::new().push_slice(&commitment).push_slice(&data).into_script());
+        assert_eq!(aux_pow.check_coinbase_commitment(aux_hash, 14, 1, 7), Err(Error::MerkleRootNotAfterHeader));
+        // a second magic is never allowed
+        let mut data = MERGED_MINING_HEADER.to_vec();
+        data.extend(&commitment);
+        data.extend(&MERGED_MINING_HEADER);
+        let aux_pow = with_script(Builder::new().push_slice(&data).push_slice(&[0x44; 40][..]).into_script());
+        assert_eq!(aux_pow.check_coinbase_commitment(aux_hash, 14, 1, 7), Err(Error::MissingMergedMiningHeader));
+    }
+
+    #[test]
+    fn test_get_expected_index() {
+        assert_eq!(get_expected_index(7, 14, 1), Some(1));
+        assert_eq!(get_expected_index(7, 15, 1), Some(0));
+        assert_eq!(get_expected_index(7, 14, 3), Some(3));
+        assert_eq!(get_expected_index(7, 10, 3), Some(7));
+        assert_eq!(get_expected_index(0, 14, 4), Some(4));
+        assert_eq!(get_expected_index(12345, 1, 8), Some(76));
+        // the arithmetic wraps rather than overflowing
+        assert_eq!(get_expected_index(0xffffffff, 14, 30), Some(705807883));
+        // a single chain always sits at the root
+        assert_eq!(get_expected_index(7, 14, 0), Some(0));
+        // the largest tree whose slots fit a u32, and the first too tall
+        assert_eq!(get_expected_index(7, 14, 31).map(|idx| idx < (1 << 31)), Some(true));
+        assert_eq!(get_expected_index(7, 14, 32), None);
+        assert_eq!(get_expected_index(7, 14, u32::max_value()), None);
+    }
+
+    #[test]
+    fn test_check_parent() {
+        let aux_pow = aux_pow();
+        let aux_hash = sha256d::Hash::from_hex("c3bba57a058de7b1da6d29b36622e9237220f980bbcfadb7c5ff3116a560242f").unwrap();
+        let params = Params::new(Network::Bitcoin);
+
+        let mut bad = aux_pow.clone();
+        bad.parent_block.version |= 14 * VERSION_CHAIN_START;
+        assert_eq!(bad.check(aux_hash, 14, &params), Err(Error::ParentHasOurChainId));
+        // testnet does not enforce distinct chain IDs
+        assert_eq!(bad.check(aux_hash, 14, &Params::new(Network::Testnet)), Ok(()));
+
+        let mut bad = aux_pow.clone();
+        bad.coinbase_branch_side_mask = 1;
+        assert_eq!(bad.check(aux_hash, 14, &params), Err(Error::NotCoinbase));
+        let mut bad = aux_pow.clone();
+        bad.parent_block.merkle_root = Default::default();
+        assert_eq!(bad.check(aux_hash, 14, &params), Err(Error::CoinbaseMerkleRootMismatch));
+        let mut bad = aux_pow.clone();
+        bad.blockchain_branch_hashes = vec![Default::default(); 31];
+        assert_eq!(bad.check(aux_hash, 14, &params), Err(Error::ChainMerkleBranchTooLong(31)));
+    }
+
+    #[test]
+    fn test_find_merged_mining_header() {
+        // height push is 4 bytes, then the push opcode of the commitment
+        assert_eq!(find_merged_mining_header(&coinbase_script(1)), Some(5));
+        // no magic at all
+        assert_eq!(find_merged_mining_header(&Builder::new().push_int(500_000).into_script()), None);
+        // a second magic could carry a different commitment
+        assert_eq!(find_merged_mining_header(&coinbase_script(2)), None);
+    }
+
+    #[test]
+    fn test_find_merged_mining_header_bounds() {
+        // the magic must be followed by 40 bytes of commitment data
+        let magic_then = |n: usize| {
+            let mut data = MERGED_MINING_HEADER.to_vec();
+            data.extend(vec![0x22; n]);
+            Script::from(data)
+        };
+        assert_eq!(find_merged_mining_header(&magic_then(40)), Some(0));
+        assert_eq!(find_merged_mining_header(&magic_then(39)), None);
+        assert_eq!(find_merged_mining_header(&magic_then(0)), None);
+    }
+
+    #[test]
+    fn test_from_slice() {
+        // the hand-built `aux_pow()` serialized, not captured from the chain
+        let data = ::hex::decode("01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff380320a1072cfabe6d6da29a10cbaebc3003653932be477f93031b6cfa06d87ae58a0f50dad241da62c60200000007000000062f706f6f6c2fffffffff0100f2052a01000000015100000000000000000000000000000000000000000000000000000000000000000000000001174d1d20bc7790bae3740d4e1d7ee22b098c92e189f6465e20318e386d155e3f0000000001563071b282e54ba4f154452c7ab335dff4d7ffcd7f6e804915a83ab3a778836c01000000020000000000000000000000000000000000000000000000000000000000000000000000e657a87e3f8b4649ae9b5759ec12166c967bbd1ce1b937bde7fe327275944b04002f6859ffff001d94000000").unwrap();
+        let decoded = AuxPow::from_slice(&data).unwrap();
+        assert_eq!(decoded, aux_pow());
+        assert_eq!(decoded.coinbase_txn.txid().to_string(), "754bbc8245480c45eae844664eadb974bdab1e21ddcd0ff2e41e1980ac2ec8ea");
+        assert_eq!(decoded.block_hash, Default::default());
+        assert_eq!(decoded.coinbase_branch_hashes.len(), 1);
+        assert_eq!(decoded.coinbase_branch_side_mask, 0);
+        assert_eq!(decoded.blockchain_branch_hashes.len(), 1);
+        assert_eq!(decoded.blockchain_branch_side_mask, 1);
+        assert_eq!(decoded.parent_block.bitcoin_hash().to_string(), "1146ce84acc23f08ce553c358b986d777b9746eb18a0f593d680f24ae35a625a");
+        assert_eq!(decoded.parent_block.merkle_root, verify_merkle_branch(decoded.coinbase_txn.txid(), &decoded.coinbase_branch_hashes, 0));
+        let aux_hash = sha256d::Hash::from_hex("c3bba57a058de7b1da6d29b36622e9237220f980bbcfadb7c5ff3116a560242f").unwrap();
+        assert_eq!(decoded.check_coinbase_commitment(aux_hash, 14, 1, 7), Ok(()));
+
+        let mut trailing = data.clone();
+        trailing.push(0);
+        match AuxPow::from_slice(&trailing) {
+            Err(encode::Error::ParseFailed(_)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(AuxPow::from_slice(&data[..data.len() - 1]).is_err());
+    }
+}
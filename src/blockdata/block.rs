@@ -20,17 +20,96 @@
 //! these blocks and the blockchain.
 //!
 
-use bitcoin_hashes::{sha256d, Hash};
+use std::{error, fmt};
 
+use bitcoin_hashes::{sha256d, Hash, HashEngine};
+use byteorder::{ByteOrder, LittleEndian};
+
+use blockdata::auxpow::AuxPow;
 use blockdata::constants::max_target;
-use blockdata::transaction::Transaction;
-use consensus::encode::VarInt;
+use blockdata::transaction::{self, Transaction};
+use blockdata::auxpow;
+use consensus::encode::{self, Encodable, Decodable, Encoder, Decoder, VarInt};
+use consensus::params::Params;
 use network::constants::Network;
 use util;
-use util::hash::BitcoinHash;
+use util::hash::{bitcoin_merkle_root, BitcoinHash, MerkleRoot};
+use util::scrypt;
 use util::uint::Uint256;
 use util::Error::{SpvBadProofOfWork, SpvBadTarget};
 
+/// Version bit flagging that a block header is followed by an auxpow
+pub const VERSION_AUXPOW: u32 = 1 << 8;
+
+/// The chain ID occupies the version bits from this one upwards
+pub const VERSION_CHAIN_START: u32 = 1 << 16;
+
+/// The largest weight a block may have, as defined by BIP141
+pub const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+
+/// The most legacy signature operations a block may have, as defined by
+/// Bitcoin Core's `MAX_BLOCK_SIGOPS_COST` of 80000 at a scale of 4
+pub const MAX_BLOCK_LEGACY_SIGOPS: usize = 20_000;
+
+/// The number of previous blocks the DarkGravityWave retarget averages over
+pub const DGW_PAST_BLOCKS: usize = 24;
+
+/// A reason a block is invalid regardless of the chain it is on
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Error {
+    /// The block has no transactions
+    NoTransactions,
+    /// The first transaction is not a coinbase
+    FirstTransactionNotCoinbase,
+    /// A transaction other than the first is a coinbase
+    MultipleCoinbases,
+    /// The merkle root in the header does not commit to the transactions
+    BadMerkleRoot,
+    /// The transactions repeat in a way that leaves the merkle root
+    /// unchanged, so the same header commits to an invalid block (CVE-2012-2459)
+    MutatedMerkleTree,
+    /// The weight of the block is above `MAX_BLOCK_WEIGHT`
+    Oversized(u64),
+    /// The transaction at this index fails a context-free check
+    BadTransaction(usize, transaction::Error),
+    /// The block has this many legacy signature operations, above
+    /// `MAX_BLOCK_LEGACY_SIGOPS`
+    TooManySigops(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Oversized(weight) => write!(f, "block weight {} too large", weight),
+            Error::BadTransaction(idx, ref e) => write!(f, "transaction {} invalid: {}", idx, e),
+            Error::TooManySigops(count) => write!(f, "block has {} sigops, too many", count),
+            _ => f.write_str(error::Error::description(self)),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::BadTransaction(_, ref e) => Some(e),
+            _ => None,
+        }
+    }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::NoTransactions => "block has no transactions",
+            Error::FirstTransactionNotCoinbase => "first transaction in block is not a coinbase",
+            Error::MultipleCoinbases => "more than one coinbase in block",
+            Error::BadMerkleRoot => "block merkle root does not match its transactions",
+            Error::MutatedMerkleTree => "block has duplicate transactions",
+            Error::Oversized(_) => "block weight too large",
+            Error::BadTransaction(..) => "block has an invalid transaction",
+            Error::TooManySigops(_) => "block has too many sigops",
+        }
+    }
+}
+
 /// A block header, which contains all the block's information except
 /// the actual transactions
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -48,32 +127,8 @@ pub struct BlockHeader {
     pub bits: u32,
     /// The nonce, selected to obtain a low enough blockhash
     pub nonce: u32,
-    ///	Coinbase transaction that is in the parent block, linking the AuxPOW block to its parent block
-    pub coinbase_txn: Transaction,
-    /// Hash of the parent_block header
-    pub block_hash: sha256d::Hash,
-    /// The merkle branch linking the coinbase_txn to the parent block's merkle_root
-    pub coinbase_branch_hashes: Vec<sha256d::Hash>,
-    /// Bitmask of which side of the merkle hash function the branch_hash element should go on. Zero means it goes on the right, One means on the left. It is equal to the index of the starting hash within the widest level of the merkle tree for this merkle branch.
-    pub coinbase_branch_side_mask: u32,
-    /// The merkle branch linking this auxiliary blockchain to the others, when used in a merged mining setup with multiple auxiliary chains
-    pub blockchain_branch_hashes: Vec<sha256d::Hash>,
-    /// Bitmask of which side of the merkle hash function the branch_hash element should go on. Zero means it goes on the right, One means on the left. It is equal to the index of the starting hash within the widest level of the merkle tree for this merkle branch.
-    pub blockchain_branch_side_mask: u32,
-    /// Parent block header
-    /// The protocol version. Should always be 1.
-    pub parent_version: u32,
-    /// Reference to the previous block in the chain
-    pub parent_prev_blockhash: sha256d::Hash,
-    /// The root hash of the merkle tree of transactions in the block
-    pub parent_merkle_root: sha256d::Hash,
-    /// The timestamp of the block, as claimed by the miner
-    pub parent_time: u32,
-    /// The target value below which the blockhash must lie, encoded as a
-    /// a float (with well-defined rounding, of course)
-    pub parent_bits: u32,
-    /// The nonce, selected to obtain a low enough blockhash
-    pub parent_nonce: u32,
+    /// The auxiliary proof-of-work, present when the version flags it
+    pub aux_pow: Option<AuxPow>,
 }
 
 /// A Bitcoin block, which is a collection of transactions with an attached
@@ -122,21 +177,36 @@ pub struct BaseBlock {
     pub txdata: Vec<Transaction>,
 }
 impl BlockHeader {
+    /// The merged-mining chain ID encoded in the high bits of the version
+    pub fn chain_id(&self) -> u32 {
+        self.version / VERSION_CHAIN_START
+    }
+
+    /// Whether the version flags this block as carrying an auxpow
+    pub fn is_auxpow(&self) -> bool {
+        self.version & VERSION_AUXPOW != 0
+    }
+
     /// Computes the target [0, T] that a blockhash must land in to be valid
     pub fn target(&self) -> Uint256 {
+        BlockHeader::u256_from_compact_target(self.bits)
+    }
+
+    /// Decodes a target in the "compact" form of the `bits` field
+    fn u256_from_compact_target(bits: u32) -> Uint256 {
         // This is a floating-point "compact" encoding originally used by
         // OpenSSL, which satoshi put into consensus code, so we're stuck
         // with it. The exponent needs to have 3 subtracted from it, hence
         // this goofy decoding code:
         let (mant, expt) = {
-            let unshifted_expt = self.bits >> 24;
+            let unshifted_expt = bits >> 24;
             if unshifted_expt <= 3 {
                 (
-                    (self.bits & 0xFFFFFF) >> (8 * (3 - unshifted_expt as usize)),
+                    (bits & 0xFFFFFF) >> (8 * (3 - unshifted_expt as usize)),
                     0,
                 )
             } else {
-                (self.bits & 0xFFFFFF, 8 * ((self.bits >> 24) - 3))
+                (bits & 0xFFFFFF, 8 * ((bits >> 24) - 3))
             }
         };
 
@@ -171,12 +241,76 @@ impl BlockHeader {
         (max_target(network) / self.target()).low_u64()
     }
 
+    /// The `bits` a block at `height` with timestamp `time` must have under
+    /// Viacoin's retarget, DarkGravityWave v3, which retargets every block.
+    /// `prev_headers` are the headers before it, oldest first, and must end
+    /// with at least the last `DGW_PAST_BLOCKS` headers of the chain, or `None`
+    /// is returned.
+    ///
+    /// The target is the running average DarkGravityWave keeps of the last 24
+    /// targets, scaled by the time those blocks took against 24 target
+    /// spacings, clamped to between a third and three times as long, and
+    /// capped at the proof-of-work limit. Before there are 24 blocks after
+    /// genesis to average, the limit is used.
+    ///
+    /// On networks allowing minimum-difficulty blocks, a block more than two
+    /// spacings after the previous one must use the limit.
+    pub fn next_work_required(prev_headers: &[BlockHeader], time: u32, height: u32, params: &Params) -> Option<u32> {
+        let limit_bits = BlockHeader::compact_target_from_u256(&params.pow_limit);
+        if height as usize <= DGW_PAST_BLOCKS {
+            return Some(limit_bits);
+        }
+        if prev_headers.len() < DGW_PAST_BLOCKS {
+            return None;
+        }
+        let last = &prev_headers[prev_headers.len() - 1];
+        if params.no_pow_retargeting {
+            return Some(last.bits);
+        }
+        if params.allow_min_difficulty_blocks && time as u64 > last.time as u64 + 2 * params.pow_target_spacing {
+            return Some(limit_bits);
+        }
+
+        // Not quite an average: each step weighs the earlier ones by one too
+        // many, but it is what consensus computes
+        let past = &prev_headers[prev_headers.len() - DGW_PAST_BLOCKS..];
+        let mut average = last.target();
+        for (count, header) in past.iter().rev().enumerate().skip(1) {
+            let count = count as u32 + 1;
+            average = (average.mul_u32(count) + header.target()) / Uint256::from_u64(count as u64 + 1).unwrap();
+        }
+
+        let target_timespan = DGW_PAST_BLOCKS as i64 * params.pow_target_spacing as i64;
+        let mut actual_timespan = last.time as i64 - past[0].time as i64;
+        if actual_timespan < target_timespan / 3 {
+            actual_timespan = target_timespan / 3;
+        }
+        if actual_timespan > target_timespan * 3 {
+            actual_timespan = target_timespan * 3;
+        }
+
+        let mut target = average.mul_u32(actual_timespan as u32) / Uint256::from_u64(target_timespan as u64).unwrap();
+        if target > params.pow_limit {
+            target = params.pow_limit;
+        }
+        Some(BlockHeader::compact_target_from_u256(&target))
+    }
+
+    /// Checks that the `bits` of this header, at `height`, are those Viacoin's
+    /// retarget requires after `prev_headers`, as computed by
+    /// [`next_work_required`](#method.next_work_required). Without enough
+    /// previous headers to compute the retarget, the check fails.
+    pub fn check_difficulty_transition(&self, prev_headers: &[BlockHeader], params: &Params, height: u32) -> Result<(), util::Error> {
+        match BlockHeader::next_work_required(prev_headers, self.time, height, params) {
+            Some(bits) if bits == self.bits => Ok(()),
+            _ => Err(SpvBadTarget),
+        }
+    }
+
     /// Performs an SPV validation of a block, which confirms that the proof-of-work
     /// is correct, but does not verify that the transactions are valid or encoded
     /// correctly.
     pub fn spv_validate(&self, required_target: &Uint256) -> Result<(), util::Error> {
-        use byteorder::{ByteOrder, LittleEndian};
-
         let target = &self.target();
         if target != required_target {
             return Err(SpvBadTarget);
@@ -192,6 +326,11 @@ impl BlockHeader {
         }
     }
 
+    /// The scrypt hash of the header, ignoring any auxpow
+    pub fn scrypt_hash(&self) -> [u8; 32] {
+        BaseHeader::from(self).scrypt_hash()
+    }
+
     /// Returns the total work of the block
     pub fn work(&self) -> Uint256 {
         // 2**256 / (target + 1) == ~target / (target+1) + 1    (eqn shamelessly stolen from bitcoind)
@@ -203,6 +342,124 @@ impl BlockHeader {
         ret
     }
 }
+impl BaseHeader {
+    /// The scrypt hash of the header, which is what its proof-of-work is measured by
+    pub fn scrypt_hash(&self) -> [u8; 32] {
+        use consensus::encode::serialize;
+        scrypt::scrypt_1024_1_1_256(&serialize(self))
+    }
+
+    /// Checks that the scrypt hash of the header lies below `target`
+    fn check_scrypt_pow(&self, target: &Uint256) -> Result<(), util::Error> {
+        let mut ret = [0u64; 4];
+        LittleEndian::read_u64_into(&self.scrypt_hash(), &mut ret);
+        if Uint256(ret) <= *target {
+            Ok(())
+        } else {
+            Err(SpvBadProofOfWork)
+        }
+    }
+}
+
+impl Block {
+    /// Checks the proof-of-work of the block against the target in its header.
+    /// For a merge-mined block this is the scrypt hash of the parent block,
+    /// which must also commit to this block on our chain; otherwise it is the
+    /// scrypt hash of the block's own header.
+    pub fn check_auxpow(&self, params: &Params) -> Result<(), util::Error> {
+        let header = &self.header;
+        let target = header.target();
+        match header.aux_pow {
+            None if header.is_auxpow() => Err(auxpow::Error::MissingAuxPow.into()),
+            None => BaseHeader::from(header).check_scrypt_pow(&target),
+            Some(_) if !header.is_auxpow() => Err(auxpow::Error::UnexpectedAuxPow.into()),
+            Some(ref aux_pow) => {
+                if params.strict_chain_id && header.chain_id() != params.auxpow_chain_id {
+                    return Err(auxpow::Error::WrongChainId(header.chain_id()).into());
+                }
+                aux_pow.check(header.bitcoin_hash(), header.chain_id(), params)?;
+                aux_pow.parent_block.check_scrypt_pow(&target)
+            }
+        }
+    }
+
+    /// Gets the weight of the block, as defined by BIP141. Any auxpow counts
+    /// as part of the header.
+    pub fn get_weight(&self) -> u64 {
+        let header_size = encode::serialize(&self.header).len() as u64 +
+            VarInt(self.txdata.len() as u64).encoded_length();
+        4 * header_size + self.txdata.iter().map(Transaction::get_weight).sum::<u64>()
+    }
+
+    /// Performs the checks of a block that do not depend on the chain. The
+    /// header is checked first, so a block without the work costs no more
+    /// than hashing it: a target no easier than
+    /// the proof-of-work limit and a proof-of-work as described at
+    /// `check_auxpow`. The block must then have a coinbase first and no
+    /// other, a merkle root committing to its transactions without
+    /// duplicates, a weight within `MAX_BLOCK_WEIGHT`, transactions with
+    /// inputs and outputs, no null or duplicate prevouts, output values
+    /// within the money supply and a coinbase scriptSig of 2 to 100 bytes,
+    /// and no more than `MAX_BLOCK_LEGACY_SIGOPS`. Scripts are not executed.
+    pub fn check(&self, params: &Params) -> Result<(), util::Error> {
+        let target = self.header.target();
+        if target == Uint256::from_u64(0).unwrap() || target > params.pow_limit {
+            return Err(SpvBadTarget);
+        }
+        self.check_auxpow(params)?;
+
+        if self.txdata.is_empty() {
+            return Err(Error::NoTransactions.into());
+        }
+        if !self.txdata[0].is_coin_base() {
+            return Err(Error::FirstTransactionNotCoinbase.into());
+        }
+        if self.txdata[1..].iter().any(Transaction::is_coin_base) {
+            return Err(Error::MultipleCoinbases.into());
+        }
+        if self.merkle_root() != self.header.merkle_root {
+            return Err(Error::BadMerkleRoot.into());
+        }
+        if self.has_mutated_merkle_tree() {
+            return Err(Error::MutatedMerkleTree.into());
+        }
+        let weight = self.get_weight();
+        if weight > MAX_BLOCK_WEIGHT {
+            return Err(Error::Oversized(weight).into());
+        }
+        for (idx, tx) in self.txdata.iter().enumerate() {
+            tx.check_inputs_outputs()
+                .and_then(|_| tx.check_duplicate_inputs())
+                .and_then(|_| tx.check_money_range(params.network))
+                .and_then(|_| tx.check_coinbase_script_size())
+                .map_err(|e| Error::BadTransaction(idx, e))?;
+        }
+        let sigops = self.txdata.iter().map(Transaction::legacy_sigop_count).sum::<usize>();
+        if sigops > MAX_BLOCK_LEGACY_SIGOPS {
+            return Err(Error::TooManySigops(sigops).into());
+        }
+        Ok(())
+    }
+
+    /// Whether some level of the merkle tree pairs a node with an identical
+    /// sibling, as happens when trailing transactions are repeated
+    fn has_mutated_merkle_tree(&self) -> bool {
+        let mut level: Vec<sha256d::Hash> = self.txdata.iter().map(Transaction::txid).collect();
+        while level.len() > 1 {
+            if level.chunks(2).any(|pair| pair.len() == 2 && pair[0] == pair[1]) {
+                return true;
+            }
+            level = level.chunks(2).map(|pair| {
+                let mut engine = sha256d::Hash::engine();
+                engine.input(&pair[0][..]);
+                engine.input(&pair[pair.len() - 1][..]);
+                sha256d::Hash::from_engine(engine)
+            }).collect();
+        }
+        false
+    }
+}
+
 impl<'a> From<&'a BlockHeader> for BaseHeader {
     fn from(item: &BlockHeader) -> Self {
         BaseHeader {
@@ -227,6 +484,12 @@ impl BitcoinHash for BlockHeader {
         base_header.bitcoin_hash()
     }
 }
+
+impl MerkleRoot for Block {
+    fn merkle_root(&self) -> sha256d::Hash {
+        bitcoin_merkle_root(self.txdata.iter().map(|obj| obj.txid()).collect())
+    }
+}
 impl BitcoinHash for BaseBlock {
     fn bitcoin_hash(&self) -> sha256d::Hash {
         self.header.bitcoin_hash()
@@ -239,27 +502,41 @@ impl BitcoinHash for Block {
     }
 }
 
-impl_consensus_encoding!(
-    BlockHeader,
-    version,
-    prev_blockhash,
-    merkle_root,
-    time,
-    bits,
-    nonce,
-    coinbase_txn,
-    block_hash,
-    coinbase_branch_hashes,
-    coinbase_branch_side_mask,
-    blockchain_branch_hashes,
-    blockchain_branch_side_mask,
-    parent_version,
-    parent_prev_blockhash,
-    parent_merkle_root,
-    parent_time,
-    parent_bits,
-    parent_nonce
-);
+impl<S: Encoder> Encodable<S> for BlockHeader {
+    fn consensus_encode(&self, s: &mut S) -> Result<(), encode::Error> {
+        self.version.consensus_encode(s)?;
+        self.prev_blockhash.consensus_encode(s)?;
+        self.merkle_root.consensus_encode(s)?;
+        self.time.consensus_encode(s)?;
+        self.bits.consensus_encode(s)?;
+        self.nonce.consensus_encode(s)?;
+        match self.aux_pow {
+            Some(ref aux_pow) if self.is_auxpow() => aux_pow.consensus_encode(s),
+            None if !self.is_auxpow() => Ok(()),
+            Some(_) => Err(encode::Error::ParseFailed("auxpow present but flag not set")),
+            None => Err(encode::Error::ParseFailed("auxpow flag set but no auxpow present")),
+        }
+    }
+}
+
+impl<D: Decoder> Decodable<D> for BlockHeader {
+    fn consensus_decode(d: &mut D) -> Result<BlockHeader, encode::Error> {
+        let mut header = BlockHeader {
+            version: Decodable::consensus_decode(d)?,
+            prev_blockhash: Decodable::consensus_decode(d)?,
+            merkle_root: Decodable::consensus_decode(d)?,
+            time: Decodable::consensus_decode(d)?,
+            bits: Decodable::consensus_decode(d)?,
+            nonce: Decodable::consensus_decode(d)?,
+            aux_pow: None,
+        };
+        if header.is_auxpow() {
+            header.aux_pow = Some(Decodable::consensus_decode(d)?);
+        }
+        Ok(header)
+    }
+}
+
 impl_consensus_encoding!(Block, header, txdata);
 impl_consensus_encoding!(
     BaseHeader,
@@ -277,9 +554,20 @@ impl_consensus_encoding!(LoneBlockHeader, header, tx_count);
 mod tests {
     use hex::decode as hex_decode;
 
-    use blockdata::block::{Block, BlockHeader, BaseHeader};
+    use bitcoin_hashes::hex::FromHex;
+    use bitcoin_hashes::sha256d;
+
+    use blockdata::auxpow::{self, verify_merkle_branch, AuxPow};
+    use blockdata::block::{self, Block, BlockHeader, BaseHeader, MAX_BLOCK_LEGACY_SIGOPS, MAX_BLOCK_WEIGHT, VERSION_AUXPOW, VERSION_CHAIN_START};
+    use blockdata::constants::{genesis_block, max_money};
+    use blockdata::opcodes;
+    use blockdata::script::Script;
+    use blockdata::transaction::{self, OutPoint, Transaction, TxIn, TxOut};
     use consensus::encode::{deserialize, serialize};
-    use util::hash::MerkleRoot;
+    use consensus::params::Params;
+    use network::constants::Network;
+    use util;
+    use util::hash::{BitcoinHash, MerkleRoot};
 
 //    #[test]
 //    fn block_test() {
@@ -336,6 +624,188 @@ mod tests {
 //        assert_eq!(serialize(&real_decode), segwit_block);
 //    }
 
+   #[test]
+   fn chain_id_test() {
+       let mut header = BlockHeader {
+           version: 2,
+           prev_blockhash: Default::default(),
+           merkle_root: Default::default(),
+           time: 0,
+           bits: 0x1e01ffff,
+           nonce: 0,
+           aux_pow: None,
+       };
+       assert_eq!(header.chain_id(), 0);
+       assert!(!header.is_auxpow());
+
+       // Viacoin's chain ID with the auxpow flag on a version 2 block
+       header.version = 0x000e0102;
+       assert_eq!(header.chain_id(), 14);
+       assert!(header.is_auxpow());
+
+       header.version = (14 * VERSION_CHAIN_START) | 2;
+       assert_eq!(header.chain_id(), 14);
+       assert!(!header.is_auxpow());
+   }
+
+   #[test]
+   fn auxpow_block_test() {
+       let coinbase: Transaction = deserialize(&hex_decode("01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0804ffff001d026e04ffffffff0100f2052a0100000043410446ef0102d1ec5240f0d061a4246c1bdef63fc3dbab7733052fbbf0ecd8f41fc26bf049ebb4f9527f374280259e7cfa99c48b0e3f39c51347a19a5819651503a5ac00000000").unwrap()).unwrap();
+       let parent = BaseHeader {
+           version: 2,
+           prev_blockhash: Default::default(),
+           merkle_root: coinbase.txid(),
+           time: 1500000000,
+           bits: 0x1b01ffff,
+           nonce: 42,
+       };
+       let aux_pow = AuxPow {
+           coinbase_txn: coinbase.clone(),
+           block_hash: parent.bitcoin_hash(),
+           coinbase_branch_hashes: vec![],
+           coinbase_branch_side_mask: 0,
+           blockchain_branch_hashes: vec![coinbase.txid()],
+           blockchain_branch_side_mask: 1,
+           parent_block: parent,
+       };
+       let mut block = Block {
+           header: BlockHeader {
+               version: (14 * VERSION_CHAIN_START) | VERSION_AUXPOW | 2,
+               prev_blockhash: Default::default(),
+               merkle_root: coinbase.txid(),
+               time: 1500000001,
+               bits: 0x1b01ffff,
+               nonce: 0,
+               aux_pow: Some(aux_pow.clone()),
+           },
+           txdata: vec![coinbase],
+       };
+
+       let data = serialize(&block);
+       assert_eq!(data.len(), 80 + serialize(&aux_pow).len() + 1 + serialize(&block.txdata[0]).len());
+       let decoded: Block = deserialize(&data).unwrap();
+       assert_eq!(decoded, block);
+       // the auxpow is not part of the block hash
+       assert_eq!(decoded.bitcoin_hash(), BaseHeader::from(&block.header).bitcoin_hash());
+       assert!(deserialize::<Block>(&data[..data.len() - 1]).is_err());
+
+       // without the flag the header is followed by the transactions directly
+       block.header.version = 2;
+       block.header.aux_pow = None;
+       let data = serialize(&block);
+       assert_eq!(data.len(), 80 + 1 + serialize(&block.txdata[0]).len());
+       assert_eq!(deserialize::<Block>(&data).unwrap(), block);
+   }
+
+   // A block on chain 14 merge-mined with one other chain, at an easy target
+   // the parent block's scrypt hash 000021b7...62ab meets
+   fn merge_mined_block() -> Block {
+       let coinbase: Transaction = deserialize(&hex_decode("01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff080301000003617578ffffffff0100f2052a01000000015100000000").unwrap()).unwrap();
+       let parent_coinbase: Transaction = deserialize(&hex_decode("01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff380320a1072cfabe6d6da29a10cbaebc3003653932be477f93031b6cfa06d87ae58a0f50dad241da62c60200000007000000062f706f6f6c2fffffffff0100f2052a01000000015100000000").unwrap()).unwrap();
+       let other_tx = sha256d::Hash::from_hex("3f5e156d388e31205e46f689e1928c092be27e1d4e0d74e3ba9077bc201d4d17").unwrap();
+       let other_chain = sha256d::Hash::from_hex("6c8378a7b33aa81549806e7fcdffd7f4df35b37a2c4554f1a44be582b2713056").unwrap();
+       let parent = BaseHeader {
+           version: 2,
+           prev_blockhash: Default::default(),
+           merkle_root: verify_merkle_branch(parent_coinbase.txid(), &[other_tx], 0),
+           time: 1500000000,
+           bits: 0x1d00ffff,
+           nonce: 148,
+       };
+       Block {
+           header: BlockHeader {
+               version: (14 * VERSION_CHAIN_START) | VERSION_AUXPOW | 2,
+               prev_blockhash: Default::default(),
+               merkle_root: coinbase.txid(),
+               time: 1500000001,
+               bits: 0x1f0fffff,
+               nonce: 0,
+               aux_pow: Some(AuxPow {
+                   coinbase_txn: parent_coinbase,
+                   block_hash: parent.bitcoin_hash(),
+                   coinbase_branch_hashes: vec![other_tx],
+                   coinbase_branch_side_mask: 0,
+                   blockchain_branch_hashes: vec![other_chain],
+                   blockchain_branch_side_mask: 1,
+                   parent_block: parent,
+               }),
+           },
+           txdata: vec![coinbase],
+       }
+   }
+
+   #[test]
+   fn check_auxpow_test() {
+       let params = Params::new(Network::Bitcoin);
+       let block = merge_mined_block();
+       assert_eq!(block.header.bitcoin_hash().to_string(), "c3bba57a058de7b1da6d29b36622e9237220f980bbcfadb7c5ff3116a560242f");
+       block.check_auxpow(&params).unwrap();
+
+       // the commitment covers our whole header
+       let mut bad = block.clone();
+       bad.header.bits = 0x1e00ffff;
+       match bad.check_auxpow(&params) {
+           Err(util::Error::AuxPow(auxpow::Error::MerkleRootMismatch)) => {},
+           other => panic!("unexpected {:?}", other),
+       }
+       // the parent proof-of-work must meet our target
+       let mut bad = block.clone();
+       bad.header.aux_pow.as_mut().unwrap().parent_block.nonce = 0;
+       match bad.check_auxpow(&params) {
+           Err(util::Error::SpvBadProofOfWork) => {},
+           other => panic!("unexpected {:?}", other),
+       }
+
+       // the branches must lead to the committed roots
+       let mut bad = block.clone();
+       bad.header.aux_pow.as_mut().unwrap().blockchain_branch_side_mask = 0;
+       match bad.check_auxpow(&params) {
+           Err(util::Error::AuxPow(auxpow::Error::MerkleRootMismatch)) => {},
+           other => panic!("unexpected {:?}", other),
+       }
+       let mut bad = block.clone();
+       bad.header.aux_pow.as_mut().unwrap().coinbase_branch_hashes.clear();
+       match bad.check_auxpow(&params) {
+           Err(util::Error::AuxPow(auxpow::Error::CoinbaseMerkleRootMismatch)) => {},
+           other => panic!("unexpected {:?}", other),
+       }
+
+       // the chain ID is only enforced where it is strict
+       let mut bad = block.clone();
+       bad.header.version = (15 * VERSION_CHAIN_START) | VERSION_AUXPOW | 2;
+       match bad.check_auxpow(&params) {
+           Err(util::Error::AuxPow(auxpow::Error::WrongChainId(15))) => {},
+           other => panic!("unexpected {:?}", other),
+       }
+       let mut bad = block.clone();
+       bad.header.version = 2;
+       match bad.check_auxpow(&params) {
+           Err(util::Error::AuxPow(auxpow::Error::UnexpectedAuxPow)) => {},
+           other => panic!("unexpected {:?}", other),
+       }
+       bad.header.version = (14 * VERSION_CHAIN_START) | VERSION_AUXPOW | 2;
+       bad.header.aux_pow = None;
+       match bad.check_auxpow(&params) {
+           Err(util::Error::AuxPow(auxpow::Error::MissingAuxPow)) => {},
+           other => panic!("unexpected {:?}", other),
+       }
+   }
+
+   #[test]
+   fn check_own_pow_test() {
+       let params = Params::new(Network::Bitcoin);
+       let mut block = merge_mined_block();
+       block.header.version = 2;
+       block.header.aux_pow = None;
+       match block.check_auxpow(&params) {
+           Err(util::Error::SpvBadProofOfWork) => {},
+           other => panic!("unexpected {:?}", other),
+       }
+       block.header.nonce = 1665;
+       assert_eq!(::hex::encode(&block.header.scrypt_hash()[..]), "561d343d6cf3de7858abd9fbf882f9c4abfdf416a595071135e26a7ded6a0100");
+       block.check_auxpow(&params).unwrap();
+   }
+
    #[test]
    fn compact_roundrtip_test() {
        let some_header = hex_decode("01000000000000000000000000000000000000000000000000000000000000000000000000522753002939c78659b4fdc6ed56c6b6aacdc7586facf2f6ada2012ed31703e61cc153ffff011ea1473d00").unwrap();
@@ -344,5 +814,210 @@ mod tests {
 
        assert_eq!(header.bits, BlockHeader::compact_target_from_u256(&header.target()));
    }
-}
 
+   #[test]
+   fn check_difficulty_transition_test() {
+       let params = Params::new(Network::Bitcoin);
+       let header = |time: u32, bits: u32| BlockHeader {
+           version: 1,
+           prev_blockhash: Default::default(),
+           merkle_root: Default::default(),
+           time: time,
+           bits: bits,
+           nonce: 0,
+           aux_pow: None,
+       };
+       let next = |headers: &[BlockHeader]| {
+           let time = headers[headers.len() - 1].time + 24;
+           BlockHeader::next_work_required(headers, time, 1000, &params)
+       };
+
+       // on schedule, the 23 intervals of the 24 blocks fall short of the
+       // 24 spacings DarkGravityWave expects, so it gets slightly harder
+       let steady: Vec<_> = (0..30).map(|i| header(1500000000 + 24 * i, 0x1c0e3a4b)).collect();
+       assert_eq!(next(&steady), Some(0x1c0da287));
+       // only the last 24 headers count
+       assert_eq!(next(&steady[6..]), Some(0x1c0da287));
+       assert_eq!(next(&steady[7..]), None);
+       let targets = [0x1c0e3a4b, 0x1c1a0000, 0x1b7fffff];
+       let mixed: Vec<_> = (0..30).map(|i| header(1500000000 + 24 * i + 7 * i % 5, targets[i as usize % 3])).collect();
+       assert_eq!(next(&mixed), Some(0x1c0c881a));
+       // the timespan is clamped to a third and three times the expected one
+       let fast: Vec<_> = (0..30).map(|_| header(1500000000, 0x1c0e3a4b)).collect();
+       assert_eq!(next(&fast), Some(0x1c04be19));
+       let slow: Vec<_> = (0..30).map(|i| header(1500000000 + 600 * i, 0x1c0e3a4b)).collect();
+       assert_eq!(next(&slow), Some(0x1c2aaee1));
+       // and the target capped at the limit
+       let slow: Vec<_> = (0..30).map(|i| header(1500000000 + 600 * i, 0x1e01ffff)).collect();
+       assert_eq!(next(&slow), Some(0x1e01ffff));
+
+       // honest headers change their bits every block
+       let last = &steady[steady.len() - 1];
+       assert!(header(last.time + 24, 0x1c0da287).check_difficulty_transition(&steady, &params, 1000).is_ok());
+       // a forged chain cannot keep to the limit, or any other easier target
+       for &bits in &[0x1e01ffff, 0x1c0e3a4b, 0x1c0da288] {
+           match header(last.time + 24, bits).check_difficulty_transition(&steady, &params, 1000) {
+               Err(util::Error::SpvBadTarget) => {},
+               other => panic!("unexpected {:?}", other),
+           }
+       }
+       assert!(header(last.time + 24, 0x1c0da287).check_difficulty_transition(&steady[7..], &params, 1000).is_err());
+
+       // the first 24 blocks after genesis are at the limit
+       assert_eq!(BlockHeader::next_work_required(&steady[..1], 1500000024, 1, &params), Some(0x1e01ffff));
+       assert_eq!(BlockHeader::next_work_required(&steady[..24], 1500000024, 24, &params), Some(0x1e01ffff));
+       assert_eq!(BlockHeader::next_work_required(&steady[..24], 1500000024, 25, &params), Some(0x1c0da287));
+
+       // testnet allows a minimum-difficulty block after a gap
+       let testnet = Params::new(Network::Testnet);
+       assert_eq!(BlockHeader::next_work_required(&steady, last.time + 48, 1000, &testnet), Some(0x1c0da287));
+       assert_eq!(BlockHeader::next_work_required(&steady, last.time + 49, 1000, &testnet), Some(0x1e01ffff));
+       // and regtest never retargets
+       let regtest = Params::new(Network::Regtest);
+       assert_eq!(BlockHeader::next_work_required(&mixed, last.time + 24, 1000, &regtest), Some(targets[29 % 3]));
+   }
+
+   #[test]
+   fn check_block_test() {
+       let params = Params::new(Network::Bitcoin);
+       let genesis = genesis_block(Network::Bitcoin);
+       let block = Block {
+           header: BlockHeader {
+               version: genesis.header.version,
+               prev_blockhash: genesis.header.prev_blockhash,
+               merkle_root: genesis.header.merkle_root,
+               time: genesis.header.time,
+               bits: genesis.header.bits,
+               nonce: genesis.header.nonce,
+               aux_pow: None,
+           },
+           txdata: genesis.txdata,
+       };
+       block.check(&params).unwrap();
+       // merge-mined, at a target only regtest allows
+       let regtest = Params::new(Network::Regtest);
+       merge_mined_block().check(&regtest).unwrap();
+       match merge_mined_block().check(&params) {
+           Err(util::Error::SpvBadTarget) => {},
+           other => panic!("unexpected {:?}", other),
+       }
+
+       // the header is checked before the transactions
+       let mut bad = block.clone();
+       bad.txdata.clear();
+       bad.header.nonce += 1;
+       match bad.check(&params) {
+           Err(util::Error::SpvBadProofOfWork) => {},
+           other => panic!("unexpected {:?}", other),
+       }
+       bad.header.bits = 0x207fffff;
+       match bad.check(&params) {
+           Err(util::Error::SpvBadTarget) => {},
+           other => panic!("unexpected {:?}", other),
+       }
+
+       // the rest are mined at the regtest target, so they get past the
+       // header to the check under test
+       let mined = |block: &Block| {
+           let mut block = block.clone();
+           block.header.bits = 0x207fffff;
+           while block.check_auxpow(&regtest).is_err() {
+               block.header.nonce += 1;
+           }
+           block
+       };
+       let check = |block: &Block| match mined(block).check(&regtest) {
+           Err(util::Error::Block(e)) => e,
+           other => panic!("unexpected {:?}", other),
+       };
+       mined(&block).check(&regtest).unwrap();
+
+       let mut bad = block.clone();
+       bad.txdata.clear();
+       assert_eq!(check(&bad), block::Error::NoTransactions);
+
+       let mut bad = block.clone();
+       bad.txdata.push(block.txdata[0].clone());
+       assert_eq!(check(&bad), block::Error::MultipleCoinbases);
+       bad.txdata.remove(0);
+       bad.txdata[0].input[0].previous_output.vout = 0;
+       assert_eq!(check(&bad), block::Error::FirstTransactionNotCoinbase);
+
+       let mut bad = block.clone();
+       bad.txdata[0].lock_time = 1;
+       assert_eq!(check(&bad), block::Error::BadMerkleRoot);
+
+       let mut bad = block.clone();
+       bad.txdata[0].output.push(TxOut { value: 0, script_pubkey: Script::from(vec![0; 1_000_000]) });
+       bad.header.merkle_root = bad.merkle_root();
+       assert!(bad.get_weight() > MAX_BLOCK_WEIGHT);
+       assert_eq!(check(&bad), block::Error::Oversized(bad.get_weight()));
+
+       // a block is invalid if any transaction is, here each with its
+       // merkle root fixed up so the transaction check is reached
+       let spend = |vout| Transaction::from_parts(vec![TxIn {
+           previous_output: OutPoint { txid: block.txdata[0].txid(), vout: vout },
+           script_sig: Script::new(),
+           sequence: 0xffffffff,
+           witness: vec![],
+       }], vec![TxOut { value: 1, script_pubkey: Script::new() }]);
+       let mut bad = block.clone();
+       let mut tx = spend(0);
+       tx.input.clear();
+       bad.txdata.push(tx);
+       bad.header.merkle_root = bad.merkle_root();
+       assert_eq!(check(&bad), block::Error::BadTransaction(1, transaction::Error::NoInputs));
+
+       let mut bad = block.clone();
+       bad.txdata[0].output.clear();
+       bad.header.merkle_root = bad.merkle_root();
+       assert_eq!(check(&bad), block::Error::BadTransaction(0, transaction::Error::NoOutputs));
+
+       let mut bad = block.clone();
+       let mut tx = spend(0);
+       tx.input.push(block.txdata[0].input[0].clone());
+       bad.txdata.push(tx);
+       bad.header.merkle_root = bad.merkle_root();
+       assert_eq!(check(&bad), block::Error::BadTransaction(1, transaction::Error::NullPrevout(1)));
+
+       let mut bad = block.clone();
+       let mut tx = spend(0);
+       tx.input.push(tx.input[0].clone());
+       bad.txdata.push(tx);
+       bad.header.merkle_root = bad.merkle_root();
+       let outpoint = bad.txdata[1].input[0].previous_output;
+       assert_eq!(check(&bad), block::Error::BadTransaction(1, transaction::Error::DuplicateInput(outpoint)));
+
+       let mut bad = block.clone();
+       bad.txdata[0].output[0].value = max_money(Network::Bitcoin) + 1;
+       bad.header.merkle_root = bad.merkle_root();
+       assert_eq!(check(&bad), block::Error::BadTransaction(0, transaction::Error::OutputValueOutOfRange(0)));
+
+       let mut bad = block.clone();
+       bad.txdata[0].input[0].script_sig = Script::from(vec![0x51]);
+       bad.header.merkle_root = bad.merkle_root();
+       assert_eq!(check(&bad), block::Error::BadTransaction(0, transaction::Error::CoinbaseScriptSize(1)));
+
+       let mut bad = block.clone();
+       let checksig = opcodes::all::OP_CHECKSIG.into_u8();
+       bad.txdata[0].output[0].script_pubkey = Script::from(vec![checksig; MAX_BLOCK_LEGACY_SIGOPS]);
+       bad.header.merkle_root = bad.merkle_root();
+       mined(&bad).check(&regtest).unwrap();
+       let mut tx = spend(0);
+       tx.input[0].script_sig = Script::from(vec![checksig]);
+       bad.txdata.push(tx);
+       bad.header.merkle_root = bad.merkle_root();
+       assert_eq!(check(&bad), block::Error::TooManySigops(MAX_BLOCK_LEGACY_SIGOPS + 1));
+
+       // repeating the last transaction of an odd level keeps the merkle root
+       let mut bad = block.clone();
+       bad.txdata.push(spend(0));
+       bad.txdata.push(spend(1));
+       bad.header.merkle_root = bad.merkle_root();
+       mined(&bad).check(&regtest).unwrap();
+       let tx = bad.txdata[2].clone();
+       bad.txdata.push(tx);
+       assert_eq!(bad.merkle_root(), bad.header.merkle_root);
+       assert_eq!(check(&bad), block::Error::MutatedMerkleTree);
+   }
+}
@@ -49,8 +49,8 @@ pub fn max_target(_: Network) -> Uint256 {
 /// The maximum value allowed in an output (useful for sanity checking,
 /// since keeping everything below this value should prevent overflows
 /// if you are doing anything remotely sane with monetary values).
-pub fn max_money(_: Network) -> u64 {
-    21_000_000 * COIN_VALUE
+pub fn max_money(network: Network) -> u64 {
+    network.max_money().as_sat()
 }
 
 /// Constructs and returns the coinbase (and only) transaction of the Bitcoin genesis block
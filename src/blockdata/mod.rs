@@ -23,4 +23,5 @@ pub mod opcodes;
 pub mod script;
 pub mod transaction;
 pub mod block;
+pub mod auxpow;
 
@@ -27,12 +27,13 @@ use std::fmt;
 use consensus::encode::{self, Decoder, Encoder};
 use consensus::encode::{Decodable, Encodable};
 
-// Note: I am deliberately not implementing PartialOrd or Ord on the
-//       opcode enum. If you want to check ranges of opcodes, etc.,
-//       write an #[inline] helper function which casts to u8s.
+// Note: the ordering on the opcode type is by byte value and only exists so
+//       opcodes can be kept in sorted collections. If you want to check
+//       ranges of opcodes, etc., write an #[inline] helper function which
+//       casts to u8s.
 
 /// A script Opcode
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct All {
     code: u8,
 }
@@ -24,6 +24,7 @@
 //! This module provides the structures and functions needed to support scripts.
 //!
 
+use std::collections::BTreeSet;
 use std::default::Default;
 use std::{error, fmt};
 
@@ -37,8 +38,15 @@ use bitcoin_hashes::{hash160, sha256, Hash};
 #[cfg(feature="bitcoinconsensus")] use std::convert;
 #[cfg(feature="bitcoinconsensus")] use bitcoin_hashes::sha256d;
 
+use blockdata::transaction::LockTime;
+use network::constants::Network;
+use util::address::Address;
 use util::key::PublicKey;
 
+/// The largest null-data script relayed: 120 bytes of data, plus the
+/// `OP_RETURN` and push opcodes
+pub const MAX_OP_RETURN_RELAY: usize = 123;
+
 #[derive(Clone, Default, PartialOrd, Ord, PartialEq, Eq, Hash)]
 /// A Bitcoin script
 pub struct Script(Box<[u8]>);
@@ -236,6 +244,17 @@ impl Script {
     /// Convert the script into a byte vector
     pub fn into_bytes(self) -> Vec<u8> { self.0.into_vec() }
 
+    /// Generates a script which can only be spent by `pubkey` once `locktime`
+    /// has passed: `<locktime> OP_CHECKLOCKTIMEVERIFY OP_DROP <pubkey> OP_CHECKSIG`
+    pub fn new_cltv(locktime: LockTime, pubkey: &PublicKey) -> Script {
+        Builder::new().push_int(locktime.to_consensus_u32() as i64)
+                      .push_opcode(opcodes::OP_CLTV)
+                      .push_opcode(opcodes::all::OP_DROP)
+                      .push_key(pubkey)
+                      .push_opcode(opcodes::all::OP_CHECKSIG)
+                      .into_script()
+    }
+
     /// Compute the P2SH output corresponding to this redeem script
     pub fn to_p2sh(&self) -> Script {
         Builder::new().push_opcode(opcodes::all::OP_HASH160)
@@ -299,6 +318,95 @@ impl Script {
             self.0[1] == opcodes::all::OP_PUSHBYTES_20.into_u8()
     }
 
+    /// Checks whether a script pubkey is a witness program of any version
+    #[inline]
+    pub fn is_witness_program(&self) -> bool {
+        // A witness program is a version push (OP_0 or OP_1..OP_16) followed
+        // by a single push of 2 to 40 bytes
+        let min_vernum: u8 = opcodes::all::OP_PUSHNUM_1.into_u8();
+        let max_vernum: u8 = opcodes::all::OP_PUSHNUM_16.into_u8();
+        self.0.len() >= 4 &&
+            self.0.len() <= 42 &&
+            (self.0[0] == 0 || (self.0[0] >= min_vernum && self.0[0] <= max_vernum)) &&
+            self.0[1] as usize == self.0.len() - 2
+    }
+
+    /// Checks whether a script pubkey is one of the templates relayed by
+    /// default: P2PK, P2PKH, P2SH, bare multisig of up to three keys, a witness
+    /// program, or a null-data output of at most `MAX_OP_RETURN_RELAY` bytes
+    pub fn is_standard(&self) -> bool {
+        if self.is_p2pk() || self.is_p2pkh() || self.is_p2sh() {
+            return true;
+        }
+        if self.is_witness_program() {
+            // version 0 must be P2WPKH or P2WSH, later versions are left to
+            // future soft forks, so P2TR and its successors are relayed
+            return self.0[0] != 0 || self.is_v0_p2wpkh() || self.is_v0_p2wsh();
+        }
+        self.is_standard_multisig() || self.is_standard_null_data()
+    }
+
+    /// `OP_m <pubkey>... OP_n OP_CHECKMULTISIG` with 1 <= m <= n <= 3
+    fn is_standard_multisig(&self) -> bool {
+        let ins: Vec<Instruction> = self.iter(true).collect();
+        if ins.len() < 4 || ins[ins.len() - 1] != Instruction::Op(opcodes::all::OP_CHECKMULTISIG) {
+            return false;
+        }
+        let num = |ins: &Instruction| match *ins {
+            Instruction::Op(op) => match op.classify() {
+                opcodes::Class::PushNum(n) => Some(n),
+                _ => None,
+            },
+            _ => None,
+        };
+        let (m, n) = match (num(&ins[0]), num(&ins[ins.len() - 2])) {
+            (Some(m), Some(n)) => (m, n),
+            _ => return false,
+        };
+        let keys = &ins[1..ins.len() - 2];
+        1 <= m && m <= n && n <= 3 && keys.len() == n as usize && keys.iter().all(|key| match *key {
+            Instruction::PushBytes(key) => key.len() == 33 || key.len() == 65,
+            _ => false,
+        })
+    }
+
+    /// `OP_RETURN` followed only by pushes, within the relay size limit
+    fn is_standard_null_data(&self) -> bool {
+        self.is_op_return() && self.len() <= MAX_OP_RETURN_RELAY && self.iter(false).skip(1).all(|ins| match ins {
+            Instruction::PushBytes(_) => true,
+            Instruction::Op(op) => match op.classify() {
+                opcodes::Class::PushNum(_) => true,
+                _ => false,
+            },
+            Instruction::Error(_) => false,
+        })
+    }
+
+    /// The smallest value an output with this script can carry without being
+    /// considered dust, at the default dust relay fee of 3 sat/byte. This uses
+    /// the same estimate of the spending input size as Bitcoin Core.
+    pub fn dust_value(&self) -> u64 {
+        if self.is_provably_unspendable() {
+            return 0;
+        }
+        // value + script length + script
+        let txout_size = 8 + encode::VarInt(self.len() as u64).encoded_length() + self.len() as u64;
+        // outpoint + script length + sequence, plus a signature and key which
+        // are discounted for witness outputs
+        let txin_size = if self.is_witness_program() { 32 + 4 + 1 + 26 + 4 } else { 32 + 4 + 1 + 107 + 4 };
+        3 * (txout_size + txin_size)
+    }
+
+    /// Describes this script as an output descriptor: `addr(...)` for scripts
+    /// with an address form and `pk(...)` for pay-to-pubkey. Returns `None` for
+    /// anything else.
+    pub fn to_descriptor(&self, network: Network) -> Option<String> {
+        if self.is_p2pk() {
+            return Some(format!("pk({})", ::hex::encode(&self.0[1..self.0.len() - 1])));
+        }
+        Address::from_script(self, network).map(|addr| format!("addr({})", addr))
+    }
+
     /// Check if this is an OP_RETURN output
     pub fn is_op_return (&self) -> bool {
         !self.0.is_empty() && (opcodes::All::from(self.0[0]) == opcodes::all::OP_RETURN)
@@ -321,6 +429,76 @@ impl Script {
         }
     }
 
+    /// Treating the script as the scriptSig of a P2SH spend, extracts the redeem
+    /// script from its final push. Returns `None` if the script does not end
+    /// with a push or fails to parse.
+    pub fn p2sh_redeem_script(&self) -> Option<Script> {
+        let mut last = None;
+        for ins in self.iter(false) {
+            last = match ins {
+                Instruction::PushBytes(data) => Some(data),
+                Instruction::Op(_) => None,
+                Instruction::Error(_) => return None,
+            };
+        }
+        last.map(|data| Script::from(data.to_vec()))
+    }
+
+    /// Every byte string pushed by the script, in order. Opcodes are skipped,
+    /// and a truncated push at the end of the script is ignored.
+    pub fn pushed_data(&self) -> Vec<Vec<u8>> {
+        self.iter(false).filter_map(|ins| match ins {
+            Instruction::PushBytes(data) => Some(data.to_vec()),
+            Instruction::Op(_) | Instruction::Error(_) => None,
+        }).collect()
+    }
+
+    /// For an `OP_RETURN` output whose first push starts with `prefix`, the
+    /// rest of that push, which is how protocols embedding data usually mark
+    /// their outputs. Any pushes after the first are not part of the result.
+    pub fn op_return_with_prefix(&self, prefix: &[u8]) -> Option<&[u8]> {
+        if !self.is_op_return() {
+            return None;
+        }
+        match self.iter(false).nth(1) {
+            Some(Instruction::PushBytes(data)) if data.starts_with(prefix) => Some(&data[prefix.len()..]),
+            _ => None,
+        }
+    }
+
+    /// The distinct opcodes used by the script. Data pushes, including
+    /// `OP_0`, are skipped, while `OP_1NEGATE` and `OP_1` to `OP_16` count as
+    /// opcodes. Parsing stops at the first error, such as a truncated push.
+    pub fn opcodes_used(&self) -> BTreeSet<opcodes::All> {
+        let mut ret = BTreeSet::new();
+        for ins in self.iter(false) {
+            match ins {
+                Instruction::Op(op) => { ret.insert(op); }
+                Instruction::PushBytes(_) => {}
+                Instruction::Error(_) => break,
+            }
+        }
+        ret
+    }
+
+    /// The number of signature operations, counted the legacy way: one for
+    /// each `OP_CHECKSIG(VERIFY)` and 20 for each `OP_CHECKMULTISIG(VERIFY)`,
+    /// whatever its key count. Counting stops at a truncated push.
+    pub fn legacy_sigop_count(&self) -> usize {
+        let mut ret = 0;
+        for ins in self.iter(false) {
+            match ins {
+                Instruction::Op(opcodes::all::OP_CHECKSIG) |
+                Instruction::Op(opcodes::all::OP_CHECKSIGVERIFY) => ret += 1,
+                Instruction::Op(opcodes::all::OP_CHECKMULTISIG) |
+                Instruction::Op(opcodes::all::OP_CHECKMULTISIGVERIFY) => ret += 20,
+                Instruction::Op(_) | Instruction::PushBytes(_) => {}
+                Instruction::Error(_) => break,
+            }
+        }
+        ret
+    }
+
     #[cfg(feature="bitcoinconsensus")]
     /// verify spend of an input script
     /// # Parameters
@@ -797,6 +975,169 @@ mod test {
         assert_eq!(hex_script!("6aa9149eb21980dc9d413d8eac27314938b9da920ee53e87").is_provably_unspendable(), true);
     }
 
+    #[test]
+    fn script_pushed_data() {
+        // OP_RETURN <"via"> OP_DUP <20 bytes> OP_PUSHDATA1 <2 bytes> OP_1
+        let script = hex_script!("6a0376696176140102030405060708090a0102030405060708090a4c02beef51");
+        assert_eq!(script.pushed_data(), vec![
+            b"via".to_vec(),
+            ::hex::decode("0102030405060708090a0102030405060708090a").unwrap(),
+            vec![0xbe, 0xef],
+        ]);
+        assert!(Script::new().pushed_data().is_empty());
+
+        // a push running off the end is dropped, earlier ones are kept
+        let truncated = hex_script!("0376696104beef");
+        assert_eq!(truncated.pushed_data(), vec![b"via".to_vec()]);
+    }
+
+    #[test]
+    fn script_p2sh_redeem_script() {
+        // 2-of-3 multisig
+        let redeem_script = hex_script!("52210375e00eb72e29da82b89367947f29ef34afb75e8654f6ea368e0acdfd92976b7c2103a1b26313f430c4b15bb1fdce663207659d8cac749a0e53d70eff01874496feff2103c96d495bfdd5ba4145e3e046fee45e84a8a48ad05bd8dbb395c011a32cf9f88053ae");
+        let script_sig = Builder::new()
+            .push_int(0)
+            .push_slice(&[0x30; 71])
+            .push_slice(&[0x30; 72])
+            .push_slice(&redeem_script[..])
+            .into_script();
+        let extracted = script_sig.p2sh_redeem_script().unwrap();
+        assert_eq!(extracted, redeem_script);
+
+        assert_eq!(Script::new().p2sh_redeem_script(), None);
+        // ending in an opcode, or with a truncated push
+        assert_eq!(hex_script!("0102ac").p2sh_redeem_script(), None);
+        assert_eq!(hex_script!("010204beef").p2sh_redeem_script(), None);
+    }
+
+    #[test]
+    fn script_op_return_with_prefix() {
+        let marked = hex_script!("6a0676690102030406ffff");
+        assert_eq!(marked.op_return_with_prefix(&[0x76, 0x69]), Some(&[0x01, 0x02, 0x03, 0x04][..]));
+        assert_eq!(marked.op_return_with_prefix(&[]), Some(&[0x76, 0x69, 0x01, 0x02, 0x03, 0x04][..]));
+        assert_eq!(hex_script!("6a027669").op_return_with_prefix(&[0x76, 0x69]), Some(&[][..]));
+
+        // the prefix must begin the first push of an OP_RETURN output
+        assert_eq!(marked.op_return_with_prefix(&[0x69, 0x76]), None);
+        assert_eq!(hex_script!("6a017604766901026a").op_return_with_prefix(&[0x76, 0x69]), None);
+        assert_eq!(hex_script!("0676690102030475").op_return_with_prefix(&[0x76, 0x69]), None);
+        assert_eq!(hex_script!("6a51").op_return_with_prefix(&[0x76, 0x69]), None);
+        assert_eq!(hex_script!("6a").op_return_with_prefix(&[]), None);
+        assert_eq!(hex_script!("6a057669").op_return_with_prefix(&[0x76, 0x69]), None);
+    }
+
+    #[test]
+    fn script_opcodes_used() {
+        // OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG OP_1 OP_0 OP_DUP
+        let script = hex_script!("76a9140389035a9225b3839e2bbf32d826a1e222031fd888ac510076");
+        let used: Vec<opcodes::All> = script.opcodes_used().into_iter().collect();
+        assert_eq!(used, vec![
+            opcodes::all::OP_PUSHNUM_1,
+            opcodes::all::OP_DUP,
+            opcodes::all::OP_EQUALVERIFY,
+            opcodes::all::OP_HASH160,
+            opcodes::all::OP_CHECKSIG,
+        ]);
+
+        assert!(Script::new().opcodes_used().is_empty());
+        assert!(hex_script!("0014751e76e8199196d454941c45d1b3a323f1433bd6").opcodes_used().is_empty());
+        // a truncated push ends the scan
+        let used = hex_script!("6a0b68656c6c6f").opcodes_used();
+        assert_eq!(used.into_iter().collect::<Vec<_>>(), vec![opcodes::all::OP_RETURN]);
+    }
+
+    #[test]
+    fn script_is_standard() {
+        let pk = "033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52";
+        // P2PK, P2PKH, P2SH, P2WPKH, P2WSH, P2TR
+        assert!(hex_script!(&format!("21{}ac", pk)).is_standard());
+        assert!(hex_script!("76a9140389035a9225b3839e2bbf32d826a1e222031fd888ac").is_standard());
+        assert!(hex_script!("a9140ffdcf96700455074292a821c74922e86529939987").is_standard());
+        assert!(hex_script!("0014751e76e8199196d454941c45d1b3a323f1433bd6").is_standard());
+        assert!(hex_script!("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262").is_standard());
+        assert!(hex_script!("51200f0c8db753acbd17343a39c2f3f4e35e4be6da749f9e35137ab220e7b238a667").is_standard());
+        // a version 0 program of another length is not
+        assert!(!hex_script!("0018751e76e8199196d454941c45d1b3a323f1433bd6751e76e8").is_standard());
+
+        // null data up to 120 bytes
+        let data = Builder::new().push_opcode(opcodes::all::OP_RETURN).push_slice(&[0x42; 120]).into_script();
+        assert_eq!(data.len(), MAX_OP_RETURN_RELAY);
+        assert!(data.is_standard());
+        assert!(hex_script!("6a").is_standard());
+        assert!(!Builder::new().push_opcode(opcodes::all::OP_RETURN).push_slice(&[0x42; 121]).into_script().is_standard());
+        assert!(!hex_script!("6a0142ac").is_standard());
+
+        // bare multisig is only standard up to three keys
+        let multisig = |m: i64, n: usize| {
+            let mut builder = Builder::new().push_int(m);
+            for _ in 0..n {
+                builder = builder.push_slice(&::hex::decode(pk).unwrap());
+            }
+            builder.push_int(n as i64).push_opcode(opcodes::all::OP_CHECKMULTISIG).into_script()
+        };
+        assert!(multisig(1, 1).is_standard());
+        assert!(multisig(2, 3).is_standard());
+        assert!(multisig(3, 3).is_standard());
+        assert!(!multisig(2, 4).is_standard());
+        assert!(!multisig(3, 2).is_standard());
+        assert!(!multisig(0, 2).is_standard());
+
+        assert!(!Script::new().is_standard());
+        assert!(!hex_script!("ac").is_standard());
+    }
+
+    #[test]
+    fn script_cltv() {
+        let pk = PublicKey::from_str("033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52").unwrap();
+
+        // block height 500000, as a 3-byte script number
+        let script = Script::new_cltv(LockTime::from_height(500_000).unwrap(), &pk);
+        assert_eq!(script, hex_script!("0320a107b17521033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ac"));
+        // small heights use the OP_N opcodes
+        let script = Script::new_cltv(LockTime::from_height(16).unwrap(), &pk);
+        assert_eq!(&script[..3], &[0x60, 0xb1, 0x75]);
+
+        // a timestamp needs four bytes
+        let script = Script::new_cltv(LockTime::from_time(1_600_000_000).unwrap(), &pk);
+        assert_eq!(script, hex_script!("0400105e5fb17521033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ac"));
+        // one with the high bit set gets a fifth byte to stay positive
+        let script = Script::new_cltv(LockTime::from_time(0x80000000).unwrap(), &pk);
+        assert_eq!(&script[..7], &[0x05, 0x00, 0x00, 0x00, 0x80, 0x00, 0xb1]);
+    }
+
+    #[test]
+    fn script_dust_value() {
+        // these match the minimum output values relayed by Bitcoin Core
+        let p2pkh = hex_script!("76a914162c5ea71c0b23f5b9022ef047c4a86470a5b07088ac");
+        assert!(!p2pkh.is_witness_program());
+        assert_eq!(p2pkh.dust_value(), 546);
+        let p2wpkh = hex_script!("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert!(p2wpkh.is_witness_program());
+        assert_eq!(p2wpkh.dust_value(), 294);
+        let p2wsh = hex_script!("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262");
+        assert!(p2wsh.is_witness_program());
+        assert_eq!(p2wsh.dust_value(), 330);
+        assert_eq!(hex_script!("6a0401020304").dust_value(), 0);
+    }
+
+    #[test]
+    fn script_descriptor() {
+        assert_eq!(
+            hex_script!("0014751e76e8199196d454941c45d1b3a323f1433bd6").to_descriptor(Network::Bitcoin),
+            Some("addr(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4)".to_owned())
+        );
+        assert_eq!(
+            hex_script!("76a914162c5ea71c0b23f5b9022ef047c4a86470a5b07088ac").to_descriptor(Network::Testnet),
+            Some("addr(mhYCK8wSgwctbFnc3u9fCzzF8XgodR9KHX)".to_owned())
+        );
+        assert_eq!(
+            hex_script!("21021aeaf2f8638a129a3156fbe7e5ef635226b0bafd495ff03afe2c843d7e3a4b51ac").to_descriptor(Network::Bitcoin),
+            Some("pk(021aeaf2f8638a129a3156fbe7e5ef635226b0bafd495ff03afe2c843d7e3a4b51)".to_owned())
+        );
+        assert_eq!(hex_script!("6a0401020304").to_descriptor(Network::Bitcoin), None);
+        assert_eq!(hex_script!("5221021aeaf2f8638a129a3156fbe7e5ef635226b0bafd495ff03afe2c843d7e3a4b5151ae").to_descriptor(Network::Bitcoin), None);
+    }
+
     #[test]
     fn op_return_test() {
         assert_eq!(hex_script!("6aa9149eb21980dc9d413d8eac27314938b9da920ee53e87").is_op_return(), true);
@@ -873,6 +1214,16 @@ mod test {
         assert_eq!(redeem_script.to_v0_p2wsh().to_p2sh(), expected_out);
     }
 
+    #[test]
+    fn test_legacy_sigop_count() {
+        assert_eq!(hex_script!("76a91402306a7c23f3e8010de41e9e591348bb83f11daa88ac").legacy_sigop_count(), 1);
+        // 1-of-1 multisig is still 20, and a push of the opcode byte is not an op
+        assert_eq!(hex_script!("5121021aeaf2f8638a129a3156fbe7e5ef635226b0bafd495ff03afe2c843d7e3a4b51ae").legacy_sigop_count(), 20);
+        assert_eq!(hex_script!("01acadaf").legacy_sigop_count(), 21);
+        assert_eq!(hex_script!("ac4cff").legacy_sigop_count(), 1);
+        assert_eq!(Script::new().legacy_sigop_count(), 0);
+    }
+
     #[test]
     fn test_iterator() {
         let zero = hex_script!("00");
@@ -23,18 +23,25 @@
 //! This module provides the structures and functions needed to support transactions.
 //!
 
-use byteorder::{LittleEndian, WriteBytesExt};
 use std::default::Default;
-use std::fmt;
+use std::collections::HashSet;
+use std::{error, fmt};
 #[cfg(feature="bitcoinconsensus")] use std::collections::HashMap;
 
 use bitcoin_hashes::{self, sha256d, Hash};
 use bitcoin_hashes::hex::FromHex;
 
+use util::address::Address;
+use util::amount::Amount;
+use util::coinselect::Utxo;
+use util::fee::FeeRate;
 use util::hash::BitcoinHash;
+use util::sighash;
+use network::constants::Network;
 #[cfg(feature="bitcoinconsensus")] use blockdata::script;
+use blockdata::constants::max_money;
 use blockdata::script::Script;
-use consensus::encode::{self, serialize, Encoder, Decoder};
+use consensus::encode::{self, Encoder, Decoder};
 use consensus::encode::{Encodable, Decodable, VarInt};
 
 /// A reference to a transaction output
@@ -173,6 +180,160 @@ impl ::std::str::FromStr for OutPoint {
     }
 }
 
+/// An error in building a transaction
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// No inputs were provided
+    NoInputs,
+    /// The output value left after paying the fee would be dust
+    Dust(u64),
+    /// The value of the output at this index exceeds the money supply
+    OutputValueOutOfRange(usize),
+    /// The total value of the outputs exceeds the money supply
+    OutputTotalOutOfRange,
+    /// The total value of the inputs overflows
+    InputTotalOutOfRange,
+    /// The estimated fee overflows
+    FeeOutOfRange,
+    /// The inputs do not cover the outputs and fee
+    InsufficientFunds {
+        /// The value needed
+        needed: u64,
+        /// The value available
+        available: u64,
+    },
+    /// The coinbase scriptSig is of this length, outside the allowed 2 to 100 bytes
+    CoinbaseScriptSize(usize),
+    /// Two inputs spend this same outpoint
+    DuplicateInput(OutPoint),
+    /// The fee for spending the inputs is at least their total value
+    FeeExceedsValue {
+        /// The estimated fee
+        fee: u64,
+        /// The total value of the inputs
+        value: u64,
+    },
+    /// The transaction has no outputs
+    NoOutputs,
+    /// The input at this index spends the null prevout outside a coinbase
+    NullPrevout(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NoInputs => f.write_str("no inputs to spend"),
+            Error::Dust(v) => write!(f, "output value {} would be dust", v),
+            Error::OutputValueOutOfRange(idx) => write!(f, "value of output {} out of range", idx),
+            Error::OutputTotalOutOfRange => f.write_str("total output value out of range"),
+            Error::InputTotalOutOfRange => f.write_str("total input value out of range"),
+            Error::FeeOutOfRange => f.write_str("fee out of range"),
+            Error::InsufficientFunds { needed, available } => write!(f, "insufficient funds: {} needed, {} available", needed, available),
+            Error::CoinbaseScriptSize(len) => write!(f, "coinbase scriptSig of {} bytes, not 2 to 100", len),
+            Error::DuplicateInput(ref outpoint) => write!(f, "outpoint {}:{} spent twice", outpoint.txid, outpoint.vout),
+            Error::FeeExceedsValue { fee, value } => write!(f, "fee {} exceeds consolidated value {}", fee, value),
+            Error::NoOutputs => f.write_str("no outputs"),
+            Error::NullPrevout(idx) => write!(f, "input {} spends the null prevout", idx),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> { None }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::NoInputs => "no inputs to spend",
+            Error::Dust(_) => "output would be dust",
+            Error::OutputValueOutOfRange(_) => "output value out of range",
+            Error::OutputTotalOutOfRange => "total output value out of range",
+            Error::InputTotalOutOfRange => "total input value out of range",
+            Error::FeeOutOfRange => "fee out of range",
+            Error::InsufficientFunds { .. } => "insufficient funds",
+            Error::CoinbaseScriptSize(_) => "coinbase scriptSig size out of range",
+            Error::DuplicateInput(_) => "outpoint spent twice",
+            Error::FeeExceedsValue { .. } => "fee exceeds consolidated value",
+            Error::NoOutputs => "no outputs",
+            Error::NullPrevout(_) => "null prevout outside a coinbase",
+        }
+    }
+}
+
+/// The dust limit of a P2PKH output, the largest of the standard change types
+const CHANGE_DUST_LIMIT: u64 = 546;
+
+/// Computes the change left from `input_sum` after paying `recipient_amount`
+/// and `fee`. Zero change means no change output is needed; change too small
+/// to be worth an output is an error, as is not having enough to pay.
+pub fn compute_change(input_sum: Amount, recipient_amount: Amount, fee: Amount) -> Result<Amount, Error> {
+    let needed = recipient_amount.checked_add(fee).ok_or(Error::OutputTotalOutOfRange)?;
+    let change = input_sum.checked_sub(needed).ok_or(Error::InsufficientFunds {
+        needed: needed.as_sat(),
+        available: input_sum.as_sat(),
+    })?;
+    if change != Amount::ZERO && change.as_sat() < CHANGE_DUST_LIMIT {
+        return Err(Error::Dust(change.as_sat()));
+    }
+    Ok(change)
+}
+
+/// Whether any transaction of a package signals BIP125 replaceability. A
+/// transaction is replaceable if it signals itself or spends an unconfirmed
+/// one that does, so the signal is inherited by descendants only: a package
+/// whose child signals may still have a parent that cannot be replaced.
+pub fn package_signals_rbf(txs: &[Transaction]) -> bool {
+    txs.iter().any(Transaction::is_explicitly_rbf)
+}
+
+/// Locktime values below this are block heights, and from it upwards UNIX timestamps
+pub const LOCK_TIME_THRESHOLD: u32 = 500_000_000;
+
+/// An absolute locktime, as found in a transaction's `lock_time` field or
+/// checked by `OP_CHECKLOCKTIMEVERIFY`
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub enum LockTime {
+    /// A block height
+    Blocks(u32),
+    /// A UNIX timestamp, in seconds
+    Seconds(u32),
+}
+
+impl LockTime {
+    /// Interprets a raw locktime value, as found in a transaction
+    pub fn from_consensus(n: u32) -> LockTime {
+        if n < LOCK_TIME_THRESHOLD {
+            LockTime::Blocks(n)
+        } else {
+            LockTime::Seconds(n)
+        }
+    }
+
+    /// A block height locktime, or `None` if `height` is in the timestamp range
+    pub fn from_height(height: u32) -> Option<LockTime> {
+        if height < LOCK_TIME_THRESHOLD {
+            Some(LockTime::Blocks(height))
+        } else {
+            None
+        }
+    }
+
+    /// A timestamp locktime, or `None` if `time` is in the block height range
+    pub fn from_time(time: u32) -> Option<LockTime> {
+        if time >= LOCK_TIME_THRESHOLD {
+            Some(LockTime::Seconds(time))
+        } else {
+            None
+        }
+    }
+
+    /// The raw locktime value
+    pub fn to_consensus_u32(self) -> u32 {
+        match self {
+            LockTime::Blocks(n) | LockTime::Seconds(n) => n,
+        }
+    }
+}
+
 /// A transaction input, which defines old coins to be consumed
 #[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub struct TxIn {
@@ -195,6 +356,56 @@ pub struct TxIn {
 }
 serde_struct_impl!(TxIn, previous_output, script_sig, sequence, witness);
 
+/// How a taproot input was spent, as revealed by its witness
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TaprootSpend {
+    /// A key path spend, carrying the 64- or 65-byte signature
+    KeyPath(Vec<u8>),
+    /// A script path spend
+    ScriptPath {
+        /// The leaf script being executed
+        script: Script,
+        /// The control block proving the script is committed to by the output key
+        control_block: Vec<u8>,
+    },
+}
+
+impl TxIn {
+    /// Classifies the witness of an input spending a taproot output, after
+    /// removing any annex as in BIP341. Returns `None` if the witness cannot
+    /// be a taproot spend. Whether the spent output is taproot at all is not
+    /// known here and is up to the caller.
+    pub fn taproot_spend_type(&self) -> Option<TaprootSpend> {
+        let mut stack = &self.witness[..];
+        if stack.len() >= 2 && stack[stack.len() - 1].first() == Some(&0x50) {
+            stack = &stack[..stack.len() - 1];
+        }
+        match stack.len() {
+            0 => None,
+            1 => {
+                let sig = &stack[0];
+                if sig.len() == 64 || sig.len() == 65 {
+                    Some(TaprootSpend::KeyPath(sig.clone()))
+                } else {
+                    None
+                }
+            }
+            n => {
+                // leaf version and parity byte, internal key, at most 128 hashes
+                let control_block = &stack[n - 1];
+                if control_block.len() < 33 || (control_block.len() - 33) % 32 != 0 ||
+                   control_block.len() > 33 + 32 * 128 {
+                    return None;
+                }
+                Some(TaprootSpend::ScriptPath {
+                    script: Script::from(stack[n - 2].clone()),
+                    control_block: control_block.clone(),
+                })
+            }
+        }
+    }
+}
+
 /// A transaction output, which defines new coins to be created from old ones.
 #[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub struct TxOut {
@@ -257,6 +468,44 @@ pub struct Transaction {
 serde_struct_impl!(Transaction, version, lock_time, input, output);
 
 impl Transaction {
+    /// Creates a version 2 transaction with a lock time of 0 from the given
+    /// inputs and outputs.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use bitcoin::{OutPoint, Script, Transaction, TxIn, TxOut};
+    ///
+    /// let txin = |outpoint: &str| TxIn {
+    ///     previous_output: OutPoint::from_str(outpoint).unwrap(),
+    ///     script_sig: Script::new(),
+    ///     sequence: 0xFFFFFFFF,
+    ///     witness: vec![],
+    /// };
+    /// let txout = |value| TxOut { value: value, script_pubkey: Script::new() };
+    ///
+    /// let tx = Transaction::from_parts(
+    ///     vec![
+    ///         txin("5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456:0"),
+    ///         txin("5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456:1"),
+    ///     ],
+    ///     vec![txout(50_000), txout(25_000)],
+    /// );
+    /// assert_eq!(tx.version, 2);
+    /// assert_eq!(tx.lock_time, 0);
+    /// assert_eq!(tx.input.len(), 2);
+    /// assert_eq!(tx.output.len(), 2);
+    /// ```
+    pub fn from_parts(input: Vec<TxIn>, output: Vec<TxOut>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: input,
+            output: output,
+        }
+    }
+
     /// Computes a "normalized TXID" which does not include any signatures.
     /// This gives a way to identify a transaction that is ``the same'' as
     /// another in the sense of having same inputs and outputs.
@@ -283,6 +532,17 @@ impl Transaction {
         sha256d::Hash::from_engine(enc)
     }
 
+    /// Computes the wtxid, the hash of the transaction including its witness
+    /// as defined by BIP141. For non-segwit transactions this is the txid.
+    pub fn wtxid(&self) -> sha256d::Hash {
+        self.bitcoin_hash()
+    }
+
+    /// Computes the txid and wtxid together, e.g. to index a transaction by both
+    pub fn id_pair(&self) -> (sha256d::Hash, sha256d::Hash) {
+        (self.txid(), self.wtxid())
+    }
+
     /// Computes a signature hash for a given input index with a given sighash flag.
     /// To actually produce a scriptSig, this hash needs to be run through an
     /// ECDSA signer, the SigHashType appended to the resulting sig, and a
@@ -291,7 +551,8 @@ impl Transaction {
     /// *Warning* This does NOT attempt to support OP_CODESEPARATOR. In general
     /// this would require evaluating `script_pubkey` to determine which separators
     /// get evaluated and which don't, which we don't have the information to
-    /// determine.
+    /// determine. `util::sighash::SighashCache::legacy_signature_hash` takes
+    /// an already trimmed script and removes the remaining separators.
     ///
     /// # Panics
     /// Panics if `input_index` is greater than or equal to `self.input.len()`
@@ -299,59 +560,17 @@ impl Transaction {
     pub fn signature_hash(&self, input_index: usize, script_pubkey: &Script, sighash_u32: u32) -> sha256d::Hash {
         assert!(input_index < self.input.len());  // Panic on OOB
 
-        let (sighash, anyone_can_pay) = SigHashType::from_u32(sighash_u32).split_anyonecanpay_flag();
-
-        // Special-case sighash_single bug because this is easy enough.
-        if sighash == SigHashType::Single && input_index >= self.output.len() {
-            return sha256d::Hash::from_slice(&[1, 0, 0, 0, 0, 0, 0, 0,
-                                               0, 0, 0, 0, 0, 0, 0, 0,
-                                               0, 0, 0, 0, 0, 0, 0, 0,
-                                               0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
-        }
-
-        // Build tx to sign
-        let mut tx = Transaction {
-            version: self.version,
-            lock_time: self.lock_time,
-            input: vec![],
-            output: vec![],
-        };
-        // Add all inputs necessary..
-        if anyone_can_pay {
-            tx.input = vec![TxIn {
-                previous_output: self.input[input_index].previous_output,
-                script_sig: script_pubkey.clone(),
-                sequence: self.input[input_index].sequence,
-                witness: vec![],
-            }];
-        } else {
-            tx.input = Vec::with_capacity(self.input.len());
-            for (n, input) in self.input.iter().enumerate() {
-                tx.input.push(TxIn {
-                    previous_output: input.previous_output,
-                    script_sig: if n == input_index { script_pubkey.clone() } else { Script::new() },
-                    sequence: if n != input_index && (sighash == SigHashType::Single || sighash == SigHashType::None) { 0 } else { input.sequence },
-                    witness: vec![],
-                });
+        match self.serialize_for_signature(input_index, script_pubkey, sighash_u32) {
+            Ok(preimage) => sha256d::Hash::hash(&preimage),
+            // Special-case sighash_single bug because this is easy enough.
+            Err(sighash::Error::SingleWithoutCorrespondingOutput(_)) => {
+                sha256d::Hash::from_slice(&[1, 0, 0, 0, 0, 0, 0, 0,
+                                            0, 0, 0, 0, 0, 0, 0, 0,
+                                            0, 0, 0, 0, 0, 0, 0, 0,
+                                            0, 0, 0, 0, 0, 0, 0, 0]).unwrap()
             }
+            Err(_) => unreachable!("input index checked above"),
         }
-        // ..then all outputs
-        tx.output = match sighash {
-            SigHashType::All => self.output.clone(),
-            SigHashType::Single => {
-                let output_iter = self.output.iter()
-                                      .take(input_index + 1)  // sign all outputs up to and including this one, but erase
-                                      .enumerate()            // all of them except for this one
-                                      .map(|(n, out)| if n == input_index { out.clone() } else { TxOut::default() });
-                output_iter.collect()
-            }
-            SigHashType::None => vec![],
-            _ => unreachable!()
-        };
-        // hash the result
-        let mut raw_vec = serialize(&tx);
-        raw_vec.write_u32::<LittleEndian>(sighash_u32).unwrap();
-        sha256d::Hash::hash(&raw_vec)
     }
 
     /// Gets the "weight" of this transaction, as defined by BIP141. For transactions with an empty
@@ -399,7 +618,7 @@ impl Transaction {
     #[cfg(feature="bitcoinconsensus")]
     /// Verify that this transaction is able to spend some outputs of spent transactions
     pub fn verify(&self, spent: &HashMap<sha256d::Hash, Transaction>) -> Result<(), script::Error> {
-        let tx = serialize(&*self);
+        let tx = encode::serialize(&*self);
         for (idx, input) in self.input.iter().enumerate() {
             if let Some(ref s) = spent.get(&input.previous_output.txid) {
                 if let Some(ref output) = s.output.get(input.previous_output.vout as usize) {
@@ -414,10 +633,223 @@ impl Transaction {
         Ok(())
     }
 
+    /// The part of the BIP141 weight taken by the witness: the segwit marker
+    /// and flag and every input's witness stack, each byte counting once.
+    /// Zero for a transaction without witnesses, as it is serialized without.
+    pub fn witness_weight(&self) -> u64 {
+        if self.input.iter().all(|input| input.witness.is_empty()) {
+            return 0;
+        }
+        let mut weight = 2; // marker and flag
+        for input in &self.input {
+            weight += VarInt(input.witness.len() as u64).encoded_length();
+            for elem in &input.witness {
+                weight += VarInt(elem.len() as u64).encoded_length() + elem.len() as u64;
+            }
+        }
+        weight
+    }
+
+    /// The transaction's locktime, whether a height or a timestamp
+    pub fn locktime(&self) -> LockTime {
+        LockTime::from_consensus(self.lock_time)
+    }
+
     /// Is this a coin base transaction?
     pub fn is_coin_base(&self) -> bool {
         self.input.len() == 1 && self.input[0].previous_output.is_null()
     }
+
+    /// Whether the transaction signals BIP125 replaceability itself, by an
+    /// input with a sequence number below 0xFFFFFFFE
+    pub fn is_explicitly_rbf(&self) -> bool {
+        self.input.iter().any(|input| input.sequence < 0xFFFFFFFE)
+    }
+
+    /// The least absolute fee a BIP125 replacement of a transaction which paid
+    /// `original_fee` for `original_vsize` virtual bytes must pay: the original
+    /// fee (rule 3) plus `incremental_relay_feerate` on the replacement's own
+    /// size (rule 4). The replacement is taken to be no larger than the
+    /// original; a bigger one owes the incremental fee on its actual size.
+    /// Returns `None` if the fee overflows.
+    pub fn min_replacement_fee(original_fee: Amount, original_vsize: usize, incremental_relay_feerate: FeeRate) -> Option<Amount> {
+        (original_vsize as u64).checked_mul(4)
+            .and_then(|weight| incremental_relay_feerate.fee_wu(weight))
+            .and_then(|incremental| original_fee.checked_add(incremental))
+    }
+
+    /// Whether the outputs of this transaction, with `confirmations` blocks
+    /// on top of and including its own, may be spent in the next block. Only
+    /// coinbase outputs have to mature; other transactions always may be.
+    pub fn is_coinbase_mature(&self, confirmations: u32, network: Network) -> bool {
+        !self.is_coin_base() || confirmations >= network.coinbase_maturity()
+    }
+
+    /// Checks that the scriptSig of a coinbase transaction is between 2 and
+    /// 100 bytes long, as consensus requires. Other transactions always pass.
+    pub fn check_coinbase_script_size(&self) -> Result<(), Error> {
+        if !self.is_coin_base() {
+            return Ok(());
+        }
+        let len = self.input[0].script_sig.len();
+        if len < 2 || len > 100 {
+            return Err(Error::CoinbaseScriptSize(len));
+        }
+        Ok(())
+    }
+
+    /// Checks that each output value, and their sum, is no more than the
+    /// money supply of `network`
+    pub fn check_money_range(&self, network: Network) -> Result<(), Error> {
+        let max = max_money(network);
+        let mut total = 0u64;
+        for (idx, output) in self.output.iter().enumerate() {
+            if output.value > max {
+                return Err(Error::OutputValueOutOfRange(idx));
+            }
+            total += output.value;
+            if total > max {
+                return Err(Error::OutputTotalOutOfRange);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that there is at least one input and one output, and that
+    /// only a coinbase spends the null prevout
+    pub fn check_inputs_outputs(&self) -> Result<(), Error> {
+        if self.input.is_empty() {
+            return Err(Error::NoInputs);
+        }
+        if self.output.is_empty() {
+            return Err(Error::NoOutputs);
+        }
+        if !self.is_coin_base() {
+            if let Some(idx) = self.input.iter().position(|input| input.previous_output.is_null()) {
+                return Err(Error::NullPrevout(idx));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that no two inputs spend the same outpoint, which consensus
+    /// forbids within a transaction
+    pub fn check_duplicate_inputs(&self) -> Result<(), Error> {
+        let mut seen = HashSet::with_capacity(self.input.len());
+        for input in &self.input {
+            if !seen.insert(input.previous_output) {
+                return Err(Error::DuplicateInput(input.previous_output));
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of signature operations in the scriptSigs and
+    /// scriptPubKeys, counted as at `Script::legacy_sigop_count`
+    pub fn legacy_sigop_count(&self) -> usize {
+        self.input.iter().map(|input| input.script_sig.legacy_sigop_count()).sum::<usize>() +
+            self.output.iter().map(|output| output.script_pubkey.legacy_sigop_count()).sum::<usize>()
+    }
+
+    /// Checks that `spent` knows of the prevout of every input, as it returns
+    /// the output for an outpoint if it is available. The first missing one
+    /// is returned, so an orphan can be told apart from an invalid spend.
+    /// Coinbase transactions spend no prevouts and always pass.
+    pub fn check_inputs_available<S>(&self, mut spent: S) -> Result<(), OutPoint>
+        where S: FnMut(&OutPoint) -> Option<TxOut>
+    {
+        if self.is_coin_base() {
+            return Ok(());
+        }
+        for input in &self.input {
+            if spent(&input.previous_output).is_none() {
+                return Err(input.previous_output);
+            }
+        }
+        Ok(())
+    }
+
+    /// The address on `network` paid by the output at `index`, if there is
+    /// such an output and its script has an address form
+    pub fn output_address(&self, index: usize, network: Network) -> Option<Address> {
+        self.output.get(index).and_then(|output| Address::from_script(&output.script_pubkey, network))
+    }
+
+    /// Builds an unsigned transaction spending all of `utxos` to a single output
+    /// paying `destination`, less a fee at `fee_rate` on its virtual size.
+    ///
+    /// The size of the transaction once signed is estimated from the
+    /// satisfaction weight of each UTXO, so any script type may be swept.
+    pub fn sweep(utxos: &[Utxo], destination: &Address, fee_rate: FeeRate) -> Result<Transaction, Error> {
+        let (mut tx, total, fee) = Transaction::sweep_template(utxos, destination, fee_rate)?;
+        let value = total.saturating_sub(fee);
+        if value < tx.output[0].script_pubkey.dust_value() {
+            return Err(Error::Dust(value));
+        }
+        tx.output[0].value = value;
+        Ok(tx)
+    }
+
+    /// Builds an unsigned transaction consolidating many small `utxos` into a
+    /// single output paying `destination`, like [`sweep`](#method.sweep).
+    ///
+    /// Spending many inputs at once can cost more than they are worth, so
+    /// rather than reporting the empty output as dust, this fails with
+    /// `Error::FeeExceedsValue` when the fee is at least the total value.
+    pub fn consolidate(utxos: &[Utxo], destination: &Address, fee_rate: FeeRate) -> Result<Transaction, Error> {
+        let (mut tx, total, fee) = Transaction::sweep_template(utxos, destination, fee_rate)?;
+        if fee >= total {
+            return Err(Error::FeeExceedsValue { fee: fee, value: total });
+        }
+        let value = total - fee;
+        if value < tx.output[0].script_pubkey.dust_value() {
+            return Err(Error::Dust(value));
+        }
+        tx.output[0].value = value;
+        Ok(tx)
+    }
+
+    /// Builds the zero-valued single-output transaction spending `utxos`,
+    /// returning it with the total input value and the estimated fee.
+    ///
+    /// A satisfaction weight does not say whether the input has a witness,
+    /// so the transaction is taken to be segwit, with the marker and flag
+    /// and an empty witness counted for every input. This overestimates by
+    /// at most two weight units and one for each input.
+    fn sweep_template(utxos: &[Utxo], destination: &Address, fee_rate: FeeRate) -> Result<(Transaction, u64, u64), Error> {
+        if utxos.is_empty() {
+            return Err(Error::NoInputs);
+        }
+
+        let tx = Transaction::from_parts(
+            utxos.iter().map(|utxo| TxIn {
+                previous_output: utxo.outpoint,
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFF,
+                witness: vec![],
+            }).collect(),
+            vec![TxOut {
+                value: 0,
+                script_pubkey: destination.script_pubkey(),
+            }],
+        );
+
+        // the template counts the length of each empty script sig, which the
+        // satisfaction weights include
+        let mut weight = tx.get_weight() - 4 * utxos.len() as u64 + 2 + utxos.len() as u64;
+        for utxo in utxos {
+            weight = weight.checked_add(utxo.satisfaction_weight).ok_or(Error::FeeOutOfRange)?;
+        }
+
+        // charged on whole virtual bytes, as the size is usually quoted
+        let fee = fee_rate.fee_wu(weight.checked_add(3).ok_or(Error::FeeOutOfRange)? / 4 * 4)
+            .ok_or(Error::FeeOutOfRange)?.as_sat();
+        let mut total: u64 = 0;
+        for utxo in utxos {
+            total = total.checked_add(utxo.value.as_sat()).ok_or(Error::InputTotalOutOfRange)?;
+        }
+        Ok((tx, total, fee))
+    }
 }
 
 impl BitcoinHash for Transaction {
@@ -555,7 +987,7 @@ pub enum SigHashType {
 
 impl SigHashType {
      /// Break the sighash flag into the "real" sighash flag and the ANYONECANPAY boolean
-     fn split_anyonecanpay_flag(&self) -> (SigHashType, bool) {
+     pub fn split_anyonecanpay_flag(&self) -> (SigHashType, bool) {
          match *self {
              SigHashType::All		=> (SigHashType::All, false),
              SigHashType::None		=> (SigHashType::None, false),
@@ -592,12 +1024,19 @@ mod tests {
     #[cfg(all(feature = "serde", feature = "strason"))]
     use strason::Json;
 
-    use super::{OutPoint, ParseOutPointError, Transaction, TxIn};
+    use super::{compute_change, package_signals_rbf, Error, LockTime, OutPoint, ParseOutPointError, TaprootSpend, Transaction, TxIn, TxOut};
 
+    use std::collections::HashMap;
     use std::str::FromStr;
+    use blockdata::constants::{max_money, COIN_VALUE};
     use blockdata::script::Script;
     use consensus::encode::serialize;
     use consensus::encode::deserialize;
+    use network::constants::Network;
+    use util::address::Address;
+    use util::amount::Amount;
+    use util::coinselect::{Utxo, P2PKH_SATISFACTION_WEIGHT, P2SH_P2WPKH_SATISFACTION_WEIGHT, P2WPKH_SATISFACTION_WEIGHT};
+    use util::fee::FeeRate;
     use util::hash::BitcoinHash;
     use util::misc::hex_bytes;
 
@@ -744,6 +1183,9 @@ mod tests {
 
         assert_eq!(format!("{:x}", tx.bitcoin_hash()), "d6ac4a5e61657c4c604dcde855a1db74ec6b3e54f32695d72c5e11c7761ea1b4");
         assert_eq!(format!("{:x}", tx.txid()), "9652aa62b0e748caeec40c4cb7bc17c6792435cc3dfe447dd1ca24f912a1c6ec");
+        assert_eq!(tx.wtxid(), tx.bitcoin_hash());
+        assert_eq!(tx.id_pair(), (tx.txid(), tx.wtxid()));
+        assert!(tx.txid() != tx.wtxid());
         assert_eq!(tx.get_weight(), 2718);
 
         // non-segwit tx from my mempool
@@ -759,6 +1201,7 @@ mod tests {
 
         assert_eq!(format!("{:x}", tx.bitcoin_hash()), "971ed48a62c143bbd9c87f4bafa2ef213cfa106c6e140f111931d0be307468dd");
         assert_eq!(format!("{:x}", tx.txid()), "971ed48a62c143bbd9c87f4bafa2ef213cfa106c6e140f111931d0be307468dd");
+        assert_eq!(tx.id_pair(), (tx.txid(), tx.txid()));
     }
 
     #[test]
@@ -1097,6 +1540,365 @@ mod tests {
         run_test_sighash("cf781855040a755f5ba85eef93837236b34a5d3daeb2dbbdcf58bb811828d806ed05754ab8010000000351ac53ffffffffda1e264727cf55c67f06ebcc56dfe7fa12ac2a994fecd0180ce09ee15c480f7d00000000096351516a51acac00ab53dd49ff9f334befd6d6f87f1a832cddfd826a90b78fd8cf19a52cb8287788af94e939d6020000000700525251ac526310d54a7e8900ed633f0f6f0841145aae7ee0cbbb1e2a0cae724ee4558dbabfdc58ba6855010000000552536a53abfd1b101102c51f910500000000096300656a525252656a300bee010000000009ac52005263635151abe19235c9", "53005365", 2, 1422854188, "d5981bd4467817c1330da72ddb8760d6c2556cd809264b2d85e6d274609fc3a3");
     }
 
+    #[test]
+    fn test_sweep() {
+        let rate = |sat_vb| FeeRate::from_sat_per_vb(sat_vb).unwrap();
+        let destination = Address::from_str("bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw").unwrap();
+        let utxo = |outpoint: &str, value, satisfaction_weight| Utxo {
+            outpoint: OutPoint::from_str(outpoint).unwrap(),
+            value: Amount::from_sat(value),
+            satisfaction_weight: satisfaction_weight,
+        };
+        let utxos = [
+            utxo("5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456:0", 10_000, P2WPKH_SATISFACTION_WEIGHT),
+            utxo("5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456:1", 20_000, P2WPKH_SATISFACTION_WEIGHT),
+            utxo("8e0ff10e8b1ba1a4141f5ec2abb6d7a3d6b0a85ec5b84e7c62e9a9b1e0e3a7c1:7", 30_000, P2WPKH_SATISFACTION_WEIGHT),
+        ];
+
+        let tx = Transaction::sweep(&utxos, &destination, rate(2)).unwrap();
+        assert_eq!(tx.input.len(), 3);
+        assert_eq!(tx.input[2].previous_output, utxos[2].outpoint);
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].script_pubkey, destination.script_pubkey());
+        // 982 weight units once signed, estimated as 985 for 247 vbytes at 2 sat/vB
+        assert_eq!(tx.output[0].value, 60_000 - 494);
+
+        assert_eq!(Transaction::sweep(&utxos, &destination, rate(242)), Err(Error::Dust(226)));
+        assert_eq!(Transaction::sweep(&utxos, &destination, rate(1000)), Err(Error::Dust(0)));
+        assert_eq!(Transaction::sweep(&[], &destination, rate(2)), Err(Error::NoInputs));
+        assert_eq!(Transaction::sweep(&utxos, &destination, FeeRate::from_sat_per_kwu(u64::max_value())), Err(Error::FeeOutOfRange));
+        let mut huge = utxos.clone();
+        huge[0].value = Amount::from_sat(u64::max_value());
+        assert_eq!(Transaction::sweep(&huge, &destination, rate(2)), Err(Error::InputTotalOutOfRange));
+        let mut huge = utxos.clone();
+        huge[0].satisfaction_weight = u64::max_value();
+        assert_eq!(Transaction::sweep(&huge, &destination, rate(2)), Err(Error::FeeOutOfRange));
+
+        // each input is estimated by its own satisfaction weight, so one
+        // P2SH-wrapped P2WPKH costs 23 vbytes more for its redeem script push
+        // and a legacy P2PKH input the size of its script sig
+        let mut mixed = utxos.clone();
+        mixed[1].satisfaction_weight = P2SH_P2WPKH_SATISFACTION_WEIGHT;
+        let tx = Transaction::sweep(&mixed, &destination, rate(2)).unwrap();
+        assert_eq!(tx.output[0].value, 60_000 - 494 - 2 * 23);
+        mixed[2].satisfaction_weight = P2PKH_SATISFACTION_WEIGHT;
+        let tx = Transaction::sweep(&mixed, &destination, rate(1)).unwrap();
+        assert_eq!(tx.output[0].value, 60_000 - 350);
+    }
+
+    #[test]
+    fn test_consolidate() {
+        let rate = |sat_vb| FeeRate::from_sat_per_vb(sat_vb).unwrap();
+        let destination = Address::from_str("bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw").unwrap();
+        let txid = "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456";
+        let utxos: Vec<Utxo> = (0..10).map(|vout| Utxo {
+            outpoint: OutPoint::from_str(&format!("{}:{}", txid, vout)).unwrap(),
+            value: Amount::from_sat(5_000),
+            satisfaction_weight: P2WPKH_SATISFACTION_WEIGHT,
+        }).collect();
+
+        let tx = Transaction::consolidate(&utxos, &destination, rate(2)).unwrap();
+        assert_eq!(tx.input.len(), 10);
+        assert_eq!(tx.input[9].previous_output, utxos[9].outpoint);
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].script_pubkey, destination.script_pubkey());
+        // 2886 weight units once signed, estimated as 2896 for 724 vbytes at 2 sat/vB
+        assert_eq!(tx.output[0].value, 50_000 - 1_448);
+        assert_eq!(tx, Transaction::sweep(&utxos, &destination, rate(2)).unwrap());
+
+        assert_eq!(
+            Transaction::consolidate(&utxos, &destination, rate(70)),
+            Err(Error::FeeExceedsValue { fee: 50_680, value: 50_000 })
+        );
+        assert_eq!(Transaction::consolidate(&utxos, &destination, rate(69)), Err(Error::Dust(44)));
+        assert_eq!(Transaction::consolidate(&[], &destination, rate(2)), Err(Error::NoInputs));
+        let max_rate = FeeRate::from_sat_per_kwu(u64::max_value());
+        assert_eq!(Transaction::consolidate(&utxos, &destination, max_rate), Err(Error::FeeOutOfRange));
+
+        // legacy inputs are estimated by their larger script sigs
+        let mut legacy = utxos.clone();
+        for utxo in &mut legacy {
+            utxo.satisfaction_weight = P2PKH_SATISFACTION_WEIGHT;
+        }
+        let tx = Transaction::consolidate(&legacy, &destination, rate(1)).unwrap();
+        assert_eq!(tx.output[0].value, 50_000 - 1_524);
+    }
+
+    #[test]
+    fn test_compute_change() {
+        let sat = Amount::from_sat;
+        assert_eq!(compute_change(sat(100_000), sat(60_000), sat(1_000)), Ok(sat(39_000)));
+        // spending exactly the inputs needs no change output
+        assert_eq!(compute_change(sat(61_000), sat(60_000), sat(1_000)), Ok(Amount::ZERO));
+        assert_eq!(compute_change(sat(61_546), sat(60_000), sat(1_000)), Ok(sat(546)));
+        assert_eq!(compute_change(sat(61_545), sat(60_000), sat(1_000)), Err(Error::Dust(545)));
+        assert_eq!(
+            compute_change(sat(60_500), sat(60_000), sat(1_000)),
+            Err(Error::InsufficientFunds { needed: 61_000, available: 60_500 })
+        );
+        assert_eq!(
+            compute_change(sat(1), sat(u64::max_value()), sat(1)),
+            Err(Error::OutputTotalOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_locktime() {
+        assert_eq!(LockTime::from_consensus(0), LockTime::Blocks(0));
+        assert_eq!(LockTime::from_consensus(499_999_999), LockTime::Blocks(499_999_999));
+        assert_eq!(LockTime::from_consensus(500_000_000), LockTime::Seconds(500_000_000));
+        assert_eq!(LockTime::from_height(500_000_000), None);
+        assert_eq!(LockTime::from_time(499_999_999), None);
+        assert_eq!(LockTime::from_time(1_600_000_000).unwrap().to_consensus_u32(), 1_600_000_000);
+
+        let tx = Transaction { version: 2, lock_time: 1_600_000_000, input: vec![], output: vec![] };
+        assert_eq!(tx.locktime(), LockTime::Seconds(1_600_000_000));
+    }
+
+    #[test]
+    fn test_check_inputs_available() {
+        let txid = "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456";
+        let outpoints: Vec<OutPoint> = (0..3).map(|vout| OutPoint::from_str(&format!("{}:{}", txid, vout)).unwrap()).collect();
+        let input = |outpoint| TxIn {
+            previous_output: outpoint,
+            script_sig: Script::new(),
+            sequence: 0xFFFFFFFF,
+            witness: vec![],
+        };
+        let output = TxOut { value: 10_000, script_pubkey: hex_script!("0014751e76e8199196d454941c45d1b3a323f1433bd6") };
+
+        let mut utxos = HashMap::new();
+        utxos.insert(outpoints[0], output.clone());
+        utxos.insert(outpoints[2], output.clone());
+        let provider = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+
+        let tx = Transaction::from_parts(vec![input(outpoints[0]), input(outpoints[2])], vec![output.clone()]);
+        assert_eq!(tx.check_inputs_available(&provider), Ok(()));
+        let tx = Transaction::from_parts(outpoints.iter().map(|&o| input(o)).collect(), vec![output.clone()]);
+        assert_eq!(tx.check_inputs_available(&provider), Err(outpoints[1]));
+
+        let coinbase = Transaction::from_parts(vec![input(OutPoint::null())], vec![output]);
+        assert_eq!(coinbase.check_inputs_available(&provider), Ok(()));
+    }
+
+    #[test]
+    fn test_witness_weight() {
+        let hex_tx = hex_bytes("010000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff3603da1b0e00045503bd5704c7dd8a0d0ced13bb5785010800000000000a636b706f6f6c122f4e696e6a61506f6f6c2f5345475749542fffffffff02b4e5a212000000001976a914876fbb82ec05caa6af7a3b5e5a983aae6c6cc6d688ac0000000000000000266a24aa21a9edf91c46b49eb8a29089980f02ee6b57e7d63d33b18b4fddac2bcd7db2a39837040120000000000000000000000000000000000000000000000000000000000000000000000000").unwrap();
+        let mut tx: Transaction = deserialize(&hex_tx).unwrap();
+        let base_weight = |tx: &Transaction| {
+            let mut stripped = tx.clone();
+            for input in &mut stripped.input {
+                input.witness.clear();
+            }
+            4 * serialize(&stripped).len() as u64
+        };
+
+        // marker, flag, and one 32-byte element
+        assert_eq!(tx.witness_weight(), 36);
+        assert_eq!(tx.witness_weight(), tx.get_weight() - base_weight(&tx));
+
+        // an input without a witness still has an empty stack encoded
+        let mut input = tx.input[0].clone();
+        input.witness.clear();
+        tx.input.push(input);
+        assert_eq!(tx.witness_weight(), 37);
+        assert_eq!(tx.witness_weight(), tx.get_weight() - base_weight(&tx));
+
+        tx.input[0].witness.clear();
+        assert_eq!(tx.witness_weight(), 0);
+        assert_eq!(tx.get_weight(), base_weight(&tx));
+    }
+
+    #[test]
+    fn test_min_replacement_fee() {
+        // Core's default incremental relay fee of 1 sat/vbyte on a 193 vbyte
+        // P2PKH spend
+        let incremental = FeeRate::from_sat_per_vb(1).unwrap();
+        assert_eq!(Transaction::min_replacement_fee(Amount::from_sat(1000), 193, incremental), Some(Amount::from_sat(1193)));
+        assert_eq!(Transaction::min_replacement_fee(Amount::from_sat(2000), 200, incremental), Some(Amount::from_sat(2200)));
+        // fractional rates round up
+        let incremental = FeeRate::from_sat_per_kwu(1);
+        assert_eq!(Transaction::min_replacement_fee(Amount::from_sat(1000), 141, incremental), Some(Amount::from_sat(1001)));
+        assert_eq!(Transaction::min_replacement_fee(Amount::from_sat(1000), 0, FeeRate::ZERO), Some(Amount::from_sat(1000)));
+        // a fee, or a sum with the original fee, too large for a u64
+        let max_rate = FeeRate::from_sat_per_kwu(u64::max_value());
+        assert_eq!(Transaction::min_replacement_fee(Amount::from_sat(1000), 193, max_rate), None);
+        let max_fee = Amount::from_sat(u64::max_value());
+        assert_eq!(Transaction::min_replacement_fee(max_fee, 193, FeeRate::from_sat_per_vb(1).unwrap()), None);
+        assert_eq!(Transaction::min_replacement_fee(max_fee, 0, FeeRate::ZERO), Some(max_fee));
+    }
+
+    #[test]
+    fn test_output_address() {
+        let tx = Transaction::from_parts(vec![], vec![
+            TxOut { value: 10_000, script_pubkey: hex_script!("0014751e76e8199196d454941c45d1b3a323f1433bd6") },
+            TxOut { value: 0, script_pubkey: hex_script!("6a0b68656c6c6f20776f726c64") },
+        ]);
+
+        let address = tx.output_address(0, Network::Bitcoin).unwrap();
+        assert_eq!(address.to_string(), "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+        assert_eq!(address.script_pubkey(), tx.output[0].script_pubkey);
+        // an OP_RETURN output has no address
+        assert_eq!(tx.output_address(1, Network::Bitcoin), None);
+        assert_eq!(tx.output_address(2, Network::Bitcoin), None);
+    }
+
+    #[test]
+    fn test_is_coinbase_mature() {
+        use blockdata::constants;
+
+        let coinbase = constants::genesis_block(Network::Bitcoin).txdata[0].clone();
+        assert!(!coinbase.is_coinbase_mature(0, Network::Bitcoin));
+        assert!(!coinbase.is_coinbase_mature(3599, Network::Bitcoin));
+        assert!(coinbase.is_coinbase_mature(3600, Network::Bitcoin));
+        assert!(coinbase.is_coinbase_mature(3601, Network::Regtest));
+
+        let mut tx = coinbase.clone();
+        tx.input[0].previous_output.vout = 0;
+        assert!(tx.is_coinbase_mature(0, Network::Bitcoin));
+    }
+
+    #[test]
+    fn test_package_signals_rbf() {
+        let txid = "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456";
+        let spend = |prevout: OutPoint, sequence| Transaction::from_parts(
+            vec![TxIn {
+                previous_output: prevout,
+                script_sig: Script::new(),
+                sequence: sequence,
+                witness: vec![],
+            }],
+            vec![TxOut { value: 10_000, script_pubkey: hex_script!("0014751e76e8199196d454941c45d1b3a323f1433bd6") }],
+        );
+
+        let parent = spend(OutPoint::from_str(&format!("{}:0", txid)).unwrap(), 0xFFFFFFFD);
+        let child = spend(OutPoint { txid: parent.txid(), vout: 0 }, 0xFFFFFFFF);
+        assert!(parent.is_explicitly_rbf());
+        assert!(!child.is_explicitly_rbf());
+        assert!(!spend(child.input[0].previous_output, 0xFFFFFFFE).is_explicitly_rbf());
+
+        // the child inherits the parent's signal
+        assert!(package_signals_rbf(&[parent.clone(), child.clone()]));
+        assert!(!package_signals_rbf(&[child]));
+        assert!(!package_signals_rbf(&[]));
+        // a signalling child makes the package signal, though not its parent
+        let quiet_parent = spend(parent.input[0].previous_output, 0xFFFFFFFF);
+        let loud_child = spend(OutPoint { txid: quiet_parent.txid(), vout: 0 }, 0xFFFFFFFD);
+        assert!(package_signals_rbf(&[quiet_parent.clone(), loud_child]));
+        assert!(!package_signals_rbf(&[quiet_parent]));
+    }
+
+    #[test]
+    fn test_check_coinbase_script_size() {
+        use blockdata::constants;
+
+        let genesis = constants::genesis_block(Network::Bitcoin);
+        let mut coinbase = genesis.txdata[0].clone();
+        assert_eq!(coinbase.check_coinbase_script_size(), Ok(()));
+
+        coinbase.input[0].script_sig = Script::from(vec![0x51]);
+        assert_eq!(coinbase.check_coinbase_script_size(), Err(Error::CoinbaseScriptSize(1)));
+        coinbase.input[0].script_sig = Script::from(vec![0x51, 0x51]);
+        assert_eq!(coinbase.check_coinbase_script_size(), Ok(()));
+        coinbase.input[0].script_sig = Script::from(vec![0x51; 100]);
+        assert_eq!(coinbase.check_coinbase_script_size(), Ok(()));
+        coinbase.input[0].script_sig = Script::from(vec![0x51; 101]);
+        assert_eq!(coinbase.check_coinbase_script_size(), Err(Error::CoinbaseScriptSize(101)));
+
+        // only coinbases are held to the limit
+        coinbase.input[0].previous_output.vout = 0;
+        assert!(!coinbase.is_coin_base());
+        assert_eq!(coinbase.check_coinbase_script_size(), Ok(()));
+    }
+
+    #[test]
+    fn test_check_inputs_outputs() {
+        let input = |vout| TxIn {
+            previous_output: OutPoint { txid: Default::default(), vout: vout },
+            script_sig: Script::new(),
+            sequence: 0xffffffff,
+            witness: vec![],
+        };
+        let output = TxOut { value: 1, script_pubkey: Script::new() };
+
+        let tx = Transaction::from_parts(vec![input(0)], vec![output.clone()]);
+        assert_eq!(tx.check_inputs_outputs(), Ok(()));
+        let tx = Transaction::from_parts(vec![], vec![output.clone()]);
+        assert_eq!(tx.check_inputs_outputs(), Err(Error::NoInputs));
+        let tx = Transaction::from_parts(vec![input(0)], vec![]);
+        assert_eq!(tx.check_inputs_outputs(), Err(Error::NoOutputs));
+
+        // a coinbase spends the null prevout, but nothing else may
+        let tx = Transaction::from_parts(vec![input(0xffffffff)], vec![output.clone()]);
+        assert!(tx.is_coin_base());
+        assert_eq!(tx.check_inputs_outputs(), Ok(()));
+        let tx = Transaction::from_parts(vec![input(0), input(0xffffffff)], vec![output]);
+        assert_eq!(tx.check_inputs_outputs(), Err(Error::NullPrevout(1)));
+    }
+
+    #[test]
+    fn test_check_duplicate_inputs() {
+        let input = |vout| TxIn {
+            previous_output: OutPoint { txid: Default::default(), vout: vout },
+            script_sig: Script::new(),
+            sequence: 0xffffffff,
+            witness: vec![],
+        };
+        let mut tx = Transaction::from_parts(vec![input(0), input(1)], vec![]);
+        assert_eq!(tx.check_duplicate_inputs(), Ok(()));
+        tx.input.push(input(0));
+        assert_eq!(tx.check_duplicate_inputs(), Err(Error::DuplicateInput(tx.input[0].previous_output)));
+    }
+
+    #[test]
+    fn test_check_money_range() {
+        let script = hex_script!("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+        let output = |value| TxOut { value: value, script_pubkey: script.clone() };
+
+        // more than Bitcoin's 21M supply, but within Viacoin's
+        let tx = Transaction::from_parts(vec![], vec![output(22_000_000 * COIN_VALUE), output(1_000_000 * COIN_VALUE)]);
+        assert_eq!(tx.check_money_range(Network::Bitcoin), Ok(()));
+        let tx = Transaction::from_parts(vec![], vec![output(max_money(Network::Bitcoin))]);
+        assert_eq!(tx.check_money_range(Network::Bitcoin), Ok(()));
+
+        let tx = Transaction::from_parts(vec![], vec![output(1), output(max_money(Network::Bitcoin) + 1)]);
+        assert_eq!(tx.check_money_range(Network::Bitcoin), Err(Error::OutputValueOutOfRange(1)));
+        let tx = Transaction::from_parts(vec![], vec![output(max_money(Network::Bitcoin)), output(1)]);
+        assert_eq!(tx.check_money_range(Network::Bitcoin), Err(Error::OutputTotalOutOfRange));
+    }
+
+    #[test]
+    fn test_taproot_spend_type() {
+        let input = |witness: Vec<&str>| TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::new(),
+            sequence: 0xffffffff,
+            witness: witness.iter().map(|w| hex_bytes(w).unwrap()).collect(),
+        };
+        let sig = "7b5d614a4610bf9196775791fcc589597ca066dcd10048e004cd4c7341bb4bb90cee4705192f3f7db524e8067a5222c7f09baf29ef6b805b8327ecd1e5ab83ca";
+        let script = "20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac";
+        let control_block = "c1a2fc329a085d8cfc4fa28795993d7b666cee024e94c40115141b8e9be4a29fa41324300a84045033ec539f60c70d582c48b9acf04150da091694d83171b44ec9";
+
+        let key_path = TaprootSpend::KeyPath(hex_bytes(sig).unwrap());
+        assert_eq!(input(vec![sig]).taproot_spend_type(), Some(key_path.clone()));
+        // a signature with an explicit sighash type, and an annex which is ignored
+        let sig_all = format!("{}01", sig);
+        assert_eq!(input(vec![&sig_all]).taproot_spend_type(), Some(TaprootSpend::KeyPath(hex_bytes(&sig_all).unwrap())));
+        assert_eq!(input(vec![sig, "50"]).taproot_spend_type(), Some(key_path));
+
+        let script_path = TaprootSpend::ScriptPath {
+            script: Script::from(hex_bytes(script).unwrap()),
+            control_block: hex_bytes(control_block).unwrap(),
+        };
+        assert_eq!(input(vec![sig, script, control_block]).taproot_spend_type(), Some(script_path.clone()));
+        assert_eq!(input(vec![sig, script, control_block, "50aa"]).taproot_spend_type(), Some(script_path));
+
+        assert_eq!(input(vec![]).taproot_spend_type(), None);
+        assert_eq!(input(vec!["50"]).taproot_spend_type(), None);
+        assert_eq!(input(vec![&sig[2..]]).taproot_spend_type(), None);
+        assert_eq!(input(vec![script, &control_block[2..]]).taproot_spend_type(), None);
+    }
+
     #[test]
     #[cfg(feature="bitcoinconsensus")]
     fn test_transaction_verify () {
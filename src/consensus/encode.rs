@@ -41,9 +41,10 @@ use byteorder::{LittleEndian, WriteBytesExt, ReadBytesExt};
 use hex::encode as hex_encode;
 
 use bitcoin_bech32;
-use bitcoin_hashes::{sha256d, Hash as HashTrait};
+use bitcoin_hashes::{sha256, sha256d, Hash as HashTrait};
 use secp256k1;
 
+use network::constants::Network;
 use util::base58;
 use util::psbt;
 
@@ -95,6 +96,15 @@ pub enum Error {
     UnrecognizedNetworkCommand(String),
     /// Unexpected hex digit
     UnexpectedHexDigit(char),
+    /// An object for one network was used where another network was expected
+    NetworkMismatch {
+        /// The expected network
+        expected: Network,
+        /// The network of the object
+        actual: Network,
+    },
+    /// An uncompressed public key was used where only compressed keys are valid
+    UncompressedPubkey,
 }
 
 impl fmt::Display for Error {
@@ -115,6 +125,8 @@ impl fmt::Display for Error {
             Error::UnsupportedSegwitFlag(ref swflag) => write!(f, "{}: {}", error::Error::description(self), swflag),
             Error::UnrecognizedNetworkCommand(ref nwcmd) => write!(f, "{}: {}", error::Error::description(self), nwcmd),
             Error::UnexpectedHexDigit(ref d) => write!(f, "{}: {}", error::Error::description(self), d),
+            Error::NetworkMismatch { expected: ref e, actual: ref a } => write!(f, "{}: expected {}, actual {}", error::Error::description(self), e, a),
+            Error::UncompressedPubkey => f.write_str(error::Error::description(self)),
         }
     }
 }
@@ -136,7 +148,9 @@ impl error::Error for Error {
             | Error::UnsupportedWitnessVersion(..)
             | Error::UnsupportedSegwitFlag(..)
             | Error::UnrecognizedNetworkCommand(..)
-            | Error::UnexpectedHexDigit(..) => None,
+            | Error::UnexpectedHexDigit(..)
+            | Error::NetworkMismatch { .. }
+            | Error::UncompressedPubkey => None,
         }
     }
 
@@ -157,6 +171,8 @@ impl error::Error for Error {
             Error::UnsupportedSegwitFlag(..) => "unsupported segwit version",
             Error::UnrecognizedNetworkCommand(..) => "unrecognized network command",
             Error::UnexpectedHexDigit(..) => "unexpected hex digit",
+            Error::NetworkMismatch { .. } => "network mismatch",
+            Error::UncompressedPubkey => "uncompressed public key not allowed",
         }
     }
 }
@@ -701,6 +717,19 @@ impl<D: Decoder> Decodable<D> for sha256d::Hash {
     }
 }
 
+impl<S: Encoder> Encodable<S> for sha256::Hash {
+    fn consensus_encode(&self, s: &mut S) -> Result<(), self::Error> {
+        self.into_inner().consensus_encode(s)
+    }
+}
+
+impl<D: Decoder> Decodable<D> for sha256::Hash {
+    fn consensus_decode(d: &mut D) -> Result<sha256::Hash, self::Error> {
+        let inner: [u8; 32] = Decodable::consensus_decode(d)?;
+        Ok(sha256::Hash::from_slice(&inner).unwrap())
+    }
+}
+
 // Tests
 #[cfg(test)]
 mod tests {
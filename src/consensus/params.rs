@@ -20,28 +20,6 @@
 use network::constants::Network;
 use util::uint::Uint256;
 
-/// Lowest possible difficulty for Mainnet.
-const MAX_BITS_BITCOIN: Uint256 = Uint256([
-    0xffffffffffffffffu64,
-    0xffffffffffffffffu64,
-    0xffffffffffffffffu64,
-    0x00000000ffffffffu64,
-]);
-/// Lowest possible difficulty for Testnet.
-const MAX_BITS_TESTNET: Uint256 = Uint256([
-    0xffffffffffffffffu64,
-    0xffffffffffffffffu64,
-    0xffffffffffffffffu64,
-    0x00000000ffffffffu64,
-]);
-/// Lowest possible difficulty for Regtest.
-const MAX_BITS_REGTEST: Uint256 = Uint256([
-    0xffffffffffffffffu64,
-    0xffffffffffffffffu64,
-    0xffffffffffffffffu64,
-    0x7fffffffffffffffu64,
-]);
-
 #[derive(Debug, Clone)]
 /// Parameters that influence chain consensus.
 pub struct Params {
@@ -71,6 +49,11 @@ pub struct Params {
     pub allow_min_difficulty_blocks: bool,
     /// Determines whether retargeting is disabled for this network or not.
     pub no_pow_retargeting: bool,
+    /// The chain ID merge-mined blocks must carry in their version.
+    pub auxpow_chain_id: u32,
+    /// Whether the chain ID of merge-mined blocks is enforced, and parent
+    /// blocks with our own chain ID rejected.
+    pub strict_chain_id: bool,
 }
 
 impl Params {
@@ -85,11 +68,13 @@ impl Params {
                 bip66_height: 363725, // 00000000000000000379eaa19dce8c9b722d46ae6a57c2f1a988119488b50931
                 rule_change_activation_threshold: 1916, // 95%
                 miner_confirmation_window: 2016,
-                pow_limit: MAX_BITS_BITCOIN,
-                pow_target_spacing: 10 * 60,            // 10 minutes.
+                pow_limit: Network::Bitcoin.pow_limit(),
+                pow_target_spacing: 24,                 // 24 seconds.
                 pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
                 allow_min_difficulty_blocks: false,
                 no_pow_retargeting: false,
+                auxpow_chain_id: 14,
+                strict_chain_id: true,
             },
             Network::Testnet => Params {
                 network: Network::Testnet,
@@ -99,11 +84,13 @@ impl Params {
                 bip66_height: 330776, // 000000002104c8c45e99a8853285a3b592602a3ccde2b832481da85e9e4ba182
                 rule_change_activation_threshold: 1512, // 75%
                 miner_confirmation_window: 2016,
-                pow_limit: MAX_BITS_TESTNET,
-                pow_target_spacing: 10 * 60,            // 10 minutes.
+                pow_limit: Network::Testnet.pow_limit(),
+                pow_target_spacing: 24,                 // 24 seconds.
                 pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
                 allow_min_difficulty_blocks: true,
                 no_pow_retargeting: false,
+                auxpow_chain_id: 14,
+                strict_chain_id: false,
             },
             Network::Regtest => Params {
                 network: Network::Regtest,
@@ -113,11 +100,13 @@ impl Params {
                 bip66_height: 1251,                    // used only in rpc tests
                 rule_change_activation_threshold: 108, // 75%
                 miner_confirmation_window: 144,
-                pow_limit: MAX_BITS_REGTEST,
-                pow_target_spacing: 10 * 60,            // 10 minutes.
+                pow_limit: Network::Regtest.pow_limit(),
+                pow_target_spacing: 24,                 // 24 seconds.
                 pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
                 allow_min_difficulty_blocks: true,
                 no_pow_retargeting: true,
+                auxpow_chain_id: 14,
+                strict_chain_id: true,
             },
         }
     }
@@ -155,6 +155,7 @@ macro_rules! impl_array_newtype {
             }
         }
 
+        #[cfg(feature = "rand")]
         impl ::rand::Rand for $thing {
             #[inline]
             fn rand<R: ::rand::Rng>(r: &mut R) -> $thing {
@@ -39,6 +39,8 @@
 
 use consensus::encode::{Decodable, Encodable};
 use consensus::encode::{self, Encoder, Decoder};
+use util::amount::Amount;
+use util::uint::Uint256;
 
 /// Version of the protocol as appearing in network message headers
 pub const PROTOCOL_VERSION: u32 = 70001;
@@ -100,6 +102,65 @@ impl Network {
             Network::Regtest => 0xDAB5BFFA,
         }
     }
+
+    /// The total number of coins that will ever exist, and so the largest
+    /// amount any output or sum of outputs may hold
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use bitcoin::network::constants::Network;
+    ///
+    /// assert_eq!(Network::Bitcoin.max_money().as_sat(), 2_317_639_200_000_000);
+    /// ```
+    pub fn max_money(&self) -> Amount {
+        // Test networks share the mainnet issuance schedule
+        Amount::from_sat(23_176_392 * 100_000_000)
+    }
+
+    /// How many confirmations a coinbase transaction needs before its outputs
+    /// may be spent, on every network
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use bitcoin::network::constants::Network;
+    ///
+    /// assert_eq!(Network::Bitcoin.coinbase_maturity(), 3600);
+    /// ```
+    pub fn coinbase_maturity(&self) -> u32 {
+        // about a day of 24 second blocks
+        3600
+    }
+
+    /// The easiest target a block may have, that of difficulty 1. Scrypt
+    /// hashing is slower, so it is looser than Bitcoin's on every network
+    /// but regtest.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use bitcoin::blockdata::block::BlockHeader;
+    /// use bitcoin::network::constants::Network;
+    ///
+    /// assert_eq!(BlockHeader::compact_target_from_u256(&Network::Bitcoin.pow_limit()), 0x1e01ffff);
+    /// ```
+    pub fn pow_limit(&self) -> Uint256 {
+        match *self {
+            Network::Bitcoin | Network::Testnet => Uint256([
+                0xffffffffffffffffu64,
+                0xffffffffffffffffu64,
+                0xffffffffffffffffu64,
+                0x000001ffffffffffu64,
+            ]),
+            Network::Regtest => Uint256([
+                0xffffffffffffffffu64,
+                0xffffffffffffffffu64,
+                0xffffffffffffffffu64,
+                0x7fffffffffffffffu64,
+            ]),
+        }
+    }
 }
 
 impl<S: Encoder> Encodable<S> for Network {
@@ -126,6 +187,9 @@ impl<D: Decoder> Decodable<D> for Network {
 mod tests {
   use super::Network;
   use consensus::encode::{deserialize, serialize};
+  use blockdata::block::BlockHeader;
+  use blockdata::constants::genesis_block;
+  use util::amount::Amount;
 
   #[test]
   fn serialize_test() {
@@ -152,5 +216,20 @@ mod tests {
       assert_eq!("regtest".parse::<Network>().unwrap(), Network::Regtest);
       assert!("fakenet".parse::<Network>().is_err());
   }
-}
 
+  #[test]
+  fn max_money_test() {
+      // Viacoin issues 23,176,392 VIA, not Bitcoin's 21M
+      assert_eq!(Network::Bitcoin.max_money(), Amount::from_sat(2_317_639_200_000_000));
+      assert!(Network::Bitcoin.max_money() > Amount::from_sat(21_000_000 * 100_000_000));
+      assert_eq!(Network::Testnet.max_money(), Network::Bitcoin.max_money());
+  }
+
+  #[test]
+  fn pow_limit_test() {
+      assert_eq!(BlockHeader::compact_target_from_u256(&Network::Bitcoin.pow_limit()), 0x1e01ffff);
+      assert_eq!(BlockHeader::compact_target_from_u256(&Network::Regtest.pow_limit()), 0x207fffff);
+      // the genesis block is mined at the limit
+      assert_eq!(genesis_block(Network::Bitcoin).header.bits, BlockHeader::compact_target_from_u256(&Network::Bitcoin.pow_limit()));
+  }
+}
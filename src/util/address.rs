@@ -68,6 +68,19 @@ pub enum Payload {
     WitnessProgram(WitnessProgram),
 }
 
+/// The standard output types an address can describe
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AddressType {
+    /// pay-to-pubkey-hash
+    P2pkh,
+    /// pay-to-script-hash
+    P2sh,
+    /// pay-to-witness-pubkey-hash
+    P2wpkh,
+    /// pay-to-witness-script-hash
+    P2wsh,
+}
+
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// A Bitcoin address
 pub struct Address {
@@ -119,20 +132,30 @@ impl Address {
 
     /// Create a pay to script address that embeds a witness pay to public key
     /// This is a segwit address type that looks familiar (as p2sh) to legacy clients
-    pub fn p2shwpkh (pk: &key::PublicKey, network: Network) -> Address {
+    ///
+    /// Also returns the `OP_0 <20-byte key hash>` redeem script, which goes in
+    /// the scriptSig when spending. Uncompressed keys are rejected, as they are
+    /// not valid in witness programs.
+    pub fn p2shwpkh (pk: &key::PublicKey, network: Network) -> Result<(Address, script::Script), encode::Error> {
+        if !pk.compressed {
+            return Err(encode::Error::UncompressedPubkey);
+        }
+
         let mut hash_engine = hash160::Hash::engine();
         pk.write_into(&mut hash_engine);
 
-        let builder = script::Builder::new()
+        let redeem_script = script::Builder::new()
             .push_int(0)
-            .push_slice(&hash160::Hash::from_engine(hash_engine)[..]);
+            .push_slice(&hash160::Hash::from_engine(hash_engine)[..])
+            .into_script();
 
-        Address {
+        let address = Address {
             network: network,
             payload: Payload::ScriptHash(
-                hash160::Hash::hash(builder.into_script().as_bytes())
+                hash160::Hash::hash(redeem_script.as_bytes())
             )
-        }
+        };
+        Ok((address, redeem_script))
     }
 
     /// Create a witness pay to script hash address
@@ -170,6 +193,49 @@ impl Address {
         }
     }
 
+    /// Constructs the address an output script pays to, if it is of a standard
+    /// type: P2PKH, P2SH or a version 0 witness program.
+    pub fn from_script(script: &script::Script, network: Network) -> Option<Address> {
+        let payload = if script.is_p2pkh() {
+            Payload::PubkeyHash(hash160::Hash::from_slice(&script[3..23]).expect("20 bytes"))
+        } else if script.is_p2sh() {
+            Payload::ScriptHash(hash160::Hash::from_slice(&script[2..22]).expect("20 bytes"))
+        } else if script.is_v0_p2wpkh() || script.is_v0_p2wsh() {
+            Payload::WitnessProgram(
+                // unwrap is safe as the program length was checked above
+                WitnessProgram::new(u5::try_from_u8(0).expect("0<32"),
+                                    script[2..].to_vec(),
+                                    Address::bech_network(network)).unwrap()
+            )
+        } else {
+            return None;
+        };
+
+        Some(Address {
+            network: network,
+            payload: payload,
+        })
+    }
+
+    /// Get the type of the address, if it is one of the standard types. Witness
+    /// programs other than v0 keyhash and scripthash ones are not recognized.
+    pub fn address_type(&self) -> Option<AddressType> {
+        match self.payload {
+            Payload::PubkeyHash(_) => Some(AddressType::P2pkh),
+            Payload::ScriptHash(_) => Some(AddressType::P2sh),
+            Payload::WitnessProgram(ref witprog) => {
+                if witprog.version().to_u8() != 0 {
+                    return None;
+                }
+                match witprog.program().len() {
+                    20 => Some(AddressType::P2wpkh),
+                    32 => Some(AddressType::P2wsh),
+                    _ => None,
+                }
+            }
+        }
+    }
+
     #[inline]
     /// convert Network to bech32 network (this should go away soon)
     fn bech_network (network: Network) -> bitcoin_bech32::constants::Network {
@@ -180,6 +246,34 @@ impl Address {
         }
     }
 
+    /// Whether this address can be used on the given network. Testnet and
+    /// regtest share their base58 prefixes, so legacy addresses are valid on
+    /// both; bech32 addresses are only valid on the network of their prefix.
+    pub fn is_valid_for_network(&self, network: Network) -> bool {
+        match (self.network, network) {
+            (a, b) if a == b => true,
+            (Network::Testnet, Network::Regtest) | (Network::Regtest, Network::Testnet) => {
+                match self.payload {
+                    Payload::WitnessProgram(_) => false,
+                    Payload::PubkeyHash(_) | Payload::ScriptHash(_) => true,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Generates a script pubkey spending to this address, erroring if the
+    /// address does not belong to `network`
+    pub fn script_pubkey_for_network(&self, network: Network) -> Result<script::Script, encode::Error> {
+        if !self.is_valid_for_network(network) {
+            return Err(encode::Error::NetworkMismatch {
+                expected: network,
+                actual: self.network,
+            });
+        }
+        Ok(self.script_pubkey())
+    }
+
     /// Generates a script pubkey spending to this address
     pub fn script_pubkey(&self) -> script::Script {
         match self.payload {
@@ -429,6 +523,63 @@ mod tests {
         let key = hex_key!("033bc8c83c52df5712229a2f72206d90192366c36428cb0c12b6af98324d97bfbc");
         let addr = Address::p2wpkh(&key, Bitcoin);
         assert_eq!(&addr.to_string(), "bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw");
+        assert_eq!(addr.address_type(), Some(AddressType::P2wpkh));
+    }
+
+    #[test]
+    fn test_p2shwpkh() {
+        let key = hex_key!("026c468be64d22761c30cd2f12cbc7de255d592d7904b1bab07236897cc4c2e766");
+        let (addr, redeem_script) = Address::p2shwpkh(&key, Bitcoin).unwrap();
+        assert_eq!(&addr.to_string(), "3QBRmWNqqBGme9er7fMkGqtZtp4gjMFxhE");
+        assert_eq!(redeem_script, hex_script!("0014177a9ed6d4082868f17ed0cf66364ad8d95ffc4d"));
+        assert_eq!(addr, Address::p2sh(&redeem_script, Bitcoin));
+        assert_eq!(&Address::p2shwpkh(&key, Testnet).unwrap().0.to_string(), "2NFjdqFJsSdn7qwHPnnyctnsq7AGrarS7Rj");
+
+        let uncompressed = key::PublicKey { compressed: false, key: key.key };
+        match Address::p2shwpkh(&uncompressed, Bitcoin) {
+            Err(encode::Error::UncompressedPubkey) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_network_mismatch() {
+        let addr = Address::from_str("VkRhad19WpsYdnp1A7sCdiguhyJA3zS4Su").unwrap();
+        assert_eq!(addr.script_pubkey_for_network(Bitcoin).unwrap(), addr.script_pubkey());
+        match addr.script_pubkey_for_network(Testnet) {
+            Err(encode::Error::NetworkMismatch { expected: Testnet, actual: Bitcoin }) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let addr = Address::from_str("mqwpxxvfv3QbM8PU8uBx2jaNt9btQqvQNx").unwrap();
+        assert!(addr.is_valid_for_network(Testnet));
+        assert!(addr.is_valid_for_network(Regtest));
+        assert!(!addr.is_valid_for_network(Bitcoin));
+
+        let addr = Address::from_str("tb1qwfjcnutuv4djp2qr73vejvvs0gzs6pu9gypkwh").unwrap();
+        assert!(addr.is_valid_for_network(Testnet));
+        assert!(!addr.is_valid_for_network(Regtest));
+        match addr.script_pubkey_for_network(Regtest) {
+            Err(encode::Error::NetworkMismatch { expected: Regtest, actual: Testnet }) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_script() {
+        for addr in &[
+            "VkRhad19WpsYdnp1A7sCdiguhyJA3zS4Su",
+            "33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k",
+            "bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw",
+            "bc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxswvvzej",
+        ] {
+            let addr = Address::from_str(addr).unwrap();
+            assert_eq!(Address::from_script(&addr.script_pubkey(), Bitcoin), Some(addr));
+        }
+
+        let p2pk = hex_script!("21021aeaf2f8638a129a3156fbe7e5ef635226b0bafd495ff03afe2c843d7e3a4b51ac");
+        assert_eq!(Address::from_script(&p2pk, Bitcoin), None);
+        assert_eq!(Address::from_script(&Script::new(), Bitcoin), None);
     }
 
 
@@ -0,0 +1,399 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Amounts
+//!
+//! A type-safe representation of a quantity of coins, counted in satoshis.
+
+use std::{error, fmt, ops};
+use std::str::FromStr;
+
+/// The number of satoshis in one VIA
+const SATOSHI_PER_VIA: u64 = 100_000_000;
+
+/// A unit in which amounts can be expressed
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Denomination {
+    /// VIA
+    Via,
+    /// mVIA, a thousandth of a VIA
+    MilliVia,
+    /// uVIA, a millionth of a VIA
+    MicroVia,
+    /// satoshi, a hundred-millionth of a VIA
+    Satoshi,
+}
+
+impl Denomination {
+    /// The number of decimal places of a satoshi amount in this unit
+    pub fn precision(self) -> usize {
+        match self {
+            Denomination::Via => 8,
+            Denomination::MilliVia => 5,
+            Denomination::MicroVia => 2,
+            Denomination::Satoshi => 0,
+        }
+    }
+}
+
+impl fmt::Display for Denomination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Denomination::Via => "VIA",
+            Denomination::MilliVia => "mVIA",
+            Denomination::MicroVia => "uVIA",
+            Denomination::Satoshi => "satoshi",
+        })
+    }
+}
+
+impl FromStr for Denomination {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Denomination, ParseAmountError> {
+        match s {
+            "VIA" => Ok(Denomination::Via),
+            "mVIA" => Ok(Denomination::MilliVia),
+            "uVIA" => Ok(Denomination::MicroVia),
+            "satoshi" | "sat" => Ok(Denomination::Satoshi),
+            _ => Err(ParseAmountError::UnknownDenomination(s.to_owned())),
+        }
+    }
+}
+
+/// An error parsing an amount or denomination
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ParseAmountError {
+    /// The amount was negative
+    Negative,
+    /// The amount does not fit in 64 bits of satoshis
+    TooBig,
+    /// The amount has more decimal places than a satoshi allows
+    TooPrecise,
+    /// The amount was empty or had a misplaced decimal point
+    InvalidFormat,
+    /// A character other than a digit or decimal point was found
+    InvalidCharacter(char),
+    /// The denomination was not recognized
+    UnknownDenomination(String),
+}
+
+impl fmt::Display for ParseAmountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseAmountError::InvalidCharacter(c) => write!(f, "invalid character {:?} in amount", c),
+            ParseAmountError::UnknownDenomination(ref d) => write!(f, "unknown denomination {}", d),
+            _ => f.write_str(error::Error::description(self)),
+        }
+    }
+}
+
+impl error::Error for ParseAmountError {
+    fn cause(&self) -> Option<&error::Error> { None }
+
+    fn description(&self) -> &str {
+        match *self {
+            ParseAmountError::Negative => "amount is negative",
+            ParseAmountError::TooBig => "amount is too big",
+            ParseAmountError::TooPrecise => "amount has too many decimal places",
+            ParseAmountError::InvalidFormat => "invalid amount format",
+            ParseAmountError::InvalidCharacter(_) => "invalid character in amount",
+            ParseAmountError::UnknownDenomination(_) => "unknown denomination",
+        }
+    }
+}
+
+/// An amount of coins, in satoshis
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct Amount(u64);
+
+impl Amount {
+    /// The zero amount
+    pub const ZERO: Amount = Amount(0);
+    /// Exactly one satoshi
+    pub const ONE_SAT: Amount = Amount(1);
+    /// Exactly one VIA
+    pub const ONE_VIA: Amount = Amount(SATOSHI_PER_VIA);
+
+    /// Create an amount from a number of satoshis
+    pub fn from_sat(satoshi: u64) -> Amount {
+        Amount(satoshi)
+    }
+
+    /// The number of satoshis in this amount
+    pub fn as_sat(self) -> u64 {
+        self.0
+    }
+
+    /// Parse a decimal amount expressed in `denom`, without a unit suffix
+    pub fn from_str_in(s: &str, denom: Denomination) -> Result<Amount, ParseAmountError> {
+        if s.starts_with('-') {
+            return Err(ParseAmountError::Negative);
+        }
+        let (int_part, frac_part) = match s.find('.') {
+            Some(pos) => (&s[..pos], &s[pos + 1..]),
+            None => (s, ""),
+        };
+        if int_part.is_empty() && frac_part.is_empty() {
+            return Err(ParseAmountError::InvalidFormat);
+        }
+        if frac_part.len() > denom.precision() {
+            // trailing zeros beyond the precision are harmless
+            if frac_part[denom.precision()..].chars().any(|c| c != '0') {
+                return Err(ParseAmountError::TooPrecise);
+            }
+        }
+
+        let mut value = 0u64;
+        let digits = int_part.chars().chain(frac_part.chars().chain(::std::iter::repeat('0')).take(denom.precision()));
+        for c in digits {
+            let digit = match c.to_digit(10) {
+                Some(d) => d as u64,
+                None if c == '.' => return Err(ParseAmountError::InvalidFormat),
+                None => return Err(ParseAmountError::InvalidCharacter(c)),
+            };
+            value = value.checked_mul(10)
+                .and_then(|v| v.checked_add(digit))
+                .ok_or(ParseAmountError::TooBig)?;
+        }
+        Ok(Amount(value))
+    }
+
+    /// Format the amount in `denom`, with as many decimal places as a
+    /// satoshi needs and without a unit suffix
+    pub fn to_string_in(self, denom: Denomination) -> String {
+        let precision = denom.precision();
+        if precision == 0 {
+            return self.0.to_string();
+        }
+        let unit = 10u64.pow(precision as u32);
+        format!("{}.{:0width$}", self.0 / unit, self.0 % unit, width = precision)
+    }
+
+    /// Checked addition, returning `None` on overflow
+    pub fn checked_add(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_add(rhs.0).map(Amount)
+    }
+
+    /// Checked subtraction, returning `None` if `rhs` is larger
+    pub fn checked_sub(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_sub(rhs.0).map(Amount)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.to_string_in(Denomination::Via), Denomination::Via)
+    }
+}
+
+impl ops::Add for Amount {
+    type Output = Amount;
+
+    fn add(self, rhs: Amount) -> Amount {
+        self.checked_add(rhs).expect("Amount addition overflowed")
+    }
+}
+
+impl ops::AddAssign for Amount {
+    fn add_assign(&mut self, rhs: Amount) {
+        *self = *self + rhs
+    }
+}
+
+impl ops::Sub for Amount {
+    type Output = Amount;
+
+    fn sub(self, rhs: Amount) -> Amount {
+        self.checked_sub(rhs).expect("Amount subtraction underflowed")
+    }
+}
+
+impl ops::SubAssign for Amount {
+    fn sub_assign(&mut self, rhs: Amount) {
+        *self = *self - rhs
+    }
+}
+
+/// A signed amount of coins, in satoshis, for values which may be negative
+/// such as differences of amounts
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct SignedAmount(i64);
+
+impl SignedAmount {
+    /// The zero amount
+    pub const ZERO: SignedAmount = SignedAmount(0);
+
+    /// Create an amount from a number of satoshis
+    pub fn from_sat(satoshi: i64) -> SignedAmount {
+        SignedAmount(satoshi)
+    }
+
+    /// The number of satoshis in this amount
+    pub fn as_sat(self) -> i64 {
+        self.0
+    }
+
+    /// Whether the amount is below zero
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    /// The amount as an unsigned `Amount`, or `None` if it is negative
+    pub fn to_unsigned(self) -> Option<Amount> {
+        if self.is_negative() { None } else { Some(Amount(self.0 as u64)) }
+    }
+
+    /// Checked addition, returning `None` on overflow
+    pub fn checked_add(self, rhs: SignedAmount) -> Option<SignedAmount> {
+        self.0.checked_add(rhs.0).map(SignedAmount)
+    }
+
+    /// Checked subtraction, returning `None` on overflow
+    pub fn checked_sub(self, rhs: SignedAmount) -> Option<SignedAmount> {
+        self.0.checked_sub(rhs.0).map(SignedAmount)
+    }
+}
+
+impl Amount {
+    /// The amount as a `SignedAmount`, or `None` if it is above `i64::MAX` satoshis
+    pub fn to_signed(self) -> Option<SignedAmount> {
+        if self.0 > i64::max_value() as u64 { None } else { Some(SignedAmount(self.0 as i64)) }
+    }
+}
+
+impl fmt::Display for SignedAmount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the magnitude of `i64::MIN` only fits unsigned
+        let magnitude = Amount(if self.is_negative() { (self.0 as u64).wrapping_neg() } else { self.0 as u64 });
+        let sign = if self.is_negative() { "-" } else { "" };
+        write!(f, "{}{} {}", sign, magnitude.to_string_in(Denomination::Via), Denomination::Via)
+    }
+}
+
+impl ops::Add for SignedAmount {
+    type Output = SignedAmount;
+
+    fn add(self, rhs: SignedAmount) -> SignedAmount {
+        self.checked_add(rhs).expect("SignedAmount addition overflowed")
+    }
+}
+
+impl ops::Sub for SignedAmount {
+    type Output = SignedAmount;
+
+    fn sub(self, rhs: SignedAmount) -> SignedAmount {
+        self.checked_sub(rhs).expect("SignedAmount subtraction overflowed")
+    }
+}
+
+impl ops::Neg for SignedAmount {
+    type Output = SignedAmount;
+
+    fn neg(self) -> SignedAmount {
+        SignedAmount(self.0.checked_neg().expect("SignedAmount negation overflowed"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Amount, Denomination, ParseAmountError, SignedAmount};
+
+    #[test]
+    fn test_amount_arithmetic() {
+        let a = Amount::from_sat(150_000_000);
+        assert_eq!(a.as_sat(), 150_000_000);
+        assert_eq!(a + Amount::ONE_SAT, Amount::from_sat(150_000_001));
+        assert_eq!(a - Amount::ONE_VIA, Amount::from_sat(50_000_000));
+        assert_eq!(Amount::ONE_SAT.checked_sub(a), None);
+        assert_eq!(Amount::from_sat(u64::max_value()).checked_add(Amount::ONE_SAT), None);
+
+        let mut b = Amount::ZERO;
+        b += a;
+        b -= Amount::ONE_SAT;
+        assert_eq!(b, Amount::from_sat(149_999_999));
+        assert!(b < a);
+    }
+
+    #[test]
+    fn test_signed_amount() {
+        let a = SignedAmount::from_sat(-150_000_000);
+        assert!(a.is_negative());
+        assert_eq!(a.to_unsigned(), None);
+        assert_eq!(-a, Amount::from_sat(150_000_000).to_signed().unwrap());
+        assert_eq!((-a).to_unsigned(), Some(Amount::from_sat(150_000_000)));
+        assert_eq!(a + SignedAmount::from_sat(200_000_000), SignedAmount::from_sat(50_000_000));
+        assert_eq!(SignedAmount::ZERO - SignedAmount::from_sat(1), SignedAmount::from_sat(-1));
+        assert_eq!(SignedAmount::from_sat(i64::min_value()).checked_sub(SignedAmount::from_sat(1)), None);
+        assert_eq!(Amount::from_sat(u64::max_value()).to_signed(), None);
+
+        assert_eq!(a.to_string(), "-1.50000000 VIA");
+        assert_eq!(SignedAmount::from_sat(1).to_string(), "0.00000001 VIA");
+        assert_eq!(SignedAmount::from_sat(i64::min_value()).to_string(), "-92233720368.54775808 VIA");
+    }
+
+    #[test]
+    fn test_amount_display() {
+        assert_eq!(Amount::from_sat(150_000_000).to_string(), "1.50000000 VIA");
+        assert_eq!(Amount::ONE_SAT.to_string(), "0.00000001 VIA");
+        assert_eq!(Amount::ZERO.to_string(), "0.00000000 VIA");
+    }
+
+    #[test]
+    fn test_to_string_in() {
+        let a = Amount::from_sat(123_456_789);
+        assert_eq!(a.to_string_in(Denomination::Via), "1.23456789");
+        assert_eq!(a.to_string_in(Denomination::MilliVia), "1234.56789");
+        assert_eq!(a.to_string_in(Denomination::MicroVia), "1234567.89");
+        assert_eq!(a.to_string_in(Denomination::Satoshi), "123456789");
+
+        assert_eq!(Amount::ONE_SAT.to_string_in(Denomination::MilliVia), "0.00001");
+        assert_eq!(Amount::ZERO.to_string_in(Denomination::MicroVia), "0.00");
+        assert_eq!(Amount::ONE_VIA.to_string_in(Denomination::Satoshi), "100000000");
+    }
+
+    #[test]
+    fn test_from_str_in() {
+        let denoms = [Denomination::Via, Denomination::MilliVia, Denomination::MicroVia, Denomination::Satoshi];
+        for &sat in &[0, 1, 99, 123_456_789, 2_317_639_200_000_000, u64::max_value()] {
+            for &denom in &denoms {
+                let a = Amount::from_sat(sat);
+                assert_eq!(Amount::from_str_in(&a.to_string_in(denom), denom), Ok(a));
+            }
+        }
+
+        assert_eq!(Amount::from_str_in("1.5", Denomination::Via), Ok(Amount::from_sat(150_000_000)));
+        assert_eq!(Amount::from_str_in(".5", Denomination::MilliVia), Ok(Amount::from_sat(50_000)));
+        assert_eq!(Amount::from_str_in("2.", Denomination::MicroVia), Ok(Amount::from_sat(200)));
+        assert_eq!(Amount::from_str_in("0.100000000", Denomination::Via), Ok(Amount::from_sat(10_000_000)));
+
+        assert_eq!(Amount::from_str_in("0.001", Denomination::MicroVia), Err(ParseAmountError::TooPrecise));
+        assert_eq!(Amount::from_str_in("1.5", Denomination::Satoshi), Err(ParseAmountError::TooPrecise));
+        assert_eq!(Amount::from_str_in("-1", Denomination::Via), Err(ParseAmountError::Negative));
+        assert_eq!(Amount::from_str_in("184467440737.09551616", Denomination::Via), Err(ParseAmountError::TooBig));
+        assert_eq!(Amount::from_str_in("", Denomination::Via), Err(ParseAmountError::InvalidFormat));
+        assert_eq!(Amount::from_str_in(".", Denomination::Via), Err(ParseAmountError::InvalidFormat));
+        assert_eq!(Amount::from_str_in("1.2.3", Denomination::Via), Err(ParseAmountError::InvalidFormat));
+        assert_eq!(Amount::from_str_in("1,5", Denomination::Via), Err(ParseAmountError::InvalidCharacter(',')));
+    }
+
+    #[test]
+    fn test_denomination_str() {
+        for &denom in &[Denomination::Via, Denomination::MilliVia, Denomination::MicroVia, Denomination::Satoshi] {
+            assert_eq!(denom.to_string().parse::<Denomination>(), Ok(denom));
+        }
+        assert_eq!("sat".parse::<Denomination>(), Ok(Denomination::Satoshi));
+        assert_eq!("BTC".parse::<Denomination>(), Err(ParseAmountError::UnknownDenomination("BTC".to_owned())));
+    }
+}
@@ -0,0 +1,155 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Base64 encoder and decoder
+//!
+//! Standard alphabet with `=` padding, as used for signed messages and PSBTs.
+
+use std::{error, fmt};
+
+static CHARS: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// An error that might occur during base64 decoding
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Error {
+    /// Invalid character encountered
+    BadByte(u8),
+    /// The length of the input was not a multiple of 4
+    InvalidLength(usize),
+    /// Padding was misplaced or had non-zero trailing bits
+    BadPadding,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::BadByte(b) => write!(f, "invalid base64 character 0x{:x}", b),
+            Error::InvalidLength(ell) => write!(f, "length {} invalid for base64", ell),
+            Error::BadPadding => f.write_str("invalid base64 padding"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> { None }
+    fn description(&self) -> &'static str {
+        match *self {
+            Error::BadByte(_) => "invalid base64 character",
+            Error::InvalidLength(_) => "invalid length for base64",
+            Error::BadPadding => "invalid base64 padding",
+        }
+    }
+}
+
+fn decode_char(c: u8) -> Result<u32, Error> {
+    match c {
+        b'A'...b'Z' => Ok((c - b'A') as u32),
+        b'a'...b'z' => Ok((c - b'a' + 26) as u32),
+        b'0'...b'9' => Ok((c - b'0' + 52) as u32),
+        b'+' => Ok(62),
+        b'/' => Ok(63),
+        _ => Err(Error::BadByte(c)),
+    }
+}
+
+/// Decode a padded base64 string into a byte vector
+pub fn from(data: &str) -> Result<Vec<u8>, Error> {
+    let data = data.as_bytes();
+    if data.len() % 4 != 0 {
+        return Err(Error::InvalidLength(data.len()));
+    }
+
+    let mut ret = Vec::with_capacity(data.len() / 4 * 3);
+    for (n, chunk) in data.chunks(4).enumerate() {
+        let last = n == data.len() / 4 - 1;
+        let pad = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if pad > 2 || (pad > 0 && !last) {
+            return Err(Error::BadPadding);
+        }
+
+        let mut acc = 0u32;
+        for &c in &chunk[..4 - pad] {
+            acc = (acc << 6) | decode_char(c)?;
+        }
+        acc <<= 6 * pad as u32;
+        // Reject non-canonical encodings with garbage in the trailing bits
+        if acc & ((1 << (8 * pad as u32)) - 1) != 0 {
+            return Err(Error::BadPadding);
+        }
+
+        ret.push((acc >> 16) as u8);
+        if pad < 2 {
+            ret.push((acc >> 8) as u8);
+        }
+        if pad < 1 {
+            ret.push(acc as u8);
+        }
+    }
+    Ok(ret)
+}
+
+/// Directly encode a slice as base64
+pub fn encode_slice(data: &[u8]) -> String {
+    let mut ret = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let mut acc = 0u32;
+        for i in 0..3 {
+            acc <<= 8;
+            if i < chunk.len() {
+                acc |= chunk[i] as u32;
+            }
+        }
+        for i in 0..4 {
+            if i <= chunk.len() {
+                ret.push(CHARS[((acc >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                ret.push('=');
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_encode() {
+        // RFC 4648 test vectors
+        assert_eq!(&encode_slice(b""), "");
+        assert_eq!(&encode_slice(b"f"), "Zg==");
+        assert_eq!(&encode_slice(b"fo"), "Zm8=");
+        assert_eq!(&encode_slice(b"foo"), "Zm9v");
+        assert_eq!(&encode_slice(b"foob"), "Zm9vYg==");
+        assert_eq!(&encode_slice(b"fooba"), "Zm9vYmE=");
+        assert_eq!(&encode_slice(b"foobar"), "Zm9vYmFy");
+        assert_eq!(&encode_slice(&[0xff, 0xfe, 0x00]), "//4A");
+    }
+
+    #[test]
+    fn test_base64_decode() {
+        assert_eq!(from("").unwrap(), b"".to_vec());
+        assert_eq!(from("Zg==").unwrap(), b"f".to_vec());
+        assert_eq!(from("Zm8=").unwrap(), b"fo".to_vec());
+        assert_eq!(from("Zm9vYmFy").unwrap(), b"foobar".to_vec());
+        assert_eq!(from("//4A").unwrap(), vec![0xff, 0xfe, 0x00]);
+
+        assert_eq!(from("Zm9"), Err(Error::InvalidLength(3)));
+        assert_eq!(from("Zm9!"), Err(Error::BadByte(b'!')));
+        assert_eq!(from("Zg==Zg=="), Err(Error::BadPadding));
+        assert_eq!(from("Z==="), Err(Error::BadPadding));
+        assert_eq!(from("Zh=="), Err(Error::BadPadding));
+    }
+}
@@ -0,0 +1,381 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP158 Compact Block Filters
+//!
+//! Golomb-coded set filters over the scripts of a block, queried by light
+//! clients, and the chain of filter headers committing to them as served in
+//! the BIP157 `cfheaders` and `cfcheckpt` messages.
+
+use std::io::Cursor;
+
+use bitcoin_hashes::{sha256d, Hash, HashEngine};
+use byteorder::{ByteOrder, LittleEndian};
+
+use consensus::encode::{self, Decodable, VarInt};
+use util::address::Address;
+
+/// Golomb-Rice parameter of the basic filter
+const P: u8 = 19;
+/// Inverse false positive rate of the basic filter
+const M: u64 = 784931;
+
+/// A BIP158 compact filter over the block with the given hash, which keys
+/// the hashing of its elements
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BlockFilter {
+    /// The hash of the block the filter is for
+    pub block_hash: sha256d::Hash,
+    /// The serialized filter: the element count then the Golomb-coded set
+    pub content: Vec<u8>,
+}
+
+impl BlockFilter {
+    /// Wraps a serialized filter, e.g. from a `cfilter` message
+    pub fn new(block_hash: sha256d::Hash, content: Vec<u8>) -> BlockFilter {
+        BlockFilter {
+            block_hash: block_hash,
+            content: content,
+        }
+    }
+
+    /// Builds a filter containing the given elements, for the basic filter
+    /// the output scripts of the block and those spent by its inputs
+    pub fn from_elements(block_hash: sha256d::Hash, elements: &[&[u8]]) -> BlockFilter {
+        let mut elements = elements.to_vec();
+        elements.sort();
+        elements.dedup();
+        let filter = BlockFilter::new(block_hash, vec![]);
+        let n = elements.len() as u64;
+        // a slice can't hold the 2^44 elements it'd take to overflow `n * M`
+        let mut values: Vec<u64> = elements.iter()
+            .map(|e| filter.hash_to_range(e, n).expect("element count fits the range"))
+            .collect();
+        values.sort();
+
+        let mut content = encode::serialize(&VarInt(n));
+        let mut writer = BitWriter::new(&mut content);
+        let mut last = 0;
+        for value in values {
+            writer.golomb_rice(value - last);
+            last = value;
+        }
+        writer.flush();
+        BlockFilter::new(block_hash, content)
+    }
+
+    /// Whether any of the elements may be in the filter. False positives
+    /// occur at a rate of about `1 / 784931` per element, false negatives
+    /// never do. A malformed filter, including one claiming more elements
+    /// than its content can encode, matches nothing.
+    pub fn match_any(&self, query: &[&[u8]]) -> bool {
+        let mut cursor = Cursor::new(&self.content[..]);
+        let n = match VarInt::consensus_decode(&mut cursor) {
+            Ok(n) => n.0,
+            Err(_) => return false,
+        };
+        if n == 0 || query.is_empty() {
+            return false;
+        }
+        // every element takes at least `P + 1` bits
+        let data = &self.content[cursor.position() as usize..];
+        match n.checked_mul(P as u64 + 1) {
+            Some(bits) if bits <= data.len() as u64 * 8 => {},
+            _ => return false,
+        }
+        let mut queries = Vec::with_capacity(query.len());
+        for e in query {
+            match self.hash_to_range(e, n) {
+                Some(q) => queries.push(q),
+                None => return false,
+            }
+        }
+        queries.sort();
+
+        let mut reader = BitReader::new(data);
+        let mut value = 0;
+        let mut queries = queries.into_iter().peekable();
+        for _ in 0..n {
+            value += match reader.golomb_rice() {
+                Some(delta) => delta,
+                None => return false,
+            };
+            while let Some(&q) = queries.peek() {
+                if q < value {
+                    queries.next();
+                } else {
+                    break;
+                }
+            }
+            match queries.peek() {
+                Some(&q) if q == value => return true,
+                Some(_) => {},
+                None => return false,
+            }
+        }
+        false
+    }
+
+    /// Whether the filter may match outputs paying to, or inputs spending
+    /// from, the address
+    pub fn match_address(&self, addr: &Address) -> bool {
+        self.match_any(&[&addr.script_pubkey()[..]])
+    }
+
+    /// Maps an element uniformly onto `[0, n * M)`, keyed by the block hash,
+    /// or `None` if `n * M` overflows
+    fn hash_to_range(&self, element: &[u8], n: u64) -> Option<u64> {
+        let k0 = LittleEndian::read_u64(&self.block_hash[0..8]);
+        let k1 = LittleEndian::read_u64(&self.block_hash[8..16]);
+        n.checked_mul(M).map(|range| mul_high(siphash24(k0, k1, element), range))
+    }
+}
+
+/// The high 64 bits of the 128-bit product of `a` and `b`
+fn mul_high(a: u64, b: u64) -> u64 {
+    let (a_hi, a_lo) = (a >> 32, a & 0xffffffff);
+    let (b_hi, b_lo) = (b >> 32, b & 0xffffffff);
+    let lo_lo = a_lo * b_lo;
+    let hi_lo = a_hi * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_hi = a_hi * b_hi;
+    let cross = (lo_lo >> 32) + (hi_lo & 0xffffffff) + lo_hi;
+    hi_hi + (hi_lo >> 32) + (cross >> 32)
+}
+
+/// SipHash-2-4 of `data` with the key `(k0, k1)`
+fn siphash24(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    let mut v = [
+        k0 ^ 0x736f6d6570736575,
+        k1 ^ 0x646f72616e646f6d,
+        k0 ^ 0x6c7967656e657261,
+        k1 ^ 0x7465646279746573,
+    ];
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]); v[1] = v[1].rotate_left(13); v[1] ^= v[0]; v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]); v[3] = v[3].rotate_left(16); v[3] ^= v[2];
+        v[0] = v[0].wrapping_add(v[3]); v[3] = v[3].rotate_left(21); v[3] ^= v[0];
+        v[2] = v[2].wrapping_add(v[1]); v[1] = v[1].rotate_left(17); v[1] ^= v[2]; v[2] = v[2].rotate_left(32);
+    }
+
+    // the trailing partial word is padded out with the length
+    let mut last = (data.len() as u64) << 56;
+    for chunk in data.chunks(8) {
+        if chunk.len() < 8 {
+            for (i, &b) in chunk.iter().enumerate() {
+                last |= (b as u64) << (8 * i);
+            }
+            break;
+        }
+        let m = LittleEndian::read_u64(chunk);
+        v[3] ^= m;
+        round(&mut v);
+        round(&mut v);
+        v[0] ^= m;
+    }
+    v[3] ^= last;
+    round(&mut v);
+    round(&mut v);
+    v[0] ^= last;
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+/// Writes bits most significant first, padding the last byte with zeroes
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    buffer: u8,
+    offset: u8,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(out: &'a mut Vec<u8>) -> BitWriter<'a> {
+        BitWriter { out: out, buffer: 0, offset: 0 }
+    }
+
+    fn bit(&mut self, bit: bool) {
+        if bit {
+            self.buffer |= 0x80 >> self.offset;
+        }
+        self.offset += 1;
+        if self.offset == 8 {
+            self.out.push(self.buffer);
+            self.buffer = 0;
+            self.offset = 0;
+        }
+    }
+
+    /// The quotient in unary, then the `P` low bits of the remainder
+    fn golomb_rice(&mut self, value: u64) {
+        for _ in 0..(value >> P) {
+            self.bit(true);
+        }
+        self.bit(false);
+        for i in (0..P).rev() {
+            self.bit(value & (1 << i) != 0);
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.offset > 0 {
+            self.out.push(self.buffer);
+            self.buffer = 0;
+            self.offset = 0;
+        }
+    }
+}
+
+/// Reads bits most significant first
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader { data: data, position: 0 }
+    }
+
+    fn bit(&mut self) -> Option<bool> {
+        let byte = *self.data.get(self.position / 8)?;
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+        Some(bit)
+    }
+
+    fn golomb_rice(&mut self) -> Option<u64> {
+        let mut quotient = 0u64;
+        while self.bit()? {
+            quotient += 1;
+        }
+        let mut remainder = 0u64;
+        for _ in 0..P {
+            remainder = (remainder << 1) | self.bit()? as u64;
+        }
+        Some((quotient << P) | remainder)
+    }
+}
+
+/// The hash of a serialized filter
+pub fn filter_hash(filter: &[u8]) -> sha256d::Hash {
+    sha256d::Hash::hash(filter)
+}
+
+/// The header of a filter, committing to it and to every filter before it:
+/// the double-SHA256 of `filter_hash || prev_header`. The header before the
+/// genesis filter is all zeroes.
+pub fn filter_header(prev_header: &sha256d::Hash, filter_hash: &sha256d::Hash) -> sha256d::Hash {
+    let mut engine = sha256d::Hash::engine();
+    engine.input(&filter_hash[..]);
+    engine.input(&prev_header[..]);
+    sha256d::Hash::from_engine(engine)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin_hashes::sha256d;
+
+    use std::str::FromStr;
+
+    use bitcoin_hashes::hex::FromHex;
+
+    use blockdata::script::Script;
+    use util::address::Address;
+    use util::misc::hex_bytes;
+    use super::*;
+
+    #[test]
+    fn test_filter_header() {
+        // the basic filter of the Bitcoin testnet genesis block, from BIP158
+        let genesis = hex_bytes("019dfca8").unwrap();
+        assert_eq!(
+            filter_hash(&genesis).to_string(),
+            "c03705b2d6fb76a59664f1d63fe8fdbb2dc076d18175fdc51d11c43afaf78a4c"
+        );
+        let mut header = filter_header(&sha256d::Hash::default(), &filter_hash(&genesis));
+        assert_eq!(header.to_string(), "21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750");
+
+        let expected = [
+            ("0174a170", "fd73206ceecc070a5ab60ed16bbdea814f0b1d174048b9b01fef7bdbc451d1ec"),
+            ("0171c080", "522c011124bfee2511f2b8bb66b828e4d2be4a0dbbfb9ed193057b8bca392e14"),
+            ("0289c6e13a7f", "3c9374efe61c494c91f08115191ef7b5826c8924fe734ce324013dbde8b0afa9"),
+        ];
+        for &(filter, expected) in expected.iter() {
+            header = filter_header(&header, &filter_hash(&hex_bytes(filter).unwrap()));
+            assert_eq!(header.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_siphash() {
+        // the reference test vectors, key 00..0f and messages 00..(n-1)
+        let k0 = 0x0706050403020100;
+        let k1 = 0x0f0e0d0c0b0a0908;
+        let data: Vec<u8> = (0..16).collect();
+        assert_eq!(siphash24(k0, k1, &[]), 0x726fdb47dd0e0e31);
+        assert_eq!(siphash24(k0, k1, &data[..1]), 0x74f839c593dc67fd);
+        assert_eq!(siphash24(k0, k1, &data[..7]), 0xab0200f58b01d137);
+        assert_eq!(siphash24(k0, k1, &data[..8]), 0x93f5f5799a932462);
+        assert_eq!(siphash24(k0, k1, &data[..15]), 0xa129ca6149be45e5);
+        assert_eq!(mul_high(0xffffffffffffffff, 0xffffffffffffffff), 0xfffffffffffffffe);
+        assert_eq!(mul_high(1 << 63, 6), 3);
+    }
+
+    #[test]
+    fn test_match_address() {
+        // the basic filter of the Bitcoin testnet genesis block, from BIP158,
+        // which holds only the script of its coinbase output
+        let block_hash = sha256d::Hash::from_hex("000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943").unwrap();
+        let coinbase_script = hex_bytes("4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac").unwrap();
+        let filter = BlockFilter::from_elements(block_hash, &[&coinbase_script]);
+        assert_eq!(filter.content, hex_bytes("019dfca8").unwrap());
+        assert!(filter.match_any(&[&coinbase_script]));
+        assert!(!filter.match_any(&[b"not a script"]));
+
+        let addr = Address::from_str("VkRhad19WpsYdnp1A7sCdiguhyJA3zS4Su").unwrap();
+        let other = Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap();
+        let scripts: Vec<Script> = (0..20u8).map(|i| Script::from(vec![0x6a, 0x01, i])).collect();
+        let mut elements: Vec<&[u8]> = scripts.iter().map(|s| &s[..]).collect();
+        let script_pubkey = addr.script_pubkey();
+        elements.push(&script_pubkey[..]);
+        let filter = BlockFilter::from_elements(block_hash, &elements);
+        assert!(filter.match_address(&addr));
+        assert!(!filter.match_address(&other));
+        assert!(BlockFilter::new(block_hash, filter.content.clone()).match_any(&[&scripts[7][..]]));
+
+        assert!(!BlockFilter::new(block_hash, vec![]).match_address(&addr));
+        assert!(!BlockFilter::new(block_hash, vec![0x05, 0xff]).match_address(&addr));
+    }
+
+    #[test]
+    fn test_match_hostile_count() {
+        let block_hash = sha256d::Hash::from_hex("000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943").unwrap();
+        let coinbase_script = hex_bytes("4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac").unwrap();
+        let filter = BlockFilter::from_elements(block_hash, &[&coinbase_script]);
+        assert!(filter.hash_to_range(&coinbase_script, u64::max_value()).is_none());
+
+        // the genesis filter's set with counts its 3 bytes can't hold,
+        // the last of them large enough to overflow `n * M`
+        let content = hex_bytes("029dfca8").unwrap();
+        assert!(!BlockFilter::new(block_hash, content).match_any(&[&coinbase_script]));
+        let content = hex_bytes("ffffffffffffffffff9dfca8").unwrap();
+        assert!(!BlockFilter::new(block_hash, content).match_any(&[&coinbase_script]));
+        let mut content = hex_bytes("ffffffffffffffffff").unwrap();
+        content.extend(vec![0xff; 64]);
+        assert!(!BlockFilter::new(block_hash, content).match_any(&[&coinbase_script]));
+    }
+}
@@ -27,6 +27,7 @@ use bitcoin_hashes::{hash160, sha512, Hash, HashEngine, Hmac, HmacEngine};
 use secp256k1::{self, Secp256k1};
 
 use network::constants::Network;
+use util::address::{Address, AddressType};
 use util::base58;
 use util::key::{PublicKey, PrivateKey};
 
@@ -64,7 +65,7 @@ pub struct ExtendedPrivKey {
 }
 
 /// Extended public key
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub struct ExtendedPubKey {
     /// The network this key is to be used on
     pub network: Network,
@@ -81,7 +82,7 @@ pub struct ExtendedPubKey {
 }
 
 /// A child number for a derived key
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub enum ChildNumber {
     /// Non-hardened key
     Normal {
@@ -556,6 +557,76 @@ impl ExtendedPubKey {
     pub fn fingerprint(&self) -> Fingerprint {
         Fingerprint::from(&self.identifier()[0..4])
     }
+
+    /// A watch-only output descriptor, with its checksum, for the addresses of
+    /// type `address_type` on the external chain `<key>/0/*` below this key.
+    /// `origin` is the master key fingerprint and the path this key is at, if
+    /// known. P2SH stands for P2SH-wrapped P2WPKH; P2WSH has no single-key form.
+    pub fn to_descriptor(&self, address_type: AddressType, origin: Option<(Fingerprint, &DerivationPath)>) -> Option<String> {
+        let mut key = String::new();
+        if let Some((fingerprint, path)) = origin {
+            key.push('[');
+            key.push_str(&::hex::encode(&fingerprint[..]));
+            for cnum in path.as_ref() {
+                key.push_str(&format!("/{}", cnum));
+            }
+            key.push(']');
+        }
+        key.push_str(&format!("{}/0/*", self));
+
+        let desc = match address_type {
+            AddressType::P2pkh => format!("pkh({})", key),
+            AddressType::P2wpkh => format!("wpkh({})", key),
+            AddressType::P2sh => format!("sh(wpkh({}))", key),
+            AddressType::P2wsh => return None,
+        };
+        let checksum = descriptor_checksum(&desc).expect("descriptor of valid characters");
+        Some(format!("{}#{}", desc, checksum))
+    }
+
+    /// Encodes the key as its 78 bytes, without the base58check wrapping
+    pub fn encode(&self) -> [u8; 78] {
+        let mut ret = [0; 78];
+        ret[0..4].copy_from_slice(&match self.network {
+            Network::Bitcoin => [0x04u8, 0x88, 0xB2, 0x1E],
+            Network::Testnet | Network::Regtest => [0x04u8, 0x35, 0x87, 0xCF],
+        }[..]);
+        ret[4] = self.depth as u8;
+        ret[5..9].copy_from_slice(&self.parent_fingerprint[..]);
+
+        BigEndian::write_u32(&mut ret[9..13], u32::from(self.child_number));
+
+        ret[13..45].copy_from_slice(&self.chain_code[..]);
+        ret[45..78].copy_from_slice(&self.public_key.key.serialize()[..]);
+        ret
+    }
+
+    /// Decodes a key from its 78 bytes, without the base58check wrapping
+    pub fn decode(data: &[u8]) -> Result<ExtendedPubKey, base58::Error> {
+        if data.len() != 78 {
+            return Err(base58::Error::InvalidLength(data.len()));
+        }
+
+        let cn_int: u32 = Cursor::new(&data[9..13]).read_u32::<BigEndian>().unwrap();
+        let child_number: ChildNumber = ChildNumber::from(cn_int);
+
+        Ok(ExtendedPubKey {
+            network: if &data[0..4] == [0x04u8, 0x88, 0xB2, 0x1E] {
+                Network::Bitcoin
+            } else if &data[0..4] == [0x04u8, 0x35, 0x87, 0xCF] {
+                Network::Testnet
+            } else {
+                return Err(base58::Error::InvalidVersion((&data[0..4]).to_vec()));
+            },
+            depth: data[4],
+            parent_fingerprint: Fingerprint::from(&data[5..9]),
+            child_number: child_number,
+            chain_code: ChainCode::from(&data[13..45]),
+            public_key: PublicKey::from_slice(
+                             &data[45..78]).map_err(|e|
+                                 base58::Error::Other(e.to_string()))?
+        })
+    }
 }
 
 impl fmt::Display for ExtendedPrivKey {
@@ -619,19 +690,7 @@ impl FromStr for ExtendedPrivKey {
 
 impl fmt::Display for ExtendedPubKey {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let mut ret = [0; 78];
-        ret[0..4].copy_from_slice(&match self.network {
-            Network::Bitcoin => [0x04u8, 0x88, 0xB2, 0x1E],
-            Network::Testnet | Network::Regtest => [0x04u8, 0x35, 0x87, 0xCF],
-        }[..]);
-        ret[4] = self.depth as u8;
-        ret[5..9].copy_from_slice(&self.parent_fingerprint[..]);
-
-        BigEndian::write_u32(&mut ret[9..13], u32::from(self.child_number));
-
-        ret[13..45].copy_from_slice(&self.chain_code[..]);
-        ret[45..78].copy_from_slice(&self.public_key.key.serialize()[..]);
-        fmt.write_str(&base58::check_encode_slice(&ret[..]))
+        fmt.write_str(&base58::check_encode_slice(&self.encode()[..]))
     }
 }
 
@@ -639,32 +698,76 @@ impl FromStr for ExtendedPubKey {
     type Err = base58::Error;
 
     fn from_str(inp: &str) -> Result<ExtendedPubKey, base58::Error> {
-        let data = base58::from_check(inp)?;
+        ExtendedPubKey::decode(&base58::from_check(inp)?)
+    }
+}
 
-        if data.len() != 78 {
-            return Err(base58::Error::InvalidLength(data.len()));
+/// The characters allowed in a descriptor, in the order the checksum uses
+const DESCRIPTOR_INPUT_CHARSET: &'static str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+
+/// The characters of a descriptor checksum
+const DESCRIPTOR_CHECKSUM_CHARSET: &'static [u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn descriptor_polymod(c: u64, val: u64) -> u64 {
+    const GENERATOR: [u64; 5] = [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd];
+    let c0 = c >> 35;
+    let mut c = ((c & 0x7ffffffff) << 5) ^ val;
+    for (i, gen) in GENERATOR.iter().enumerate() {
+        if (c0 >> i) & 1 != 0 {
+            c ^= gen;
         }
+    }
+    c
+}
 
-        let cn_int: u32 = Cursor::new(&data[9..13]).read_u32::<BigEndian>().unwrap();
-        let child_number: ChildNumber = ChildNumber::from(cn_int);
+/// The eight character checksum of an output script descriptor, which follows
+/// it after a `#`, or `None` if it has characters no descriptor may contain
+pub fn descriptor_checksum(desc: &str) -> Option<String> {
+    let mut c = 1;
+    let mut cls = 0;
+    let mut clscount = 0;
+    for ch in desc.chars() {
+        let pos = DESCRIPTOR_INPUT_CHARSET.find(ch)? as u64;
+        // the low five bits of each character, then the high bits of each
+        // group of three characters
+        c = descriptor_polymod(c, pos & 31);
+        cls = cls * 3 + (pos >> 5);
+        clscount += 1;
+        if clscount == 3 {
+            c = descriptor_polymod(c, cls);
+            cls = 0;
+            clscount = 0;
+        }
+    }
+    if clscount > 0 {
+        c = descriptor_polymod(c, cls);
+    }
+    for _ in 0..8 {
+        c = descriptor_polymod(c, 0);
+    }
+    c ^= 1;
 
-        Ok(ExtendedPubKey {
-            network: if &data[0..4] == [0x04u8, 0x88, 0xB2, 0x1E] {
-                Network::Bitcoin
-            } else if &data[0..4] == [0x04u8, 0x35, 0x87, 0xCF] {
-                Network::Testnet
-            } else {
-                return Err(base58::Error::InvalidVersion((&data[0..4]).to_vec()));
-            },
-            depth: data[4],
-            parent_fingerprint: Fingerprint::from(&data[5..9]),
-            child_number: child_number,
-            chain_code: ChainCode::from(&data[13..45]),
-            public_key: PublicKey::from_slice(
-                             &data[45..78]).map_err(|e|
-                                 base58::Error::Other(e.to_string()))?
-        })
+    let mut ret = String::with_capacity(8);
+    for j in 0..8 {
+        ret.push(DESCRIPTOR_CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char);
     }
+    Some(ret)
+}
+
+/// The master key of a wallet from a fixed seed, for reproducible tests.
+/// Panics in the practically impossible case of an invalid master key.
+pub fn test_wallet(seed: &[u8], network: Network) -> ExtendedPrivKey {
+    ExtendedPrivKey::new_master(network, seed).expect("valid master key")
+}
+
+/// The first `count` P2PKH addresses of the external chain `m/0/i` of a
+/// wallet, e.g. one made by `test_wallet`
+pub fn test_wallet_addresses<C: secp256k1::Signing>(secp: &Secp256k1<C>, wallet: &ExtendedPrivKey, count: u32) -> Result<Vec<Address>, Error> {
+    let external = wallet.ckd_priv(secp, ChildNumber::from_normal_idx(0)?)?;
+    (0..count).map(|i| {
+        let child = external.ckd_priv(secp, ChildNumber::from_normal_idx(i)?)?;
+        Ok(Address::p2pkh(&child.private_key.public_key(secp), wallet.network))
+    }).collect()
 }
 
 #[cfg(test)]
@@ -677,10 +780,26 @@ mod tests {
 
     use network::constants::Network::{self, Bitcoin};
 
-    use super::{ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+    use util::address::AddressType;
+    use super::{ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint};
+    use super::{descriptor_checksum, test_wallet, test_wallet_addresses};
     use super::ChildNumber::{Hardened, Normal};
     use super::Error;
 
+    #[test]
+    fn test_test_wallet() {
+        let secp = Secp256k1::new();
+        let seed = hex_decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let wallet = test_wallet(&seed, Bitcoin);
+        assert_eq!(wallet, ExtendedPrivKey::new_master(Bitcoin, &seed).unwrap());
+
+        let addresses = test_wallet_addresses(&secp, &wallet, 2).unwrap();
+        assert_eq!(addresses.len(), 2);
+        assert_eq!(&addresses[0].to_string(), "VbC9y2C6uedKmBMsguGyY6p8ta7MWUHovG");
+        assert_eq!(&addresses[1].to_string(), "VcPsorGqjhsSUxigQpeymhdT6gXB92aKtU");
+        assert_eq!(test_wallet_addresses(&secp, &test_wallet(&seed, Bitcoin), 2).unwrap(), addresses);
+    }
+
     #[test]
     fn test_parse_derivation_path() {
         assert_eq!(DerivationPath::from_str("42"), Err(Error::InvalidDerivationPathFormat));
@@ -898,5 +1017,37 @@ mod tests {
         serde_round_trip!(ChildNumber::from_hardened_idx(1).unwrap());
         serde_round_trip!(ChildNumber::from_hardened_idx((1 << 31) - 1).unwrap());
     }
+
+    #[test]
+    fn test_descriptor_checksum() {
+        // from Bitcoin Core's descriptor documentation
+        let desc = "pkh([d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*)";
+        assert_eq!(descriptor_checksum(desc), Some("ml40v0wf".to_owned()));
+        assert_eq!(descriptor_checksum("pkh(\u{e9})"), None);
+    }
+
+    #[test]
+    fn test_to_descriptor() {
+        let xpub = ExtendedPubKey::from_str("xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL").unwrap();
+        let fingerprint = Fingerprint::from(&hex_decode("d34db33f").unwrap()[..]);
+        let path = DerivationPath::from_str("m/44'/0'/0'").unwrap();
+
+        let desc = xpub.to_descriptor(AddressType::P2pkh, Some((fingerprint, &path))).unwrap();
+        assert_eq!(desc, format!("pkh([d34db33f/44'/0'/0']{}/0/*)#2tsw3673", xpub));
+        assert_eq!(
+            xpub.to_descriptor(AddressType::P2wpkh, None).unwrap(),
+            format!("wpkh({}/0/*)#q336qs49", xpub)
+        );
+        assert_eq!(
+            xpub.to_descriptor(AddressType::P2sh, Some((fingerprint, &path))).unwrap(),
+            format!("sh(wpkh([d34db33f/44'/0'/0']{}/0/*))#hpqew33w", xpub)
+        );
+        assert_eq!(xpub.to_descriptor(AddressType::P2wsh, None), None);
+
+        // the checksum covers everything before the `#`
+        let (body, checksum) = desc.split_at(desc.len() - 9);
+        assert_eq!(descriptor_checksum(body).map(|c| format!("#{}", c)), Some(checksum.to_owned()));
+    }
+
 }
 
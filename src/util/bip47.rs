@@ -0,0 +1,231 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP47 Payment Codes
+//!
+//! Version 1 reusable payment codes, as defined at
+//! https://github.com/bitcoin/bips/blob/master/bip-0047.mediawiki
+//! Two parties who know each other's payment code derive a sequence of
+//! addresses only they can link, from an ECDH shared secret per address.
+//! Notification transactions, which carry the sender's masked payment code
+//! to the receiver, are not handled here.
+
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin_hashes::{sha256, Hash};
+use secp256k1::{self, Secp256k1};
+
+use network::constants::Network;
+use util::address::Address;
+use util::base58;
+use util::bip32::{self, ChainCode, ChildNumber, ExtendedPrivKey, ExtendedPubKey, Fingerprint};
+use util::key::{PrivateKey, PublicKey};
+
+/// The base58 version byte of a payment code, giving the `PM8T` prefix
+const PAYMENT_CODE_VERSION_BYTE: u8 = 0x47;
+
+/// A version 1 payment code: the public key and chain code of the account
+/// key `m/47'/0'/n'`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PaymentCode {
+    /// The account public key
+    pub public_key: PublicKey,
+    /// The account chain code
+    pub chain_code: ChainCode,
+}
+
+impl PaymentCode {
+    /// Creates the payment code of the account key `m/47'/0'/n'`
+    pub fn from_account_key(account: &ExtendedPubKey) -> PaymentCode {
+        PaymentCode {
+            public_key: account.public_key,
+            chain_code: account.chain_code,
+        }
+    }
+
+    /// Deserializes the 80-byte binary form of a payment code
+    pub fn from_slice(data: &[u8]) -> Result<PaymentCode, base58::Error> {
+        if data.len() != 80 {
+            return Err(base58::Error::InvalidLength(data.len()));
+        }
+        if data[0] != 1 {
+            return Err(base58::Error::InvalidVersion(vec![data[0]]));
+        }
+        Ok(PaymentCode {
+            public_key: PublicKey::from_slice(&data[2..35]).map_err(|e| base58::Error::Other(e.to_string()))?,
+            chain_code: ChainCode::from(&data[35..67]),
+        })
+    }
+
+    /// Serializes the payment code in its 80-byte binary form
+    pub fn serialize(&self) -> [u8; 80] {
+        let mut ret = [0; 80];
+        ret[0] = 1;
+        // ret[1] holds the feature bits, of which none are set
+        ret[2..35].copy_from_slice(&self.public_key.key.serialize()[..]);
+        ret[35..67].copy_from_slice(&self.chain_code[..]);
+        ret
+    }
+
+    /// The public key with the given index under the payment code
+    fn derive_key<C: secp256k1::Verification>(&self, secp: &Secp256k1<C>, index: u32) -> Result<PublicKey, bip32::Error> {
+        let account = ExtendedPubKey {
+            network: Network::Bitcoin,
+            depth: 3,
+            parent_fingerprint: Fingerprint::default(),
+            child_number: ChildNumber::from_hardened_idx(0)?,
+            public_key: self.public_key,
+            chain_code: self.chain_code,
+        };
+        Ok(account.ckd_pub(secp, ChildNumber::from_normal_idx(index)?)?.public_key)
+    }
+
+    /// The address a sender pays to notify the owner of this payment code
+    /// that they will use it
+    pub fn notification_address<C: secp256k1::Verification>(&self, secp: &Secp256k1<C>, network: Network) -> Result<Address, bip32::Error> {
+        Ok(Address::p2pkh(&self.derive_key(secp, 0)?, network))
+    }
+}
+
+impl fmt::Display for PaymentCode {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut ret = [0; 81];
+        ret[0] = PAYMENT_CODE_VERSION_BYTE;
+        ret[1..].copy_from_slice(&self.serialize()[..]);
+        fmt.write_str(&base58::check_encode_slice(&ret[..]))
+    }
+}
+
+impl FromStr for PaymentCode {
+    type Err = base58::Error;
+
+    fn from_str(inp: &str) -> Result<PaymentCode, base58::Error> {
+        let data = base58::from_check(inp)?;
+        if data.is_empty() {
+            return Err(base58::Error::InvalidLength(0));
+        }
+        if data[0] != PAYMENT_CODE_VERSION_BYTE {
+            return Err(base58::Error::InvalidVersion(vec![data[0]]));
+        }
+        PaymentCode::from_slice(&data[1..])
+    }
+}
+
+/// The scalar `SHA256(x(sk * pk))` both parties can compute for an address
+fn shared_secret<C: secp256k1::Verification>(secp: &Secp256k1<C>, sk: &secp256k1::SecretKey, pk: &PublicKey) -> Result<secp256k1::SecretKey, bip32::Error> {
+    let mut point = pk.key;
+    point.mul_assign(secp, &sk[..])?;
+    let hash = sha256::Hash::hash(&point.serialize()[1..]);
+    Ok(secp256k1::SecretKey::from_slice(&hash[..])?)
+}
+
+/// The `index`th address the sender, whose account key is `sender`, pays
+/// the owner of `receiver` at
+pub fn payment_address<C: secp256k1::Signing + secp256k1::Verification>(
+    secp: &Secp256k1<C>,
+    sender: &ExtendedPrivKey,
+    receiver: &PaymentCode,
+    index: u32,
+) -> Result<Address, bip32::Error> {
+    let notification_key = sender.ckd_priv(secp, ChildNumber::from_normal_idx(0)?)?.private_key;
+    let mut key = receiver.derive_key(secp, index)?;
+    let secret = shared_secret(secp, &notification_key.key, &key)?;
+    key.key.add_exp_assign(secp, &secret[..])?;
+    Ok(Address::p2pkh(&key, sender.network))
+}
+
+/// The private key of the `index`th address the owner of `sender` pays the
+/// receiver, whose account key is `receiver`, at
+pub fn payment_key<C: secp256k1::Signing + secp256k1::Verification>(
+    secp: &Secp256k1<C>,
+    receiver: &ExtendedPrivKey,
+    sender: &PaymentCode,
+    index: u32,
+) -> Result<PrivateKey, bip32::Error> {
+    let mut key = receiver.ckd_priv(secp, ChildNumber::from_normal_idx(index)?)?.private_key;
+    let secret = shared_secret(secp, &key.key, &sender.derive_key(secp, 0)?)?;
+    key.key.add_assign(&secret[..])?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use secp256k1::Secp256k1;
+
+    use network::constants::Network;
+    use util::address::Address;
+    use util::bip32::{ChildNumber, ExtendedPrivKey, ExtendedPubKey};
+    use util::misc::hex_bytes;
+    use super::{payment_address, payment_key, PaymentCode};
+
+    fn account_key(seed: &str) -> ExtendedPrivKey {
+        let secp = Secp256k1::new();
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &hex_bytes(seed).unwrap()).unwrap();
+        let path = [
+            ChildNumber::from_hardened_idx(47).unwrap(),
+            ChildNumber::from_hardened_idx(0).unwrap(),
+            ChildNumber::from_hardened_idx(0).unwrap(),
+        ];
+        master.derive_priv(&secp, &path).unwrap()
+    }
+
+    #[test]
+    fn test_payment_codes() {
+        // from the BIP47 test vectors, with addresses in viacoin's encoding
+        let secp = Secp256k1::new();
+        let alice = account_key("64dca76abc9c6f0cf3d212d248c380c4622c8f93b2c425ec6a5567fd5db57e10d3e6f94a2f6af4ac2edb8998072aad92098db73558c323777abf5bd1082d970a");
+        let bob = account_key("87eaaac5a539ab028df44d9110defbef3797ddb805ca309f61a69ff96dbaa7ab5b24038cf029edec5235d933110f0aea8aeecf939ed14fc20730bba71e4b1110");
+
+        let alice_code = PaymentCode::from_account_key(&ExtendedPubKey::from_private(&secp, &alice));
+        let bob_code = PaymentCode::from_account_key(&ExtendedPubKey::from_private(&secp, &bob));
+        assert_eq!(alice_code.to_string(), "PM8TJTLJbPRGxSbc8EJi42Wrr6QbNSaSSVJ5Y3E4pbCYiTHUskHg13935Ubb7q8tx9GVbh2UuRnBc3WSyJHhUrw8KhprKnn9eDznYGieTzFcwQRya4GA");
+        assert_eq!(bob_code.to_string(), "PM8TJS2JxQ5ztXUpBBRnpTbcUXbUHy2T1abfrb3KkAAtMEGNbey4oumH7Hc578WgQJhPjBxteQ5GHHToTYHE3A1w6p7tU6KSoFmWBVbFGjKPisZDbP97");
+        assert_eq!(PaymentCode::from_str(&bob_code.to_string()).unwrap(), bob_code);
+        assert_eq!(PaymentCode::from_slice(&alice_code.serialize()[..]).unwrap(), alice_code);
+
+        assert_eq!(alice_code.notification_address(&secp, Network::Bitcoin).unwrap().to_string(), "VsDTgYQo7dtpYiHoTaLw2Nink2628YLoUZ");
+        assert_eq!(bob_code.notification_address(&secp, Network::Bitcoin).unwrap().to_string(), "VmhkPC6AHqbgUgZRvJtkoafZBwjuhhuNqk");
+
+        let expected = [
+            "Vd1VcpczTW3FAhKd4UfU6NthpfjANTFnFb",
+            "VbtsPcp5SUoxroggoET4gAXex9V9xz4nNU",
+            "Vps1QQcY3Dp6LiUYPB7AnYQhcN8pEfgHq4",
+        ];
+        for (index, addr) in expected.iter().enumerate() {
+            let sent_to = payment_address(&secp, &alice, &bob_code, index as u32).unwrap();
+            assert_eq!(&sent_to.to_string(), addr);
+            // Bob can spend what Alice pays him
+            let key = payment_key(&secp, &bob, &alice_code, index as u32).unwrap();
+            assert_eq!(Address::p2pkh(&key.public_key(&secp), key.network), sent_to);
+        }
+
+        // payment addresses are not symmetric
+        assert!(payment_address(&secp, &bob, &alice_code, 0).unwrap().to_string() != expected[0]);
+    }
+
+    #[test]
+    fn test_payment_code_parse_errors() {
+        let mut data = PaymentCode::from_str("PM8TJS2JxQ5ztXUpBBRnpTbcUXbUHy2T1abfrb3KkAAtMEGNbey4oumH7Hc578WgQJhPjBxteQ5GHHToTYHE3A1w6p7tU6KSoFmWBVbFGjKPisZDbP97").unwrap().serialize();
+        assert!(PaymentCode::from_slice(&data[..79]).is_err());
+        data[0] = 2;
+        assert!(PaymentCode::from_slice(&data[..]).is_err());
+        data[0] = 1;
+        data[2] = 0x04;
+        assert!(PaymentCode::from_slice(&data[..]).is_err());
+        assert!(PaymentCode::from_str("VsDTgYQo7dtpYiHoTaLw2Nink2628YLoUZ").is_err());
+    }
+}
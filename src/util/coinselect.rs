@@ -0,0 +1,105 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Coin selection
+//!
+//! Support for choosing which unspent outputs fund a transaction. An output
+//! is only worth spending if its value exceeds the fee for the input that
+//! spends it.
+
+use blockdata::transaction::OutPoint;
+use util::amount::{Amount, SignedAmount};
+use util::fee::FeeRate;
+
+/// The weight of an input besides its script sig and witness: the outpoint
+/// and sequence number
+pub const TXIN_BASE_WEIGHT: u64 = 4 * (32 + 4 + 4);
+
+/// The largest satisfaction weight of a P2PKH output with a compressed key:
+/// a script sig of a 72-byte signature and a 33-byte key, with its length
+pub const P2PKH_SATISFACTION_WEIGHT: u64 = 4 * (1 + 1 + 72 + 1 + 33);
+
+/// The largest satisfaction weight of a P2PK output: a script sig of a
+/// 72-byte signature, with its length
+pub const P2PK_SATISFACTION_WEIGHT: u64 = 4 * (1 + 1 + 72);
+
+/// The largest satisfaction weight of a P2WPKH output: an empty script sig,
+/// and a witness of a 72-byte signature and a 33-byte key
+pub const P2WPKH_SATISFACTION_WEIGHT: u64 = 4 + (1 + 1 + 72 + 1 + 33);
+
+/// The largest satisfaction weight of a P2SH-wrapped P2WPKH output: a script
+/// sig pushing the 22-byte redeem script, and the witness of P2WPKH
+pub const P2SH_P2WPKH_SATISFACTION_WEIGHT: u64 = 4 * (1 + 1 + 22) + (1 + 1 + 72 + 1 + 33);
+
+/// An unspent output which may be chosen to fund a transaction
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Utxo {
+    /// The output
+    pub outpoint: OutPoint,
+    /// The value of the output
+    pub value: Amount,
+    /// The largest weight of the script sig and witness spending the output,
+    /// including their lengths
+    pub satisfaction_weight: u64,
+}
+
+impl Utxo {
+    /// The weight of the input spending this output
+    pub fn input_weight(&self) -> u64 {
+        TXIN_BASE_WEIGHT + self.satisfaction_weight
+    }
+
+    /// The value of the output less the fee of spending it at `fee_rate`. It
+    /// is negative if spending the output costs more than it brings in.
+    pub fn effective_value(&self, fee_rate: FeeRate) -> SignedAmount {
+        let value = self.value.to_signed().unwrap_or(SignedAmount::from_sat(i64::max_value()));
+        let fee = fee_rate.fee_wu(self.input_weight())
+            .and_then(Amount::to_signed)
+            .unwrap_or(SignedAmount::from_sat(i64::max_value()));
+        value - fee
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blockdata::transaction::OutPoint;
+    use util::amount::{Amount, SignedAmount};
+    use util::fee::FeeRate;
+    use super::{Utxo, P2PKH_SATISFACTION_WEIGHT, P2WPKH_SATISFACTION_WEIGHT};
+
+    #[test]
+    fn test_effective_value() {
+        let utxo = |value, satisfaction_weight| Utxo {
+            outpoint: OutPoint::default(),
+            value: Amount::from_sat(value),
+            satisfaction_weight: satisfaction_weight,
+        };
+
+        // 68 vbytes at 10 sat/vB
+        let segwit = utxo(100_000, P2WPKH_SATISFACTION_WEIGHT);
+        assert_eq!(segwit.input_weight(), 272);
+        assert_eq!(segwit.effective_value(FeeRate::from_sat_per_vb(10).unwrap()), SignedAmount::from_sat(99_320));
+        assert_eq!(segwit.effective_value(FeeRate::ZERO), SignedAmount::from_sat(100_000));
+
+        // dust costs more to spend than it is worth
+        let dust = utxo(1_000, P2PKH_SATISFACTION_WEIGHT);
+        assert_eq!(dust.input_weight(), 592);
+        let high = FeeRate::from_sat_per_vb(100).unwrap();
+        assert_eq!(dust.effective_value(high), SignedAmount::from_sat(-13_800));
+        assert!(dust.effective_value(high).is_negative());
+        assert!(!dust.effective_value(FeeRate::from_sat_per_vb(1).unwrap()).is_negative());
+
+        assert!(dust.effective_value(FeeRate::from_sat_per_kwu(u64::max_value())).is_negative());
+    }
+}
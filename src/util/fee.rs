@@ -0,0 +1,217 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Fee rates
+//!
+//! A fee rate is kept in satoshis per 1000 weight units, in which every
+//! other common unit is exact or rounds down predictably: a virtual byte is
+//! four weight units.
+
+use std::{error, fmt};
+use std::str::FromStr;
+
+use util::amount::{Amount, Denomination, ParseAmountError};
+
+/// An error parsing a fee rate
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ParseFeeRateError {
+    /// The number or its denomination was invalid
+    Amount(ParseAmountError),
+    /// The string was not a number followed by a unit
+    InvalidFormat,
+    /// The size unit after the `/` was not one of `vB`, `kvB`, `wu` or `kwu`
+    UnknownUnit(String),
+}
+
+impl fmt::Display for ParseFeeRateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseFeeRateError::Amount(ref e) => fmt::Display::fmt(e, f),
+            ParseFeeRateError::UnknownUnit(ref u) => write!(f, "unknown fee rate unit {}", u),
+            ParseFeeRateError::InvalidFormat => f.write_str(error::Error::description(self)),
+        }
+    }
+}
+
+impl error::Error for ParseFeeRateError {
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            ParseFeeRateError::Amount(ref e) => Some(e),
+            _ => None,
+        }
+    }
+
+    fn description(&self) -> &str {
+        match *self {
+            ParseFeeRateError::Amount(ref e) => error::Error::description(e),
+            ParseFeeRateError::InvalidFormat => "fee rate is not a number followed by a unit",
+            ParseFeeRateError::UnknownUnit(_) => "unknown fee rate unit",
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<ParseAmountError> for ParseFeeRateError {
+    fn from(e: ParseAmountError) -> ParseFeeRateError {
+        ParseFeeRateError::Amount(e)
+    }
+}
+
+/// A fee rate, in satoshis per 1000 weight units
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct FeeRate(u64);
+
+impl FeeRate {
+    /// The zero fee rate
+    pub const ZERO: FeeRate = FeeRate(0);
+
+    /// Create a fee rate from satoshis per 1000 weight units
+    pub fn from_sat_per_kwu(sat_kwu: u64) -> FeeRate {
+        FeeRate(sat_kwu)
+    }
+
+    /// Create a fee rate from satoshis per virtual byte, or `None` on overflow
+    pub fn from_sat_per_vb(sat_vb: u64) -> Option<FeeRate> {
+        sat_vb.checked_mul(250).map(FeeRate)
+    }
+
+    /// The fee rate in satoshis per 1000 weight units
+    pub fn as_sat_per_kwu(self) -> u64 {
+        self.0
+    }
+
+    /// The fee rate in satoshis per virtual byte, rounded down
+    pub fn as_sat_per_vb_floor(self) -> u64 {
+        self.0 / 250
+    }
+
+    /// The fee for `weight` weight units at this rate, rounded up, or `None`
+    /// on overflow
+    pub fn fee_wu(self, weight: u64) -> Option<Amount> {
+        self.0.checked_mul(weight)
+            .and_then(|v| v.checked_add(999))
+            .map(|v| Amount::from_sat(v / 1000))
+    }
+}
+
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} sat/kwu", self.0)
+    }
+}
+
+/// Parses a fee rate such as `10 sat/vB`, `0.0001 VIA/kvB` or `2500 sat/kwu`.
+/// The amount may be in any `Denomination` and is per `vB`, `kvB`, `wu` or
+/// `kwu`; anything else, such as a bare `B`, is rejected as ambiguous.
+/// Fractions of a satoshi per 1000 weight units are rounded down.
+impl FromStr for FeeRate {
+    type Err = ParseFeeRateError;
+
+    fn from_str(s: &str) -> Result<FeeRate, ParseFeeRateError> {
+        let mut words = s.split_whitespace();
+        let (number, unit) = match (words.next(), words.next(), words.next()) {
+            (Some(number), Some(unit), None) => (number, unit),
+            _ => return Err(ParseFeeRateError::InvalidFormat),
+        };
+        let slash = unit.find('/').ok_or(ParseFeeRateError::InvalidFormat)?;
+        let denom = Denomination::from_str(&unit[..slash])?;
+        // weight units per size unit, as a fraction
+        let (mul, div) = match &unit[slash + 1..] {
+            "kwu" => (1, 1),
+            "wu" => (1000, 1),
+            "vB" => (250, 1),
+            "kvB" => (1, 4),
+            other => return Err(ParseFeeRateError::UnknownUnit(other.to_owned())),
+        };
+
+        if number.starts_with('-') {
+            return Err(ParseAmountError::Negative.into());
+        }
+        let (int_part, frac_part) = match number.find('.') {
+            Some(pos) => (&number[..pos], &number[pos + 1..]),
+            None => (number, ""),
+        };
+        if int_part.is_empty() && frac_part.is_empty() {
+            return Err(ParseAmountError::InvalidFormat.into());
+        }
+        // the number is `mantissa / scale` of the denomination
+        let mut mantissa = 0u64;
+        let mut scale = 1u64;
+        for (n, c) in int_part.chars().chain(frac_part.chars()).enumerate() {
+            let digit = match c.to_digit(10) {
+                Some(d) => d as u64,
+                None if c == '.' => return Err(ParseAmountError::InvalidFormat.into()),
+                None => return Err(ParseAmountError::InvalidCharacter(c).into()),
+            };
+            mantissa = mantissa.checked_mul(10)
+                .and_then(|v| v.checked_add(digit))
+                .ok_or(ParseAmountError::TooBig)?;
+            if n >= int_part.len() {
+                scale = scale.checked_mul(10).ok_or(ParseAmountError::TooPrecise)?;
+            }
+        }
+
+        let sat_per_unit = 10u64.pow(denom.precision() as u32);
+        let numerator = mantissa.checked_mul(sat_per_unit)
+            .and_then(|v| v.checked_mul(mul))
+            .ok_or(ParseAmountError::TooBig)?;
+        Ok(FeeRate(numerator / scale / div))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use util::amount::{Amount, ParseAmountError};
+    use super::{FeeRate, ParseFeeRateError};
+
+    #[test]
+    fn test_fee_rate_units() {
+        assert_eq!(FeeRate::from_sat_per_vb(10), Some(FeeRate::from_sat_per_kwu(2500)));
+        assert_eq!(FeeRate::from_sat_per_vb(u64::max_value()), None);
+        assert_eq!(FeeRate::from_sat_per_kwu(2749).as_sat_per_vb_floor(), 10);
+        assert_eq!(FeeRate::from_sat_per_kwu(2500).to_string(), "2500 sat/kwu");
+
+        let rate = FeeRate::from_sat_per_kwu(253);
+        assert_eq!(rate.fee_wu(1000), Some(Amount::from_sat(253)));
+        assert_eq!(rate.fee_wu(561), Some(Amount::from_sat(142)));
+        assert_eq!(rate.fee_wu(0), Some(Amount::ZERO));
+        assert_eq!(rate.fee_wu(u64::max_value()), None);
+    }
+
+    #[test]
+    fn test_fee_rate_from_str() {
+        let ten_sat_vb = FeeRate::from_sat_per_kwu(2500);
+        assert_eq!(FeeRate::from_str("10 sat/vB"), Ok(ten_sat_vb));
+        assert_eq!(FeeRate::from_str("0.0001 VIA/kvB"), Ok(ten_sat_vb));
+        assert_eq!(FeeRate::from_str("10000 satoshi/kvB"), Ok(ten_sat_vb));
+        assert_eq!(FeeRate::from_str("0.1 mVIA/kvB"), Ok(ten_sat_vb));
+        assert_eq!(FeeRate::from_str("2.5 sat/wu"), Ok(ten_sat_vb));
+        assert_eq!(FeeRate::from_str(&ten_sat_vb.to_string()), Ok(ten_sat_vb));
+        assert_eq!(FeeRate::from_str("1.5 sat/vB"), Ok(FeeRate::from_sat_per_kwu(375)));
+        // rounded down to a whole satoshi per 1000 weight units
+        assert_eq!(FeeRate::from_str("1 sat/kvB"), Ok(FeeRate::ZERO));
+
+        assert_eq!(FeeRate::from_str("10 sat/B"), Err(ParseFeeRateError::UnknownUnit("B".to_owned())));
+        assert_eq!(FeeRate::from_str("10 sat/byte"), Err(ParseFeeRateError::UnknownUnit("byte".to_owned())));
+        assert_eq!(FeeRate::from_str("10"), Err(ParseFeeRateError::InvalidFormat));
+        assert_eq!(FeeRate::from_str("10 sat"), Err(ParseFeeRateError::InvalidFormat));
+        assert_eq!(FeeRate::from_str("10 sat / vB"), Err(ParseFeeRateError::InvalidFormat));
+        assert_eq!(FeeRate::from_str("10 BTC/vB"), Err(ParseFeeRateError::Amount(ParseAmountError::UnknownDenomination("BTC".to_owned()))));
+        assert_eq!(FeeRate::from_str("-1 sat/vB"), Err(ParseFeeRateError::Amount(ParseAmountError::Negative)));
+        assert_eq!(FeeRate::from_str("1,5 sat/vB"), Err(ParseFeeRateError::Amount(ParseAmountError::InvalidCharacter(','))));
+        assert_eq!(FeeRate::from_str("100000000000 VIA/vB"), Err(ParseFeeRateError::Amount(ParseAmountError::TooBig)));
+    }
+}
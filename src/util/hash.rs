@@ -18,7 +18,7 @@
 use std::cmp::min;
 use std::default::Default;
 
-use bitcoin_hashes::{sha256d, Hash};
+use bitcoin_hashes::{sha256, sha256d, Hash, HashEngine};
 
 use consensus::encode::Encodable;
 
@@ -51,6 +51,23 @@ pub fn bitcoin_merkle_root(data: Vec<sha256d::Hash>) -> sha256d::Hash {
     bitcoin_merkle_root(next)
 }
 
+/// Returns a SHA256 engine primed for a BIP340-style tagged hash, i.e. one that
+/// has already been fed `SHA256(tag) || SHA256(tag)`.
+pub fn tagged_hash_engine(tag: &str) -> sha256::HashEngine {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag_hash[..]);
+    engine.input(&tag_hash[..]);
+    engine
+}
+
+/// Computes the BIP340-style tagged hash `SHA256(SHA256(tag) || SHA256(tag) || data)`
+pub fn tagged_hash(tag: &str, data: &[u8]) -> sha256::Hash {
+    let mut engine = tagged_hash_engine(tag);
+    engine.input(data);
+    sha256::Hash::from_engine(engine)
+}
+
 /// Objects which are referred to by hash
 pub trait BitcoinHash {
     /// Produces a Sha256dHash which can be used to refer to the object
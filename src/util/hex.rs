@@ -0,0 +1,182 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//     Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Hex encoding
+//!
+//! Uniform hex conversion traits for the byte-producing types of this crate.
+//! Consensus types use their consensus encoding, scripts and keys their raw
+//! bytes, and hashes their usual display form, which is byte-reversed for
+//! double-SHA256 hashes.
+
+use bitcoin_hashes::{self, hash160, sha256, sha256d};
+use bitcoin_hashes::hex::{FromHex as HashFromHex, ToHex as HashToHex};
+
+use blockdata::block::{Block, BlockHeader};
+use blockdata::script::Script;
+use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+use consensus::encode::{self, serialize, deserialize};
+use util::key::PublicKey;
+use util::misc::hex_bytes;
+
+/// Types which can be encoded as a hex string
+pub trait ToHex {
+    /// Encode the object as lowercase hex
+    fn to_hex(&self) -> String;
+}
+
+/// Types which can be decoded from a hex string
+pub trait FromHex: Sized {
+    /// Decode the object from hex, in either case
+    fn from_hex(s: &str) -> Result<Self, encode::Error>;
+}
+
+macro_rules! impl_hex_consensus {
+    ($thing:ty) => {
+        impl ToHex for $thing {
+            fn to_hex(&self) -> String {
+                ::hex::encode(serialize(self))
+            }
+        }
+
+        impl FromHex for $thing {
+            fn from_hex(s: &str) -> Result<$thing, encode::Error> {
+                deserialize(&hex_bytes(s)?)
+            }
+        }
+    }
+}
+
+impl_hex_consensus!(Block);
+impl_hex_consensus!(BlockHeader);
+impl_hex_consensus!(Transaction);
+impl_hex_consensus!(TxIn);
+impl_hex_consensus!(TxOut);
+impl_hex_consensus!(OutPoint);
+
+impl ToHex for Script {
+    fn to_hex(&self) -> String {
+        ::hex::encode(&self[..])
+    }
+}
+
+impl FromHex for Script {
+    fn from_hex(s: &str) -> Result<Script, encode::Error> {
+        Ok(Script::from(hex_bytes(s)?))
+    }
+}
+
+impl ToHex for PublicKey {
+    fn to_hex(&self) -> String {
+        ::hex::encode(&self.to_bytes())
+    }
+}
+
+impl FromHex for PublicKey {
+    fn from_hex(s: &str) -> Result<PublicKey, encode::Error> {
+        PublicKey::from_slice(&hex_bytes(s)?)
+    }
+}
+
+fn hash_error(e: bitcoin_hashes::Error) -> encode::Error {
+    match e {
+        bitcoin_hashes::Error::InvalidChar(c) => encode::Error::UnexpectedHexDigit(c),
+        bitcoin_hashes::Error::OddLengthString(_) => encode::Error::ParseFailed("hexstring of odd length"),
+        bitcoin_hashes::Error::InvalidLength(_, _) => encode::Error::ParseFailed("hexstring of wrong length for hash"),
+    }
+}
+
+macro_rules! impl_hex_hash {
+    ($thing:ty) => {
+        impl ToHex for $thing {
+            fn to_hex(&self) -> String {
+                HashToHex::to_hex(self)
+            }
+        }
+
+        impl FromHex for $thing {
+            fn from_hex(s: &str) -> Result<$thing, encode::Error> {
+                HashFromHex::from_hex(s).map_err(hash_error)
+            }
+        }
+    }
+}
+
+impl_hex_hash!(sha256d::Hash);
+impl_hex_hash!(sha256::Hash);
+impl_hex_hash!(hash160::Hash);
+
+#[cfg(test)]
+mod tests {
+    use bitcoin_hashes::{hash160, sha256, sha256d, Hash};
+
+    use blockdata::script::Script;
+    use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+    use consensus::encode;
+    use util::key::PublicKey;
+    use super::{FromHex, ToHex};
+
+    fn round_trip<T: FromHex + ToHex + PartialEq + ::std::fmt::Debug>(hex: &str) -> T {
+        let obj = T::from_hex(hex).unwrap();
+        assert_eq!(obj.to_hex(), hex.to_lowercase());
+        assert_eq!(T::from_hex(&obj.to_hex()).unwrap(), obj);
+        obj
+    }
+
+    #[test]
+    fn test_consensus_hex() {
+        let tx_hex = "0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000";
+        let tx: Transaction = round_trip(tx_hex);
+        assert_eq!(tx.input.len(), 1);
+        let txin: TxIn = round_trip(&::hex::encode(encode::serialize(&tx.input[0])));
+        assert_eq!(txin, tx.input[0]);
+        let txout: TxOut = round_trip("00e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac");
+        assert_eq!(txout, tx.output[0]);
+        let outpoint: OutPoint = round_trip(&::hex::encode(encode::serialize(&tx.input[0].previous_output)));
+        assert_eq!(outpoint, tx.input[0].previous_output);
+
+        assert!(Transaction::from_hex(&tx_hex[..tx_hex.len() - 2]).is_err());
+        assert!(Transaction::from_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_bytes_hex() {
+        let script: Script = round_trip("76a9140389035a9225b3839e2bbf32d826a1e222031fd888ac");
+        assert!(script.is_p2pkh());
+        let pk: PublicKey = round_trip("033B9B137EE87D5A812D6F506EFDD37F0AFFA7FFC310711C06C7F3E097C9447C52");
+        assert!(pk.compressed);
+        assert!(PublicKey::from_hex("033b9b").is_err());
+    }
+
+    #[test]
+    fn test_hash_hex() {
+        // display reversal is kept for double-SHA256
+        let hash: sha256d::Hash = round_trip("6fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000");
+        assert_eq!(hash[0], 0);
+        assert_eq!(hash, sha256d::Hash::from_slice(&::hex::decode("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f").unwrap()).unwrap());
+
+        let hash: sha256::Hash = round_trip("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hash, sha256::Hash::hash(&[]));
+        let hash: hash160::Hash = round_trip("b472a266d0bd89c13706a4132ccfb16f7c3b9fcb");
+        assert_eq!(hash, hash160::Hash::hash(&[]));
+
+        match sha256::Hash::from_hex("e3b0") {
+            Err(encode::Error::ParseFailed(_)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        match hash160::Hash::from_hex("g472a266d0bd89c13706a4132ccfb16f7c3b9fcb") {
+            Err(encode::Error::UnexpectedHexDigit('g')) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
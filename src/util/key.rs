@@ -19,9 +19,11 @@
 use std::fmt::{self, Write};
 use std::{io, ops};
 use std::str::FromStr;
+#[cfg(feature = "rand")] use rand::{OsRng, Rand, Rng};
 use secp256k1::{self, Secp256k1};
 use consensus::encode;
 use network::constants::Network;
+use util::address::{Address, AddressType};
 use util::base58;
 
 /// A Bitcoin ECDSA public key
@@ -69,6 +71,28 @@ impl PublicKey {
     pub fn from_private_key<C: secp256k1::Signing>(secp: &Secp256k1<C>, sk: &PrivateKey) -> PublicKey {
         sk.public_key(secp)
     }
+
+    /// Whether both keys are the same point, however each is serialized. The
+    /// derived `PartialEq` also compares `compressed`, so a key and its
+    /// uncompressed form are not `==`.
+    pub fn same_point(&self, other: &PublicKey) -> bool {
+        self.key == other.key
+    }
+}
+
+/// Generates valid public keys, compressed or not with even odds, so that
+/// `Rng::gen` can feed property tests of key serialization. This plays the
+/// part of an `Arbitrary` impl, as neither quickcheck nor proptest is a
+/// dependency of this crate.
+#[cfg(feature = "rand")]
+impl Rand for PublicKey {
+    fn rand<R: Rng>(rng: &mut R) -> PublicKey {
+        let secp = Secp256k1::signing_only();
+        PublicKey {
+            compressed: rng.gen(),
+            key: secp256k1::PublicKey::from_secret_key(&secp, &secp256k1::SecretKey::new(rng)),
+        }
+    }
 }
 
 impl fmt::Display for PublicKey {
@@ -122,6 +146,45 @@ impl PrivateKey {
         self.key[..].to_vec()
     }
 
+    /// Generates a fresh key from the operating system's random number
+    /// generator. Panics if that generator cannot be opened.
+    #[cfg(feature = "rand")]
+    pub fn new_random(network: Network, compressed: bool) -> PrivateKey {
+        let mut rng = OsRng::new().expect("OS random number generator");
+        PrivateKey {
+            compressed: compressed,
+            network: network,
+            key: secp256k1::SecretKey::new(&mut rng),
+        }
+    }
+
+    /// Generates `count` random keys from `rng`, one per iteration, e.g. to
+    /// search for a vanity address. Keys only depend on the output of `rng`,
+    /// so a seeded generator reproduces the same sequence.
+    #[cfg(feature = "rand")]
+    pub fn generate_many<R: Rng>(network: Network, compressed: bool, rng: &mut R, count: usize) -> GeneratedKeys<R> {
+        GeneratedKeys {
+            network: network,
+            compressed: compressed,
+            rng: rng,
+            remaining: count,
+        }
+    }
+
+    /// Computes every standard single-key address this private key can spend
+    /// from on the given network. Segwit forms are only included for compressed
+    /// keys, since uncompressed keys are not valid in witness programs.
+    pub fn all_addresses<C: secp256k1::Signing>(&self, secp: &Secp256k1<C>, network: Network) -> Vec<(AddressType, Address)> {
+        let pk = self.public_key(secp);
+        let mut ret = vec![(AddressType::P2pkh, Address::p2pkh(&pk, network))];
+        if pk.compressed {
+            ret.push((AddressType::P2wpkh, Address::p2wpkh(&pk, network)));
+            let (p2shwpkh, _) = Address::p2shwpkh(&pk, network).expect("key is compressed");
+            ret.push((AddressType::P2sh, p2shwpkh));
+        }
+        ret
+    }
+
     /// Format the private key to WIF format.
     pub fn fmt_wif(&self, fmt: &mut fmt::Write) -> fmt::Result {
         let mut ret = [0; 34];
@@ -171,6 +234,36 @@ impl PrivateKey {
     }
 }
 
+/// Iterator over randomly generated private keys, see `PrivateKey::generate_many`
+#[cfg(feature = "rand")]
+pub struct GeneratedKeys<'a, R: 'a> {
+    network: Network,
+    compressed: bool,
+    rng: &'a mut R,
+    remaining: usize,
+}
+
+#[cfg(feature = "rand")]
+impl<'a, R: Rng> Iterator for GeneratedKeys<'a, R> {
+    type Item = PrivateKey;
+
+    fn next(&mut self) -> Option<PrivateKey> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(PrivateKey {
+            compressed: self.compressed,
+            network: self.network,
+            key: secp256k1::SecretKey::new(self.rng),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
 impl fmt::Display for PrivateKey {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.fmt_wif(f)
@@ -200,11 +293,74 @@ impl ops::Index<ops::RangeFull> for PrivateKey {
 #[cfg(test)]
 mod tests {
     use super::{PrivateKey, PublicKey};
+    #[cfg(feature = "rand")]
+    use rand::{Rng, SeedableRng, XorShiftRng};
     use secp256k1::Secp256k1;
+    #[cfg(feature = "rand")]
+    use std::collections::HashSet;
     use std::str::FromStr;
     use network::constants::Network::Testnet;
     use network::constants::Network::Bitcoin;
-    use util::address::Address;
+    use util::address::{Address, AddressType};
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_pubkey_round_trip() {
+        // a property test over generated keys of both forms
+        let mut rng = XorShiftRng::from_seed([5, 6, 7, 8]);
+        let (mut compressed, mut uncompressed) = (0, 0);
+        for _ in 0..256 {
+            let pk: PublicKey = rng.gen();
+            let bytes = pk.to_bytes();
+            assert_eq!(bytes.len(), if pk.compressed { 33 } else { 65 });
+            assert_eq!(PublicKey::from_slice(&bytes).unwrap(), pk);
+            assert_eq!(PublicKey::from_str(&pk.to_string()).unwrap(), pk);
+            if pk.compressed { compressed += 1 } else { uncompressed += 1 }
+        }
+        assert!(compressed > 0 && uncompressed > 0);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_generate_many() {
+        let secp = Secp256k1::new();
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let keys: Vec<PrivateKey> = PrivateKey::generate_many(Bitcoin, true, &mut rng, 100).collect();
+        assert_eq!(keys.len(), 100);
+        assert!(keys.iter().all(|k| k.compressed && k.network == Bitcoin));
+
+        let distinct: HashSet<Vec<u8>> = keys.iter().map(|k| k.to_bytes()).collect();
+        assert_eq!(distinct.len(), 100);
+        // the same context serves every address derivation
+        let addresses: HashSet<String> = keys.iter()
+            .map(|k| Address::p2pkh(&k.public_key(&secp), Bitcoin).to_string())
+            .collect();
+        assert_eq!(addresses.len(), 100);
+
+        // a seeded generator gives the same keys again
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let again: Vec<PrivateKey> = PrivateKey::generate_many(Bitcoin, true, &mut rng, 100).collect();
+        assert!(keys == again);
+
+        let mut iter = PrivateKey::generate_many(Testnet, false, &mut rng, 2);
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert!(!iter.next().unwrap().compressed);
+        assert!(iter.next().is_some());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_new_random() {
+        let one = PrivateKey::new_random(Bitcoin, true);
+        let two = PrivateKey::new_random(Bitcoin, true);
+        assert!(one.key != two.key);
+        assert!(one.compressed && one.network == Bitcoin);
+
+        let key = PrivateKey::new_random(Testnet, false);
+        assert!(!key.compressed);
+        assert_eq!(key.network, Testnet);
+    }
 
     #[test]
     fn test_key_derivation() {
@@ -241,4 +397,39 @@ mod tests {
         assert_eq!(&pk.to_string(), "023b8f2b8f1e4cffe479c512a082306306e39b28961c3e8e6f91ff31cfa7d46faa");
         assert_eq!(pk, PublicKey::from_str("023b8f2b8f1e4cffe479c512a082306306e39b28961c3e8e6f91ff31cfa7d46faa").unwrap());
     }
+
+    #[test]
+    fn test_same_point() {
+        let compressed = PublicKey::from_str("023b8f2b8f1e4cffe479c512a082306306e39b28961c3e8e6f91ff31cfa7d46faa").unwrap();
+        let uncompressed = PublicKey::from_str("043b8f2b8f1e4cffe479c512a082306306e39b28961c3e8e6f91ff31cfa7d46faad951cc2e10702857d7c9389ef7ef82886b69430358e72992fbbd0bcde709c3bc").unwrap();
+        assert!(compressed.same_point(&uncompressed));
+        assert!(uncompressed.same_point(&compressed));
+        assert!(compressed != uncompressed);
+
+        let other = PublicKey::from_str("033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52").unwrap();
+        assert!(!compressed.same_point(&other));
+    }
+
+    #[test]
+    fn test_all_addresses() {
+        let secp = Secp256k1::new();
+
+        let sk = PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+        let addrs: Vec<(AddressType, String)> = sk.all_addresses(&secp, Testnet)
+            .into_iter()
+            .map(|(t, a)| (t, a.to_string()))
+            .collect();
+        assert_eq!(addrs, vec![
+            (AddressType::P2pkh, "mqwpxxvfv3QbM8PU8uBx2jaNt9btQqvQNx".to_owned()),
+            (AddressType::P2wpkh, "tb1qwfjcnutuv4djp2qr73vejvvs0gzs6pu9gypkwh".to_owned()),
+            (AddressType::P2sh, "2NCqUYLbZTWg7wUeXJyi6px9eQaAyBouyv1".to_owned()),
+        ]);
+
+        // uncompressed keys only have a legacy address
+        let sk = PrivateKey::from_wif("7gLEdvAfpYMHai6kzaNvurMjhqizH9Fyz3LijTaCZ2Lxyxme8Yo").unwrap();
+        let addrs = sk.all_addresses(&secp, Bitcoin);
+        assert_eq!(addrs.len(), 1);
+        assert_eq!(addrs[0].0, AddressType::P2pkh);
+        assert_eq!(&addrs[0].1.to_string(), "VbnnCpepvFv8puAAwRYJeKTXQeYieMwKcn");
+    }
 }
@@ -0,0 +1,85 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Merkle roots
+//!
+//! Merkle roots of lists of transaction hashes, for use without the
+//! transactions themselves, and of other lists such as sets of scripts.
+
+use bitcoin_hashes::{sha256d, Hash};
+
+use blockdata::script::Script;
+use util::hash::bitcoin_merkle_root;
+
+/// The witness merkle root of a block from the wtxids of its transactions, in
+/// block order. The coinbase wtxid is replaced by zero as it cannot commit to
+/// itself, so the first entry may hold anything.
+pub fn witness_merkle_root(wtxids: &[sha256d::Hash]) -> sha256d::Hash {
+    let mut hashes = wtxids.to_vec();
+    if let Some(coinbase) = hashes.first_mut() {
+        *coinbase = Default::default();
+    }
+    bitcoin_merkle_root(hashes)
+}
+
+/// A commitment to a list of scripts, in order: the merkle root of the
+/// double-SHA256 hashes of their raw bytes, built as for transactions.
+pub fn script_merkle_root(scripts: &[Script]) -> sha256d::Hash {
+    bitcoin_merkle_root(scripts.iter().map(|script| sha256d::Hash::hash(&script[..])).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin_hashes::{sha256d, Hash};
+
+    use blockdata::script::Script;
+    use super::{script_merkle_root, witness_merkle_root};
+
+    #[test]
+    fn test_witness_merkle_root() {
+        let coinbase = sha256d::Hash::hash(b"coinbase");
+        let one = sha256d::Hash::hash(b"one");
+        let two = sha256d::Hash::hash(b"two");
+
+        // a lone coinbase always has a zero root
+        assert_eq!(witness_merkle_root(&[coinbase]), sha256d::Hash::default());
+        assert_eq!(
+            witness_merkle_root(&[coinbase, one]).to_string(),
+            "ea54f4aee03be696298e1b3346d7966c7c23c55bdbe1a1fbf69b73693d0dca25"
+        );
+        // the odd hash out is paired with itself
+        let root = witness_merkle_root(&[coinbase, one, two]);
+        assert_eq!(root.to_string(), "11da5e4c307a1356bf1ba5ff302588fd81b553ab62d63701c0a7db07f73a010a");
+        assert_eq!(witness_merkle_root(&[sha256d::Hash::default(), one, two]), root);
+    }
+
+    #[test]
+    fn test_script_merkle_root() {
+        let scripts = vec![
+            hex_script!("76a9140389035a9225b3839e2bbf32d826a1e222031fd888ac"),
+            hex_script!("0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+            hex_script!("6a0b68656c6c6f20776f726c64"),
+        ];
+        let leaves: Vec<sha256d::Hash> = scripts.iter().map(|s| sha256d::Hash::hash(&s[..])).collect();
+
+        assert_eq!(script_merkle_root(&[]), sha256d::Hash::default());
+        assert_eq!(script_merkle_root(&scripts[..1]), leaves[0]);
+        let root = script_merkle_root(&scripts);
+        assert_eq!(root.to_string(), "e13021f9bd9d4cef0ebf9601016cfda4f9db024b6117e0ed81d44bb280a5ef9d");
+        // the commitment is to the order as well as the set
+        let swapped = vec![scripts[1].clone(), scripts[0].clone(), scripts[2].clone()];
+        assert!(script_merkle_root(&swapped) != root);
+        assert!(script_merkle_root(&[scripts[0].clone(), scripts[1].clone(), Script::new()]) != root);
+    }
+}
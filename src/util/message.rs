@@ -0,0 +1,573 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Signed messages
+//!
+//! Support for the `signmessage`/`verifymessage` format: a compact recoverable
+//! signature over the double-SHA256 of the message with a magic prefix,
+//! base64 encoded with a header byte carrying the recovery id.
+//!
+//! The BIP322 "simple" format is also supported for P2WPKH addresses. There
+//! the message is committed to in a virtual transaction paying to the address,
+//! and the signature is the witness of a second transaction spending it.
+
+use std::{error, fmt};
+
+use bitcoin_hashes::{hash160, sha256, sha256d, Hash};
+#[cfg(feature = "non-constant-time-signing")]
+use bitcoin_hashes::{HashEngine, Hmac, HmacEngine};
+use secp256k1::{self, Secp256k1, Message, RecoverableSignature, RecoveryId, Signature};
+#[cfg(feature = "non-constant-time-signing")]
+use secp256k1::SecretKey;
+
+use blockdata::opcodes;
+use blockdata::script::{Builder, Script};
+use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+use consensus::encode;
+use network::constants::Network;
+use util::address::Address;
+use util::base64;
+use util::bip143::SighashComponents;
+use util::hash::tagged_hash;
+use util::key::{PrivateKey, PublicKey};
+
+/// The prefix prepended to every message before hashing
+pub const MESSAGE_SIGN_PREFIX: &'static [u8] = b"\x18Viacoin Signed Message:\n";
+
+/// An error that might occur when verifying a signed message
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Error {
+    /// The signature was not valid base64
+    Base64(base64::Error),
+    /// The decoded signature was not 65 bytes long
+    InvalidLength(usize),
+    /// The header byte was outside of the 27..=34 range
+    InvalidHeader(u8),
+    /// A secp256k1 error occurred
+    Secp256k1(secp256k1::Error),
+    /// A BIP322 signature did not decode to a witness of the expected shape
+    InvalidWitness,
+    /// BIP322 signing or verification is not supported for this address type
+    UnsupportedAddress,
+    /// BIP322 signing from a P2WPKH address requires a compressed key
+    UncompressedKey,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Base64(ref e) => fmt::Display::fmt(e, f),
+            Error::InvalidLength(ell) => write!(f, "signature length {} invalid, expected 65", ell),
+            Error::InvalidHeader(h) => write!(f, "invalid signature header byte {}", h),
+            Error::Secp256k1(ref e) => fmt::Display::fmt(e, f),
+            Error::InvalidWitness => f.write_str("signature is not a valid witness"),
+            Error::UnsupportedAddress => f.write_str("address type not supported for BIP322"),
+            Error::UncompressedKey => f.write_str("P2WPKH requires a compressed key"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Base64(ref e) => Some(e),
+            Error::Secp256k1(ref e) => Some(e),
+            Error::InvalidLength(_) | Error::InvalidHeader(_) |
+            Error::InvalidWitness | Error::UnsupportedAddress | Error::UncompressedKey => None,
+        }
+    }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::Base64(ref e) => error::Error::description(e),
+            Error::InvalidLength(_) => "invalid signature length",
+            Error::InvalidHeader(_) => "invalid signature header byte",
+            Error::Secp256k1(ref e) => error::Error::description(e),
+            Error::InvalidWitness => "signature is not a valid witness",
+            Error::UnsupportedAddress => "address type not supported for BIP322",
+            Error::UncompressedKey => "P2WPKH requires a compressed key",
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<base64::Error> for Error {
+    fn from(e: base64::Error) -> Error {
+        Error::Base64(e)
+    }
+}
+
+#[doc(hidden)]
+impl From<secp256k1::Error> for Error {
+    fn from(e: secp256k1::Error) -> Error {
+        Error::Secp256k1(e)
+    }
+}
+
+/// Hash a message for signing, including the magic prefix
+pub fn signed_msg_hash(msg: &str) -> sha256d::Hash {
+    let mut data = MESSAGE_SIGN_PREFIX.to_vec();
+    data.extend(encode::serialize(&msg.to_owned()));
+    sha256d::Hash::hash(&data)
+}
+
+/// Sign a message, returning the base64-encoded signature
+pub fn sign<C: secp256k1::Signing>(secp: &Secp256k1<C>, msg: &str, sk: &PrivateKey) -> String {
+    let hash = signed_msg_hash(msg);
+    let msg = Message::from_slice(&hash[..]).expect("32 bytes");
+    encode_signature(&secp.sign_recoverable(&msg, &sk.key), sk.compressed)
+}
+
+/// Sign a message like `sign`, but with `aux` mixed into the RFC6979 nonce
+/// derivation as its extra data, the way libsecp256k1 takes `noncedata`.
+/// Each `aux` gives a different but reproducible signature.
+///
+/// # Warning
+///
+/// Unlike `sign`, the nonce and signature are computed in this crate rather
+/// than by libsecp256k1, and are not guaranteed to run in constant time, so
+/// they may leak the key to an attacker able to time them. It is only
+/// available with the `non-constant-time-signing` feature.
+#[cfg(feature = "non-constant-time-signing")]
+pub fn sign_message_with_aux<C: secp256k1::Signing>(secp: &Secp256k1<C>, msg: &str, sk: &PrivateKey, aux: [u8; 32]) -> String {
+    let hash = signed_msg_hash(msg);
+    encode_signature(&sign_recoverable_rfc6979(secp, &hash[..], &sk.key, &aux), sk.compressed)
+}
+
+/// Base64-encodes a recoverable signature behind its header byte
+fn encode_signature(sig: &RecoverableSignature, compressed: bool) -> String {
+    let (recid, sig) = sig.serialize_compact();
+    let mut ret = [0; 65];
+    ret[0] = 27 + recid.to_i32() as u8 + if compressed { 4 } else { 0 };
+    ret[1..].copy_from_slice(&sig[..]);
+    base64::encode_slice(&ret)
+}
+
+/// The order of the secp256k1 group
+#[cfg(feature = "non-constant-time-signing")]
+const CURVE_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+/// Half the group order, rounded down: the largest low-S value
+#[cfg(feature = "non-constant-time-signing")]
+const HALF_CURVE_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+/// Big-endian `a - b`, for `a >= b`
+#[cfg(feature = "non-constant-time-signing")]
+fn sub_be(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut ret = [0; 32];
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let d = a[i] as i16 - b[i] as i16 - borrow;
+        borrow = if d < 0 { 1 } else { 0 };
+        ret[i] = (d & 0xff) as u8;
+    }
+    ret
+}
+
+/// The modular inverse of a scalar, as `k^(n - 2)`
+#[cfg(feature = "non-constant-time-signing")]
+fn invert_scalar(k: &SecretKey) -> SecretKey {
+    let mut exponent = CURVE_ORDER;
+    exponent[31] -= 2;
+    let mut acc = *k;
+    // the top bit of the exponent is set, and accounted for by starting at `k`
+    for bit in (0..255).rev() {
+        let square = acc;
+        acc.mul_assign(&square[..]).expect("nonzero times nonzero");
+        if exponent[31 - bit / 8] & (1 << (bit % 8)) != 0 {
+            acc.mul_assign(&k[..]).expect("nonzero times nonzero");
+        }
+    }
+    acc
+}
+
+/// The RFC6979 HMAC-SHA256 generator, seeded with the key, message hash and
+/// extra data
+#[cfg(feature = "non-constant-time-signing")]
+struct Rfc6979 {
+    k: [u8; 32],
+    v: [u8; 32],
+    retry: bool,
+}
+
+#[cfg(feature = "non-constant-time-signing")]
+impl Rfc6979 {
+    fn hmac(key: &[u8], data: &[&[u8]]) -> [u8; 32] {
+        let mut engine: HmacEngine<sha256::Hash> = HmacEngine::new(key);
+        for d in data {
+            engine.input(d);
+        }
+        let mut ret = [0; 32];
+        ret.copy_from_slice(&Hmac::from_engine(engine)[..]);
+        ret
+    }
+
+    fn new(seed: &[u8]) -> Rfc6979 {
+        let mut k = [0; 32];
+        let mut v = [1; 32];
+        k = Rfc6979::hmac(&k, &[&v, &[0], seed]);
+        v = Rfc6979::hmac(&k, &[&v]);
+        k = Rfc6979::hmac(&k, &[&v, &[1], seed]);
+        v = Rfc6979::hmac(&k, &[&v]);
+        Rfc6979 { k: k, v: v, retry: false }
+    }
+
+    fn next(&mut self) -> [u8; 32] {
+        if self.retry {
+            self.k = Rfc6979::hmac(&self.k, &[&self.v, &[0]]);
+            self.v = Rfc6979::hmac(&self.k, &[&self.v]);
+        }
+        self.retry = true;
+        self.v = Rfc6979::hmac(&self.k, &[&self.v]);
+        self.v
+    }
+}
+
+/// ECDSA signing with RFC6979 nonces seeded with `extra` after the key and
+/// message hash, as libsecp256k1 seeds them with its `noncedata`. The secp256k1
+/// bindings cannot pass extra data, so the signature is assembled here from
+/// scalar and point operations, producing the same low-S result libsecp256k1
+/// would. With no extra data it is the signature `sign_recoverable` makes.
+#[cfg(feature = "non-constant-time-signing")]
+fn sign_recoverable_rfc6979<C: secp256k1::Signing>(secp: &Secp256k1<C>, hash: &[u8], sk: &SecretKey, extra: &[u8]) -> RecoverableSignature {
+    let mut z = [0; 32];
+    z.copy_from_slice(hash);
+    if z >= CURVE_ORDER {
+        z = sub_be(&z, &CURVE_ORDER);
+    }
+
+    let mut rng = Rfc6979::new(&[&sk[..], hash, extra].concat());
+    loop {
+        let k = match SecretKey::from_slice(&rng.next()) {
+            Ok(k) => k,
+            Err(_) => continue,
+        };
+        let point = secp256k1::PublicKey::from_secret_key(secp, &k).serialize_uncompressed();
+        let mut r = [0; 32];
+        r.copy_from_slice(&point[1..33]);
+        let mut recid = point[64] & 1;
+        if r >= CURVE_ORDER {
+            r = sub_be(&r, &CURVE_ORDER);
+            recid |= 2;
+        }
+
+        // s = (z + r * d) / k, retrying on the negligible chance of a zero
+        let mut s = *sk;
+        if s.mul_assign(&r).is_err() || s.add_assign(&z).is_err() {
+            continue;
+        }
+        s.mul_assign(&invert_scalar(&k)[..]).expect("nonzero times nonzero");
+        let mut s = {
+            let mut bytes = [0; 32];
+            bytes.copy_from_slice(&s[..]);
+            bytes
+        };
+        if s > HALF_CURVE_ORDER {
+            s = sub_be(&CURVE_ORDER, &s);
+            recid ^= 1;
+        }
+
+        let recid = RecoveryId::from_i32(recid as i32).expect("0..3");
+        return RecoverableSignature::from_compact(&[&r[..], &s[..]].concat(), recid).expect("valid signature");
+    }
+}
+
+/// Recover the public key which produced a base64-encoded message signature.
+/// The recovery id and whether the key is compressed come from the header byte.
+pub fn recover_pubkey<C: secp256k1::Verification>(secp: &Secp256k1<C>, msg: &str, signature: &str) -> Result<PublicKey, Error> {
+    let sig = base64::from(signature)?;
+    if sig.len() != 65 {
+        return Err(Error::InvalidLength(sig.len()));
+    }
+    if sig[0] < 27 || sig[0] > 34 {
+        return Err(Error::InvalidHeader(sig[0]));
+    }
+    let flag = sig[0] - 27;
+    let compressed = flag & 4 != 0;
+    let recid = RecoveryId::from_i32((flag & 3) as i32)?;
+    let sig = RecoverableSignature::from_compact(&sig[1..], recid)?;
+
+    let hash = signed_msg_hash(msg);
+    let msg = Message::from_slice(&hash[..]).expect("32 bytes");
+    Ok(PublicKey {
+        compressed: compressed,
+        key: secp.recover(&msg, &sig)?,
+    })
+}
+
+/// Recover the P2PKH address which signed a message
+pub fn recover_address(msg: &str, signature: &str, network: Network) -> Result<Address, Error> {
+    let secp = Secp256k1::verification_only();
+    let pk = recover_pubkey(&secp, msg, signature)?;
+    Ok(Address::p2pkh(&pk, network))
+}
+
+/// Verify that a message was signed by the key behind a P2PKH address
+pub fn verify(msg: &str, signature: &str, address: &Address) -> Result<bool, Error> {
+    let recovered = recover_address(msg, signature, address.network)?;
+    Ok(recovered.payload == address.payload)
+}
+
+/// The BIP322 tagged hash of a message
+pub fn bip322_msg_hash(msg: &str) -> sha256::Hash {
+    tagged_hash("BIP0322-signed-message", msg.as_bytes())
+}
+
+/// The BIP322 virtual transaction committing to the message, with a single
+/// zero-value output paying to `script_pubkey`
+pub fn bip322_to_spend(msg: &str, script_pubkey: &Script) -> Transaction {
+    let script_sig = Builder::new()
+        .push_opcode(opcodes::OP_FALSE)
+        .push_slice(&bip322_msg_hash(msg)[..])
+        .into_script();
+    Transaction {
+        version: 0,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint { txid: Default::default(), vout: 0xffffffff },
+            script_sig: script_sig,
+            sequence: 0,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: script_pubkey.clone(),
+        }],
+    }
+}
+
+/// The unsigned BIP322 virtual transaction spending `to_spend` to an
+/// `OP_RETURN` output; a simple signature is the witness of its only input
+pub fn bip322_to_sign(to_spend: &Transaction) -> Transaction {
+    Transaction {
+        version: 0,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint { txid: to_spend.txid(), vout: 0 },
+            script_sig: Script::new(),
+            sequence: 0,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: Builder::new().push_opcode(opcodes::all::OP_RETURN).into_script(),
+        }],
+    }
+}
+
+/// The BIP143 sighash a P2WPKH key signs for a BIP322 message
+fn bip322_p2wpkh_sighash(msg: &str, pk: &PublicKey, address: &Address) -> Message {
+    let to_spend = bip322_to_spend(msg, &address.script_pubkey());
+    let to_sign = bip322_to_sign(&to_spend);
+    let script_code = Address::p2pkh(pk, address.network).script_pubkey();
+    let hash = SighashComponents::new(&to_sign).sighash_all(&to_sign.input[0], &script_code, 0);
+    Message::from_slice(&hash[..]).expect("32 bytes")
+}
+
+/// Sign a message with the BIP322 simple format, as the P2WPKH address of the
+/// key, returning the base64-encoded witness
+pub fn sign_bip322_simple<C: secp256k1::Signing>(secp: &Secp256k1<C>, msg: &str, sk: &PrivateKey) -> Result<String, Error> {
+    if !sk.compressed {
+        return Err(Error::UncompressedKey);
+    }
+    let pk = sk.public_key(secp);
+    let address = Address::p2wpkh(&pk, sk.network);
+    let sighash = bip322_p2wpkh_sighash(msg, &pk, &address);
+
+    let mut sig = secp.sign(&sighash, &sk.key).serialize_der();
+    sig.push(0x01); // SIGHASH_ALL
+    let witness = vec![sig, pk.to_bytes()];
+    Ok(base64::encode_slice(&encode::serialize(&witness)))
+}
+
+/// Verify a BIP322 simple signature against a P2WPKH address
+pub fn verify_bip322_simple<C: secp256k1::Verification>(secp: &Secp256k1<C>, msg: &str, signature: &str, address: &Address) -> Result<bool, Error> {
+    let script_pubkey = address.script_pubkey();
+    if !script_pubkey.is_v0_p2wpkh() {
+        return Err(Error::UnsupportedAddress);
+    }
+    let witness: Vec<Vec<u8>> = encode::deserialize(&base64::from(signature)?)
+        .map_err(|_| Error::InvalidWitness)?;
+    if witness.len() != 2 || witness[0].is_empty() {
+        return Err(Error::InvalidWitness);
+    }
+
+    let pk = PublicKey::from_slice(&witness[1]).map_err(|_| Error::InvalidWitness)?;
+    if !pk.compressed || hash160::Hash::hash(&witness[1])[..] != script_pubkey[2..] {
+        return Ok(false);
+    }
+    let (hashtype, sig) = witness[0].split_last().expect("nonempty");
+    if *hashtype != 0x01 {
+        return Ok(false);
+    }
+    let sig = match Signature::from_der(sig) {
+        Ok(sig) => sig,
+        Err(_) => return Ok(false),
+    };
+    let sighash = bip322_p2wpkh_sighash(msg, &pk, address);
+    Ok(secp.verify(&sighash, &sig, &pk.key).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "non-constant-time-signing")]
+    use rand::{Rng, SeedableRng, XorShiftRng};
+    use secp256k1::Secp256k1;
+    use std::str::FromStr;
+
+    use network::constants::Network;
+    use util::address::Address;
+    use util::key::PrivateKey;
+    use super::*;
+
+    #[test]
+    fn test_msg_hash() {
+        assert_eq!(
+            signed_msg_hash("Hello, Viacoin!").to_string(),
+            // displayed byte-reversed, like every sha256d
+            "aabc9f45ba793cac1ecc8b59e0c0e68630187c88ea6324b5d0d30f243c8be94f"
+        );
+    }
+
+    #[test]
+    fn test_recover_address() {
+        let sig = "H47bZw8UrsD8t2ehpR5RodppeAiGIiyRLx3W1qPptvPMDXjCNeAtdvk6epYhF5UazyERFl3z34oQpP+k3tO45xo=";
+        let addr = recover_address("Hello, Viacoin!", sig, Network::Bitcoin).unwrap();
+        assert_eq!(&addr.to_string(), "VkRhad19WpsYdnp1A7sCdiguhyJA3zS4Su");
+        let addr = recover_address("Hello, Viacoin!", sig, Network::Testnet).unwrap();
+        assert_eq!(&addr.to_string(), "mqwpxxvfv3QbM8PU8uBx2jaNt9btQqvQNx");
+
+        // a different message recovers some other key
+        let other = recover_address("Hello, Bitcoin!", sig, Network::Testnet).unwrap();
+        assert!(other != addr);
+
+        assert_eq!(recover_address("", "H47b", Network::Bitcoin), Err(Error::InvalidLength(3)));
+        let mut bad_header = base64::from(sig).unwrap();
+        bad_header[0] = 35;
+        assert_eq!(
+            recover_address("", &base64::encode_slice(&bad_header), Network::Bitcoin),
+            Err(Error::InvalidHeader(35))
+        );
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let secp = Secp256k1::new();
+        let sk = PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+
+        // signing is deterministic, so this reproduces the vector above
+        let sig = sign(&secp, "Hello, Viacoin!", &sk);
+        assert_eq!(&sig, "H47bZw8UrsD8t2ehpR5RodppeAiGIiyRLx3W1qPptvPMDXjCNeAtdvk6epYhF5UazyERFl3z34oQpP+k3tO45xo=");
+
+        let addr = Address::from_str("mqwpxxvfv3QbM8PU8uBx2jaNt9btQqvQNx").unwrap();
+        assert_eq!(verify("Hello, Viacoin!", &sig, &addr), Ok(true));
+        assert_eq!(verify("Hello, Viacoin?", &sig, &addr), Ok(false));
+    }
+
+    #[test]
+    #[cfg(feature = "non-constant-time-signing")]
+    fn test_sign_with_aux() {
+        let secp = Secp256k1::new();
+        let sk = PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+        let addr = Address::from_str("mqwpxxvfv3QbM8PU8uBx2jaNt9btQqvQNx").unwrap();
+
+        let sig = sign_message_with_aux(&secp, "Hello, Viacoin!", &sk, [1; 32]);
+        assert_eq!(&sig, "IE4fg/jjucaYnJKXGxskz0UtOgNdl1KjkRN31IuXvbWjc6txKh3mPSVFUgh7FMUs0DQsHhOjG8OSG+MZj6xItLo=");
+        assert_eq!(verify("Hello, Viacoin!", &sig, &addr), Ok(true));
+
+        // an all-zero aux is still extra data, so differs from plain signing
+        let sig = sign_message_with_aux(&secp, "Hello, Viacoin!", &sk, [0; 32]);
+        assert_eq!(&sig, "ILCG/X4gLJayH02QO3Q37NZ1M1ufZtFVZkKT3s/wQ83rOL9xJGefz7mVu37Bp90ap44b9OKkSMfgriuill+ganE=");
+        assert!(sig != sign(&secp, "Hello, Viacoin!", &sk));
+        assert_eq!(verify("Hello, Viacoin!", &sig, &addr), Ok(true));
+    }
+
+    #[test]
+    #[cfg(feature = "non-constant-time-signing")]
+    fn test_sign_recoverable_rfc6979() {
+        // without extra data the nonces are those of libsecp256k1's own signer
+        let secp = Secp256k1::new();
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        for _ in 0..20 {
+            let sk = SecretKey::new(&mut rng);
+            let mut hash = [0; 32];
+            rng.fill_bytes(&mut hash);
+            let msg = Message::from_slice(&hash).unwrap();
+            assert_eq!(sign_recoverable_rfc6979(&secp, &hash, &sk, &[]), secp.sign_recoverable(&msg, &sk));
+        }
+
+        let sk = PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+        let hash = signed_msg_hash("Hello, Viacoin!");
+        assert_eq!(encode_signature(&sign_recoverable_rfc6979(&secp, &hash[..], &sk.key, &[]), true),
+                   sign(&secp, "Hello, Viacoin!", &sk));
+    }
+
+    #[test]
+    fn test_bip322_txs() {
+        // vectors from BIP322
+        assert_eq!(
+            ::hex::encode(&bip322_msg_hash("")[..]),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            ::hex::encode(&bip322_msg_hash("Hello World")[..]),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+
+        let addr = Address::from_str("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l").unwrap();
+        let to_spend = bip322_to_spend("", &addr.script_pubkey());
+        assert_eq!(to_spend.txid().to_string(), "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7");
+        let to_sign = bip322_to_sign(&to_spend);
+        assert_eq!(to_sign.txid().to_string(), "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6");
+        let to_spend = bip322_to_spend("Hello World", &addr.script_pubkey());
+        assert_eq!(to_spend.txid().to_string(), "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b");
+        let to_sign = bip322_to_sign(&to_spend);
+        assert_eq!(to_sign.txid().to_string(), "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf");
+    }
+
+    #[test]
+    fn test_bip322_simple() {
+        let secp = Secp256k1::new();
+        let addr = Address::from_str("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l").unwrap();
+
+        // vectors from BIP322, for the key below
+        let sig_empty = "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        let sig_hello = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        assert_eq!(verify_bip322_simple(&secp, "", sig_empty, &addr), Ok(true));
+        assert_eq!(verify_bip322_simple(&secp, "Hello World", sig_hello, &addr), Ok(true));
+        assert_eq!(verify_bip322_simple(&secp, "Hello World", sig_empty, &addr), Ok(false));
+
+        let sk = PrivateKey::from_wif("WYf2EhVbhohh8tsYPfERC49As8MRShHFNaD2NfpjJmtr62Z7vU5y").unwrap();
+        assert_eq!(Address::p2wpkh(&sk.public_key(&secp), sk.network), addr);
+        let sig = sign_bip322_simple(&secp, "Hello, Viacoin!", &sk).unwrap();
+        assert_eq!(verify_bip322_simple(&secp, "Hello, Viacoin!", &sig, &addr), Ok(true));
+        assert_eq!(verify_bip322_simple(&secp, "Hello, Viacoin?", &sig, &addr), Ok(false));
+
+        let other = Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap();
+        assert_eq!(verify_bip322_simple(&secp, "Hello, Viacoin!", &sig, &other), Ok(false));
+        let legacy = Address::p2pkh(&sk.public_key(&secp), sk.network);
+        assert_eq!(verify_bip322_simple(&secp, "", sig_empty, &legacy), Err(Error::UnsupportedAddress));
+        assert_eq!(verify_bip322_simple(&secp, "", "AA==", &addr), Err(Error::InvalidWitness));
+
+        let mut uncompressed = sk;
+        uncompressed.compressed = false;
+        assert_eq!(sign_bip322_simple(&secp, "", &uncompressed), Err(Error::UncompressedKey));
+    }
+}
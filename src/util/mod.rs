@@ -18,19 +18,38 @@
 
 pub mod key;
 pub mod address;
+pub mod amount;
 pub mod base58;
+pub mod base64;
 pub mod bip32;
+pub mod bip47;
 pub mod bip143;
+pub mod bip158;
+pub mod coinselect;
 pub mod contracthash;
 pub mod decimal;
+pub mod fee;
 pub mod hash;
+pub mod hex;
+pub mod merkle;
+pub mod message;
 pub mod misc;
+#[cfg(feature = "strason")]
+pub mod payment_request;
+pub mod pow;
 pub mod psbt;
+pub mod scrypt;
+pub mod sighash;
+pub mod silentpayment;
+pub mod taproot;
 pub mod uint;
 
 use std::{error, fmt};
+use std::str::FromStr;
 
+use blockdata::{auxpow, block};
 use network;
+use network::constants::Network;
 use consensus::encode;
 
 /// A trait which allows numbers to act as fixed-size bit arrays
@@ -66,6 +85,10 @@ pub enum Error {
     SpvBadProofOfWork,
     /// The `target` field of a block header did not match the expected difficulty
     SpvBadTarget,
+    /// The auxiliary proof-of-work of a block was invalid
+    AuxPow(auxpow::Error),
+    /// A block failed validation
+    Block(block::Error),
 }
 
 impl fmt::Display for Error {
@@ -73,6 +96,8 @@ impl fmt::Display for Error {
         match *self {
             Error::Encode(ref e) => fmt::Display::fmt(e, f),
             Error::Network(ref e) => fmt::Display::fmt(e, f),
+            Error::AuxPow(ref e) => fmt::Display::fmt(e, f),
+            Error::Block(ref e) => fmt::Display::fmt(e, f),
             Error::SpvBadProofOfWork | Error::SpvBadTarget => f.write_str(error::Error::description(self)),
         }
     }
@@ -83,6 +108,8 @@ impl error::Error for Error {
         match *self {
             Error::Encode(ref e) => Some(e),
             Error::Network(ref e) => Some(e),
+            Error::AuxPow(ref e) => Some(e),
+            Error::Block(ref e) => Some(e),
             Error::SpvBadProofOfWork | Error::SpvBadTarget => None
         }
     }
@@ -91,6 +118,8 @@ impl error::Error for Error {
         match *self {
             Error::Encode(ref e) => e.description(),
             Error::Network(ref e) => e.description(),
+            Error::AuxPow(ref e) => e.description(),
+            Error::Block(ref e) => e.description(),
             Error::SpvBadProofOfWork => "target correct but not attained",
             Error::SpvBadTarget => "target incorrect",
         }
@@ -110,3 +139,54 @@ impl From<network::Error> for Error {
         Error::Network(e)
     }
 }
+
+#[doc(hidden)]
+impl From<auxpow::Error> for Error {
+    fn from(e: auxpow::Error) -> Error {
+        Error::AuxPow(e)
+    }
+}
+
+#[doc(hidden)]
+impl From<block::Error> for Error {
+    fn from(e: block::Error) -> Error {
+        Error::Block(e)
+    }
+}
+
+/// Guess the network of an address, WIF private key or extended key string by
+/// trying each of their parsers in turn. Returns `None` if none of them accept it.
+pub fn detect_network(s: &str) -> Option<Network> {
+    if let Ok(addr) = address::Address::from_str(s) {
+        return Some(addr.network);
+    }
+    if let Ok(sk) = key::PrivateKey::from_str(s) {
+        return Some(sk.network);
+    }
+    if let Ok(xpub) = bip32::ExtendedPubKey::from_str(s) {
+        return Some(xpub.network);
+    }
+    if let Ok(xpriv) = bip32::ExtendedPrivKey::from_str(s) {
+        return Some(xpriv.network);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use network::constants::Network;
+    use super::detect_network;
+
+    #[test]
+    fn test_detect_network() {
+        assert_eq!(detect_network("VkRhad19WpsYdnp1A7sCdiguhyJA3zS4Su"), Some(Network::Bitcoin));
+        assert_eq!(detect_network("tb1qwfjcnutuv4djp2qr73vejvvs0gzs6pu9gypkwh"), Some(Network::Testnet));
+        assert_eq!(detect_network("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy"), Some(Network::Testnet));
+        assert_eq!(
+            detect_network("xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"),
+            Some(Network::Bitcoin)
+        );
+        assert_eq!(detect_network("not a key or address"), None);
+        assert_eq!(detect_network(""), None);
+    }
+}
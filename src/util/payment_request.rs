@@ -0,0 +1,257 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Payment requests
+//!
+//! A minimal reader for BIP70-style payment requests in a JSON form rather
+//! than protobuf, keeping only what is needed to pay them. A request is a
+//! flat object whose values are strings or whole numbers, such as
+//!
+//! ```text
+//! {"address": "VbC9y2C6uedKmBMsguGyY6p8ta7MWUHovG", "amount": 150000000, "memo": "Invoice 42"}
+//! ```
+//!
+//! where `amount` is in satoshis, and a hex `script` may replace `address`.
+//! Other fields are ignored. The JSON is read with `strason`, so this
+//! module needs that feature.
+
+use std::{error, fmt};
+use std::str::FromStr;
+
+use strason::{self, Json};
+
+use blockdata::script::Script;
+use consensus::encode;
+use network::constants::Network;
+use util::address::Address;
+use util::amount::Amount;
+use util::misc::hex_bytes;
+
+/// An error reading a payment request
+#[derive(Debug)]
+pub enum Error {
+    /// The request was not well-formed JSON
+    Json(strason::Error),
+    /// The request was not a JSON object
+    NotAnObject,
+    /// The named field appeared more than once
+    DuplicateField(String),
+    /// The named required field was missing
+    MissingField(&'static str),
+    /// The named field had the wrong type or an unusable value
+    InvalidField(&'static str),
+    /// The address could not be parsed
+    Address(encode::Error),
+    /// The address is for another network than the one expected
+    WrongNetwork(Network),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Json(ref e) => fmt::Display::fmt(e, f),
+            Error::NotAnObject => f.write_str(error::Error::description(self)),
+            Error::DuplicateField(ref name) => write!(f, "duplicate field {}", name),
+            Error::MissingField(name) => write!(f, "missing field {}", name),
+            Error::InvalidField(name) => write!(f, "invalid field {}", name),
+            Error::Address(ref e) => fmt::Display::fmt(e, f),
+            Error::WrongNetwork(n) => write!(f, "address is for network {}", n),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Json(ref e) => Some(e),
+            Error::Address(ref e) => Some(e),
+            _ => None,
+        }
+    }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::Json(ref e) => error::Error::description(e),
+            Error::NotAnObject => "payment request is not an object",
+            Error::DuplicateField(_) => "duplicate field",
+            Error::MissingField(_) => "missing field",
+            Error::InvalidField(_) => "invalid field",
+            Error::Address(ref e) => error::Error::description(e),
+            Error::WrongNetwork(_) => "address is for the wrong network",
+        }
+    }
+}
+
+/// A payment request, reduced to what is needed to pay it
+#[derive(Clone, PartialEq, Debug)]
+pub struct PaymentRequest {
+    /// The address to pay
+    pub address: Address,
+    /// The amount to pay; zero lets the payer choose
+    pub amount: Amount,
+    /// A note for the payer, if the request has one
+    pub memo: Option<String>,
+}
+
+impl PaymentRequest {
+    /// Reads a payment request in its JSON form, expecting it to pay to an
+    /// address on `network` no more than the money supply
+    pub fn from_json(s: &str, network: Network) -> Result<PaymentRequest, Error> {
+        let json = Json::from_str(s).map_err(Error::Json)?;
+        let fields = json.object().ok_or(Error::NotAnObject)?;
+        for (idx, &(ref key, _)) in fields.iter().enumerate() {
+            if fields[..idx].iter().any(|field| field.0 == *key) {
+                return Err(Error::DuplicateField(key.clone()));
+            }
+        }
+        let field = |name: &'static str| fields.iter().find(|field| field.0 == name).map(|field| &field.1);
+        let string = |name: &'static str| match field(name) {
+            Some(value) => value.string().map(Some).ok_or(Error::InvalidField(name)),
+            None => Ok(None),
+        };
+
+        let address = string("address")?;
+        let script = string("script")?;
+        let memo = string("memo")?.map(str::to_owned);
+        // a whole number of satoshis, so no sign, fraction or exponent
+        let amount = match field("amount") {
+            Some(value) => {
+                let sat = value.num().and_then(|n| u64::from_str(n).ok()).ok_or(Error::InvalidField("amount"))?;
+                if sat > network.max_money().as_sat() {
+                    return Err(Error::InvalidField("amount"));
+                }
+                Some(Amount::from_sat(sat))
+            }
+            None => None,
+        };
+
+        let address = match (address, script) {
+            (Some(s), None) => {
+                let address = Address::from_str(s).map_err(Error::Address)?;
+                if !address.is_valid_for_network(network) {
+                    return Err(Error::WrongNetwork(address.network));
+                }
+                address
+            }
+            (None, Some(s)) => {
+                let script = Script::from(hex_bytes(s).map_err(|_| Error::InvalidField("script"))?);
+                Address::from_script(&script, network).ok_or(Error::InvalidField("script"))?
+            }
+            // a request paying two places at once is ambiguous
+            (Some(_), Some(_)) => return Err(Error::InvalidField("script")),
+            (None, None) => return Err(Error::MissingField("address")),
+        };
+        Ok(PaymentRequest {
+            address: address,
+            amount: amount.ok_or(Error::MissingField("amount"))?,
+            memo: memo,
+        })
+    }
+
+    /// The address and amount to pay
+    pub fn into_pair(self) -> (Address, Amount) {
+        (self.address, self.amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use network::constants::Network;
+    use util::address::Address;
+    use util::amount::Amount;
+    use super::{Error, PaymentRequest};
+
+    #[test]
+    fn test_from_json() {
+        let json = "{\n  \"network\": \"main\",\n  \"address\": \"VbC9y2C6uedKmBMsguGyY6p8ta7MWUHovG\",\n  \"amount\": 150000000,\n  \"memo\": \"Invoice 42 \\u2014 \\\"thanks\\\"\"\n}";
+        let request = PaymentRequest::from_json(json, Network::Bitcoin).unwrap();
+        assert_eq!(request.memo, Some("Invoice 42 \u{2014} \"thanks\"".to_owned()));
+        let (address, amount) = request.into_pair();
+        assert_eq!(address, Address::from_str("VbC9y2C6uedKmBMsguGyY6p8ta7MWUHovG").unwrap());
+        assert_eq!(amount, Amount::from_sat(150_000_000));
+
+        let json = r#"{"script":"0014751e76e8199196d454941c45d1b3a323f1433bd6","amount":1000}"#;
+        let request = PaymentRequest::from_json(json, Network::Bitcoin).unwrap();
+        assert_eq!(request.address.to_string(), "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+        assert_eq!(request.amount, Amount::from_sat(1000));
+        assert_eq!(request.memo, None);
+
+        // testnet and regtest share their legacy address prefixes
+        let json = r#"{"address":"mqwpxxvfv3QbM8PU8uBx2jaNt9btQqvQNx","amount":1000}"#;
+        let request = PaymentRequest::from_json(json, Network::Regtest).unwrap();
+        assert_eq!(request.address, Address::from_str("mqwpxxvfv3QbM8PU8uBx2jaNt9btQqvQNx").unwrap());
+    }
+
+    #[test]
+    fn test_from_json_errors() {
+        let err = |json: &str| PaymentRequest::from_json(json, Network::Bitcoin).unwrap_err();
+
+        match err(r#"{"amount":1000}"#) {
+            Error::MissingField("address") => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        match err(r#"{"address":"VbC9y2C6uedKmBMsguGyY6p8ta7MWUHovG"}"#) {
+            Error::MissingField("amount") => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        match err(r#"{"amount":1,"amount":2,"address":"VbC9y2C6uedKmBMsguGyY6p8ta7MWUHovG"}"#) {
+            Error::DuplicateField(ref name) if name == "amount" => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        match err(r#"{"amount":"1000","address":"VbC9y2C6uedKmBMsguGyY6p8ta7MWUHovG"}"#) {
+            Error::InvalidField("amount") => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        match err(r#"{"amount":1000,"address":"tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"}"#) {
+            Error::WrongNetwork(Network::Testnet) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        match PaymentRequest::from_json(r#"{"amount":1000,"address":"tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"}"#, Network::Regtest) {
+            Err(Error::WrongNetwork(Network::Testnet)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        // an OP_RETURN script has no address to pay
+        match err(r#"{"amount":0,"script":"6a0b68656c6c6f20776f726c64"}"#) {
+            Error::InvalidField("script") => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        for amount in &["1.5", "-1", "1e3", "18446744073709551616"] {
+            match err(&format!(r#"{{"amount":{},"address":"VbC9y2C6uedKmBMsguGyY6p8ta7MWUHovG"}}"#, amount)) {
+                Error::InvalidField("amount") => {},
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        // more than the money supply
+        let max = Network::Bitcoin.max_money().as_sat();
+        assert!(PaymentRequest::from_json(&format!(r#"{{"amount":{},"address":"VbC9y2C6uedKmBMsguGyY6p8ta7MWUHovG"}}"#, max), Network::Bitcoin).is_ok());
+        match err(&format!(r#"{{"amount":{},"address":"VbC9y2C6uedKmBMsguGyY6p8ta7MWUHovG"}}"#, max + 1)) {
+            Error::InvalidField("amount") => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        match err(r#"{"amount":01,"address":"VbC9y2C6uedKmBMsguGyY6p8ta7MWUHovG"}"#) {
+            Error::Json(_) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        match err(r#"{"amount":1000,"#) {
+            Error::Json(_) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        match err(r#"[1000]"#) {
+            Error::NotAnObject => {},
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
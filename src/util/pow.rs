@@ -0,0 +1,60 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Proof of work
+//!
+//! Measures of the work behind headers, as used to select the best chain.
+
+use blockdata::block::BlockHeader;
+use util::uint::Uint256;
+
+/// The total work of a chain of headers, the sum of `2^256 / (target + 1)`
+/// over each header. Nodes follow the valid chain with the most work, which
+/// need not be the longest one.
+pub fn chainwork(headers: &[BlockHeader]) -> Uint256 {
+    headers.iter().fold(Uint256::from_u64(0).unwrap(), |acc, header| acc + header.work())
+}
+
+#[cfg(test)]
+mod tests {
+    use blockdata::block::BlockHeader;
+    use util::uint::Uint256;
+    use super::chainwork;
+
+    fn header(bits: u32) -> BlockHeader {
+        BlockHeader {
+            version: 1,
+            prev_blockhash: Default::default(),
+            merkle_root: Default::default(),
+            time: 0,
+            bits: bits,
+            nonce: 0,
+            aux_pow: None,
+        }
+    }
+
+    #[test]
+    fn test_chainwork() {
+        assert_eq!(chainwork(&[]), Uint256::from_u64(0).unwrap());
+        // difficulty 1 is 2^32 + 2^16 + 1 hashes, rounded down
+        assert_eq!(chainwork(&[header(0x1d00ffff)]), Uint256::from_u64(0x100010001).unwrap());
+        assert_eq!(chainwork(&vec![header(0x1d00ffff); 3]), Uint256::from_u64(3 * 0x100010001).unwrap());
+
+        // three easy headers carry less work than two which are each twice as hard
+        let easy = vec![header(0x1d00ffff); 3];
+        let hard = vec![header(0x1c7fff80); 2];
+        assert!(chainwork(&hard) > chainwork(&easy));
+        assert_eq!(chainwork(&hard), Uint256::from_u64(4 * 0x100010001).unwrap());
+    }
+}
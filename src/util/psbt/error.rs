@@ -15,7 +15,10 @@
 use std::error;
 use std::fmt;
 
+use bitcoin_hashes::sha256d;
+
 use blockdata::transaction::Transaction;
+use util::base64;
 use util::psbt::raw;
 
 /// Ways that a Partially Signed Transaction might fail.
@@ -48,6 +51,21 @@ pub enum Error {
     },
     /// Unable to parse as a standard SigHash type.
     NonStandardSigHashType(u32),
+    /// The base64 encoding of a PSBT was malformed.
+    InvalidBase64(base64::Error),
+    /// There is no input or output at this index.
+    IndexOutOfRange(usize),
+    /// A previous transaction attached to an input is not the one it spends.
+    NonWitnessUtxoMismatch {
+        /// The txid spent by the input
+        expected: sha256d::Hash,
+        /// The txid of the transaction attached
+        actual: sha256d::Hash,
+    },
+    /// The value spent by the input at this index is not known.
+    MissingUtxo(usize),
+    /// The outputs are worth more than the inputs, or the sums overflow.
+    NegativeFee,
 }
 
 impl fmt::Display for Error {
@@ -57,12 +75,17 @@ impl fmt::Display for Error {
             Error::DuplicateKey(ref rkey) => write!(f, "{}: {}", error::Error::description(self), rkey),
             Error::UnexpectedUnsignedTx { expected: ref e, actual: ref a } => write!(f, "{}: expected {}, actual {}", error::Error::description(self), e.txid(), a.txid()),
             Error::NonStandardSigHashType(ref sht) => write!(f, "{}: {}", error::Error::description(self), sht),
+            Error::InvalidBase64(ref e) => write!(f, "{}: {}", error::Error::description(self), e),
+            Error::IndexOutOfRange(index) => write!(f, "{}: {}", error::Error::description(self), index),
+            Error::NonWitnessUtxoMismatch { expected: ref e, actual: ref a } => write!(f, "{}: expected {}, actual {}", error::Error::description(self), e, a),
+            Error::MissingUtxo(index) => write!(f, "{}: input {}", error::Error::description(self), index),
             Error::InvalidMagic
             | Error::InvalidSeparator
             | Error::UnsignedTxHasScriptSigs
             | Error::UnsignedTxHasScriptWitnesses
             | Error::MustHaveUnsignedTx
-            | Error::NoMorePairs => f.write_str(error::Error::description(self))
+            | Error::NoMorePairs
+            | Error::NegativeFee => f.write_str(error::Error::description(self))
         }
     }
 }
@@ -82,6 +105,11 @@ impl error::Error for Error {
             Error::NoMorePairs => "no more key-value pairs for this psbt map",
             Error::UnexpectedUnsignedTx { .. } => "different unsigned transaction",
             Error::NonStandardSigHashType(..) =>  "non-standard sighash type",
+            Error::InvalidBase64(..) => "invalid base64",
+            Error::IndexOutOfRange(..) => "index out of range",
+            Error::NonWitnessUtxoMismatch { .. } => "previous transaction does not match the input",
+            Error::MissingUtxo(..) => "value of spent output unknown",
+            Error::NegativeFee => "outputs exceed inputs",
         }
     }
 }
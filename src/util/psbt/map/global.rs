@@ -17,6 +17,7 @@ use std::io::Cursor;
 
 use blockdata::transaction::Transaction;
 use consensus::encode::{self, Encodable, Decodable, Decoder};
+use util::bip32::{DerivationPath, ExtendedPubKey, Fingerprint};
 use util::psbt::map::Map;
 use util::psbt::raw;
 use util::psbt;
@@ -28,6 +29,9 @@ pub struct Global {
     /// The unsigned transaction, scriptSigs and witnesses for each input must be
     /// empty.
     pub unsigned_tx: Transaction,
+    /// A map from the extended public keys of the wallet to the master key
+    /// fingerprint and derivation path each was derived at.
+    pub xpub: HashMap<ExtendedPubKey, (Fingerprint, DerivationPath)>,
     /// Unknown global key-value pairs.
     pub unknown: HashMap<raw::Key, Vec<u8>>,
 }
@@ -47,6 +51,7 @@ impl Global {
 
         Ok(Global {
             unsigned_tx: tx,
+            xpub: Default::default(),
             unknown: Default::default(),
         })
     }
@@ -63,6 +68,11 @@ impl Map for Global {
             0u8 => {
                 return Err(Error::DuplicateKey(raw_key).into());
             }
+            1u8 => {
+                impl_psbt_insert_pair! {
+                    self.xpub <= <raw_key: ExtendedPubKey>|<raw_value: (Fingerprint, DerivationPath)>
+                }
+            }
             _ => {
                 if self.unknown.contains_key(&raw_key) {
                     return Err(Error::DuplicateKey(raw_key).into());
@@ -95,6 +105,10 @@ impl Map for Global {
             },
         });
 
+        impl_psbt_get_pair! {
+            rv.push(self.xpub as <1u8, ExtendedPubKey>|<(Fingerprint, DerivationPath)>)
+        }
+
         for (key, value) in self.unknown.iter() {
             rv.push(raw::Pair {
                 key: key.clone(),
@@ -113,6 +127,7 @@ impl Map for Global {
             });
         }
 
+        self.xpub.extend(other.xpub);
         self.unknown.extend(other.unknown);
         Ok(())
     }
@@ -124,7 +139,7 @@ impl<D: Decoder> Decodable<D> for Global {
     fn consensus_decode(d: &mut D) -> Result<Self, encode::Error> {
 
         let mut tx: Option<Transaction> = None;
-        let mut unknowns: HashMap<raw::Key, Vec<u8>> = Default::default();
+        let mut pairs: Vec<raw::Pair> = Default::default();
 
         loop {
             match raw::Pair::consensus_decode(d) {
@@ -158,13 +173,7 @@ impl<D: Decoder> Decodable<D> for Global {
                                 return Err(Error::InvalidKey(pair.key).into())
                             }
                         }
-                        _ => {
-                            if unknowns.contains_key(&pair.key) {
-                                return Err(Error::DuplicateKey(pair.key).into());
-                            } else {
-                                unknowns.insert(pair.key, pair.value);
-                            }
-                        }
+                        _ => pairs.push(pair),
                     }
                 }
                 Err(::consensus::encode::Error::Psbt(::util::psbt::Error::NoMorePairs)) => break,
@@ -174,7 +183,9 @@ impl<D: Decoder> Decodable<D> for Global {
 
         if let Some(tx) = tx {
             let mut rv: Global = Global::from_unsigned_tx(tx)?;
-            rv.unknown = unknowns;
+            for pair in pairs {
+                rv.insert_pair(pair)?;
+            }
             Ok(rv)
         } else {
             Err(Error::MustHaveUnsignedTx.into())
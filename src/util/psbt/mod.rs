@@ -21,6 +21,10 @@
 use blockdata::script::Script;
 use blockdata::transaction::Transaction;
 use consensus::encode::{self, Encodable, Decodable, Encoder, Decoder};
+use util::amount::Amount;
+use util::base64;
+use util::bip32::{DerivationPath, ExtendedPubKey, Fingerprint};
+use util::key::PublicKey;
 
 mod error;
 pub use self::error::Error;
@@ -35,6 +39,9 @@ pub mod serialize;
 mod map;
 pub use self::map::{Map, Global, Input, Output};
 
+/// A shorter name for `PartiallySignedTransaction`
+pub type Psbt = PartiallySignedTransaction;
+
 /// A Partially Signed Transaction.
 #[derive(Debug, Clone, PartialEq)]
 pub struct PartiallySignedTransaction {
@@ -59,6 +66,100 @@ impl PartiallySignedTransaction {
         })
     }
 
+    /// Parse a PartiallySignedTransaction from base64, the encoding PSBTs are
+    /// usually exchanged in
+    pub fn from_base64(s: &str) -> Result<Self, encode::Error> {
+        let data = base64::from(s).map_err(Error::InvalidBase64)?;
+        encode::deserialize(&data)
+    }
+
+    /// Serialize the PartiallySignedTransaction as base64
+    pub fn to_base64(&self) -> String {
+        base64::encode_slice(&encode::serialize(self))
+    }
+
+    /// Serialize the PartiallySignedTransaction as lowercase hex
+    pub fn to_hex(&self) -> String {
+        encode::serialize_hex(self)
+    }
+
+    /// The unsigned transaction this PartiallySignedTransaction is for
+    pub fn unsigned_tx(&self) -> &Transaction {
+        &self.global.unsigned_tx
+    }
+
+    /// Attach the full previous transaction to an input, as legacy inputs need
+    /// for signing. It must be the transaction the input spends from.
+    pub fn add_non_witness_utxo(&mut self, input_index: usize, tx: Transaction) -> Result<(), self::Error> {
+        let expected = match self.global.unsigned_tx.input.get(input_index) {
+            Some(txin) => txin.previous_output.txid,
+            None => return Err(Error::IndexOutOfRange(input_index)),
+        };
+        let actual = tx.txid();
+        if actual != expected {
+            return Err(Error::NonWitnessUtxoMismatch {
+                expected: expected,
+                actual: actual,
+            });
+        }
+        self.inputs[input_index].non_witness_utxo = Some(tx);
+        Ok(())
+    }
+
+    /// Record the master key fingerprint and derivation path of a key used by
+    /// an input, or by an output if `is_output`, so a signer can find it
+    pub fn add_key_source(
+        &mut self,
+        index: usize,
+        is_output: bool,
+        pubkey: PublicKey,
+        source: (Fingerprint, DerivationPath),
+    ) -> Result<(), self::Error> {
+        let hd_keypaths = if is_output {
+            self.outputs.get_mut(index).map(|output| &mut output.hd_keypaths)
+        } else {
+            self.inputs.get_mut(index).map(|input| &mut input.hd_keypaths)
+        };
+        match hd_keypaths {
+            Some(hd_keypaths) => {
+                hd_keypaths.insert(pubkey, source);
+                Ok(())
+            }
+            None => Err(Error::IndexOutOfRange(index)),
+        }
+    }
+
+    /// Record the master key fingerprint and derivation path of an extended
+    /// public key of the wallet, so signers know the wallet's key structure
+    pub fn add_global_xpub(&mut self, xpub: ExtendedPubKey, source: (Fingerprint, DerivationPath)) {
+        self.global.xpub.insert(xpub, source);
+    }
+
+    /// The fee the final transaction will pay: the value of the outputs spent,
+    /// from each input's witness or non-witness UTXO, less that of the outputs
+    pub fn fee(&self) -> Result<Amount, self::Error> {
+        let mut total_in = Amount::ZERO;
+        for (index, (txin, input)) in self.global.unsigned_tx.input.iter().zip(self.inputs.iter()).enumerate() {
+            let value = if let Some(ref utxo) = input.witness_utxo {
+                utxo.value
+            } else if let Some(ref tx) = input.non_witness_utxo {
+                match tx.output.get(txin.previous_output.vout as usize) {
+                    Some(utxo) => utxo.value,
+                    None => return Err(Error::MissingUtxo(index)),
+                }
+            } else {
+                return Err(Error::MissingUtxo(index));
+            };
+            total_in = total_in.checked_add(Amount::from_sat(value)).ok_or(Error::NegativeFee)?;
+        }
+
+        let mut total_out = Amount::ZERO;
+        for txout in &self.global.unsigned_tx.output {
+            total_out = total_out.checked_add(Amount::from_sat(txout.value)).ok_or(Error::NegativeFee)?;
+        }
+        total_in.checked_sub(total_out).ok_or(Error::NegativeFee)
+    }
+
     /// Extract the Transaction from a PartiallySignedTransaction by filling in
     /// the available signature information in place.
     pub fn extract_tx(self) -> Transaction {
@@ -168,13 +269,14 @@ mod tests {
     use blockdata::script::Script;
     use blockdata::transaction::{Transaction, TxIn, TxOut, OutPoint};
     use network::constants::Network::Bitcoin;
-    use consensus::encode::{deserialize, serialize, serialize_hex};
+    use consensus::encode::{self, deserialize, serialize, serialize_hex};
     use util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint};
+    use util::amount::Amount;
     use util::key::PublicKey;
     use util::psbt::map::{Global, Output};
     use util::psbt::raw;
 
-    use super::PartiallySignedTransaction;
+    use super::{Error, PartiallySignedTransaction, Psbt};
 
     #[test]
     fn trivial_psbt() {
@@ -186,6 +288,7 @@ mod tests {
                     input: vec![],
                     output: vec![],
                 },
+                xpub: Default::default(),
                 unknown: HashMap::new(),
             },
             inputs: vec![],
@@ -197,6 +300,191 @@ mod tests {
         );
     }
 
+    #[test]
+    fn from_base64() {
+        let psbt = Psbt::from_base64("cHNidP8BAKACAAAAAqsJSaCMWvfEm4IS9Bfi8Vqz9cM9zxU4IagTn4d6W3vkAAAAAAD+////qwlJoIxa98SbghL0F+LxWrP1wz3PFTghqBOfh3pbe+QBAAAAAP7///8CYDvqCwAAAAAZdqkUdopAu9dAy+gdmI5x3ipNXHE5ax2IrI4kAAAAAAAAGXapFG9GILVT+glechue4O/p+gOcykWXiKwAAAAAAAEHakcwRAIgR1lmF5fAGwNrJZKJSGhiGDR9iYZLcZ4ff89X0eURZYcCIFMJ6r9Wqk2Ikf/REf3xM286KdqGbX+EhtdVRs7tr5MZASEDXNxh/HupccC1AaZGoqg7ECy0OIEhfKaC3Ibi1z+ogpIAAQEgAOH1BQAAAAAXqRQ1RebjO4MsRwUPJNPuuTycA5SLx4cBBBYAFIXRNTfy4mVAWjTbr6nj3aAfuCMIAAAA").unwrap();
+        assert_eq!(psbt.unsigned_tx().input.len(), 2);
+        assert_eq!(psbt.unsigned_tx().output.len(), 2);
+        assert_eq!(psbt.inputs.len(), 2);
+
+        match Psbt::from_base64("cHNidP8BAKACAAAAAqsJSaCMWvfEm4IS9Bfi8Vqz9cM9zxU4IagTn4d6W3vk!") {
+            Err(encode::Error::Psbt(Error::InvalidBase64(_))) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        // "psbu" followed by the separator
+        match Psbt::from_base64("cHNidf8=") {
+            Err(encode::Error::Psbt(Error::InvalidMagic)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        let b64 = "cHNidP8BAKACAAAAAqsJSaCMWvfEm4IS9Bfi8Vqz9cM9zxU4IagTn4d6W3vkAAAAAAD+////qwlJoIxa98SbghL0F+LxWrP1wz3PFTghqBOfh3pbe+QBAAAAAP7///8CYDvqCwAAAAAZdqkUdopAu9dAy+gdmI5x3ipNXHE5ax2IrI4kAAAAAAAAGXapFG9GILVT+glechue4O/p+gOcykWXiKwAAAAAAAEHakcwRAIgR1lmF5fAGwNrJZKJSGhiGDR9iYZLcZ4ff89X0eURZYcCIFMJ6r9Wqk2Ikf/REf3xM286KdqGbX+EhtdVRs7tr5MZASEDXNxh/HupccC1AaZGoqg7ECy0OIEhfKaC3Ibi1z+ogpIAAQEgAOH1BQAAAAAXqRQ1RebjO4MsRwUPJNPuuTycA5SLx4cBBBYAFIXRNTfy4mVAWjTbr6nj3aAfuCMIAAAA";
+        let psbt = Psbt::from_base64(b64).unwrap();
+        assert_eq!(psbt.to_base64(), b64);
+        assert_eq!(Psbt::from_base64(&psbt.to_base64()).unwrap(), psbt);
+        assert_eq!(psbt.to_hex(), serialize_hex(&psbt));
+        assert!(psbt.to_hex().starts_with("70736274ff"));
+        let from_hex: Psbt = deserialize(&hex_decode(&psbt.to_hex()).unwrap()).unwrap();
+        assert_eq!(from_hex, psbt);
+    }
+
+    #[test]
+    fn add_non_witness_utxo() {
+        let prev_tx = |value: u64| Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut {
+                value: value,
+                script_pubkey: hex_script!("76a914d0c59903c5bac2868760e90fd521a4665aa7652088ac"),
+            }],
+        };
+        let unsigned_tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint { txid: prev_tx(100000000).txid(), vout: 0 },
+                script_sig: Script::new(),
+                sequence: 0xffffffff,
+                witness: vec![],
+            }],
+            output: vec![],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+
+        match psbt.add_non_witness_utxo(0, prev_tx(99999999)) {
+            Err(Error::NonWitnessUtxoMismatch { expected, actual }) => {
+                assert_eq!(expected, prev_tx(100000000).txid());
+                assert_eq!(actual, prev_tx(99999999).txid());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(psbt.inputs[0].non_witness_utxo, None);
+        match psbt.add_non_witness_utxo(1, prev_tx(100000000)) {
+            Err(Error::IndexOutOfRange(1)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+
+        psbt.add_non_witness_utxo(0, prev_tx(100000000)).unwrap();
+        assert_eq!(psbt.inputs[0].non_witness_utxo, Some(prev_tx(100000000)));
+    }
+
+    #[test]
+    fn add_key_source() {
+        let secp = Secp256k1::new();
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Script::new(),
+                sequence: 0xffffffff,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 99999699,
+                script_pubkey: hex_script!("76a914d0c59903c5bac2868760e90fd521a4665aa7652088ac"),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+
+        let sk = ExtendedPrivKey::new_master(Bitcoin, &[0; 32]).unwrap();
+        let fingerprint = sk.fingerprint(&secp);
+        let path: DerivationPath = vec![ChildNumber::from_hardened_idx(84).unwrap(), ChildNumber::from_normal_idx(0).unwrap()].into();
+        let pk = ExtendedPubKey::from_private(&secp, &sk.derive_priv(&secp, &path).unwrap()).public_key;
+
+        psbt.add_key_source(0, false, pk, (fingerprint, path.clone())).unwrap();
+        psbt.add_key_source(0, true, pk, (fingerprint, path.clone())).unwrap();
+        assert_eq!(psbt.inputs[0].hd_keypaths.get(&pk), Some(&(fingerprint, path.clone())));
+        assert_eq!(psbt.outputs[0].hd_keypaths.get(&pk), Some(&(fingerprint, path.clone())));
+
+        // the derivation survives serialization
+        let decoded = Psbt::from_base64(&psbt.to_base64()).unwrap();
+        assert_eq!(decoded.inputs[0].hd_keypaths.get(&pk), Some(&(fingerprint, path.clone())));
+
+        match psbt.add_key_source(1, true, pk, (fingerprint, path)) {
+            Err(Error::IndexOutOfRange(1)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn add_global_xpub() {
+        let secp = Secp256k1::new();
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+
+        let sk = ExtendedPrivKey::new_master(Bitcoin, &[0; 32]).unwrap();
+        let fingerprint = sk.fingerprint(&secp);
+        let path: DerivationPath = vec![ChildNumber::from_hardened_idx(48).unwrap(), ChildNumber::from_hardened_idx(0).unwrap()].into();
+        let xpub = ExtendedPubKey::from_private(&secp, &sk.derive_priv(&secp, &path).unwrap());
+
+        psbt.add_global_xpub(xpub, (fingerprint, path.clone()));
+        assert_eq!(psbt.global.xpub.get(&xpub), Some(&(fingerprint, path.clone())));
+
+        // serialized under PSBT_GLOBAL_XPUB, keyed by the 78-byte xpub
+        let hex = psbt.to_hex();
+        assert!(hex.contains(&format!("4f01{}", ::hex::encode(&xpub.encode()[..]))));
+        let decoded = Psbt::from_base64(&psbt.to_base64()).unwrap();
+        assert_eq!(decoded.global.xpub.get(&xpub), Some(&(fingerprint, path)));
+        assert_eq!(decoded, psbt);
+    }
+
+    #[test]
+    fn fee() {
+        let prev_tx = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![],
+            output: vec![
+                TxOut { value: 50000, script_pubkey: Script::new() },
+                TxOut { value: 100000, script_pubkey: Script::new() },
+            ],
+        };
+        let txin = |vout: u32| TxIn {
+            previous_output: OutPoint { txid: prev_tx.txid(), vout: vout },
+            script_sig: Script::new(),
+            sequence: 0xffffffff,
+            witness: vec![],
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![txin(1), txin(2)],
+            output: vec![TxOut { value: 129000, script_pubkey: Script::new() }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+
+        psbt.add_non_witness_utxo(0, prev_tx.clone()).unwrap();
+        match psbt.fee() {
+            Err(Error::MissingUtxo(1)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        psbt.inputs[1].witness_utxo = Some(TxOut { value: 30000, script_pubkey: Script::new() });
+        assert_eq!(psbt.fee().unwrap(), Amount::from_sat(1000));
+
+        // an output index the previous transaction does not have
+        psbt.add_non_witness_utxo(1, prev_tx.clone()).unwrap();
+        psbt.inputs[1].witness_utxo = None;
+        match psbt.fee() {
+            Err(Error::MissingUtxo(1)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+
+        psbt.inputs[1].witness_utxo = Some(TxOut { value: 28999, script_pubkey: Script::new() });
+        match psbt.fee() {
+            Err(Error::NegativeFee) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
     #[test]
     fn serialize_then_deserialize_output() {
         let secp = &Secp256k1::new();
@@ -273,6 +561,7 @@ mod tests {
                     },
                 ],
             },
+            xpub: Default::default(),
             unknown: Default::default(),
         };
 
@@ -370,6 +659,7 @@ mod tests {
                             },
                         ],
                     },
+                    xpub: Default::default(),
                     unknown: HashMap::new(),
                 },
                 inputs: vec![Input {
@@ -22,7 +22,7 @@ use std::io::{self, Cursor};
 use blockdata::script::Script;
 use blockdata::transaction::{SigHashType, Transaction, TxOut};
 use consensus::encode::{self, serialize, Decodable};
-use util::bip32::{ChildNumber, DerivationPath, Fingerprint};
+use util::bip32::{ChildNumber, DerivationPath, ExtendedPubKey, Fingerprint};
 use util::key::PublicKey;
 use util::psbt;
 
@@ -70,6 +70,19 @@ impl Deserialize for PublicKey {
     }
 }
 
+impl Serialize for ExtendedPubKey {
+    fn serialize(&self) -> Vec<u8> {
+        self.encode().to_vec()
+    }
+}
+
+impl Deserialize for ExtendedPubKey {
+    fn deserialize(bytes: &[u8]) -> Result<Self, encode::Error> {
+        ExtendedPubKey::decode(bytes)
+            .map_err(|_| encode::Error::ParseFailed("invalid extended public key"))
+    }
+}
+
 impl Serialize for (Fingerprint, DerivationPath) {
     fn serialize(&self) -> Vec<u8> {
         let mut rv: Vec<u8> = Vec::with_capacity(4 + 4 * (self.1).as_ref().len());
@@ -0,0 +1,134 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! scrypt
+//!
+//! The scrypt key derivation function, with the fixed parameters Viacoin
+//! (like Litecoin) uses to hash block headers for proof-of-work.
+
+use bitcoin_hashes::{sha256, HashEngine, Hash, Hmac, HmacEngine};
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Single-iteration PBKDF2-HMAC-SHA256, which is all scrypt needs
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], out: &mut [u8]) {
+    for (i, chunk) in out.chunks_mut(32).enumerate() {
+        let mut engine = HmacEngine::<sha256::Hash>::new(password);
+        engine.input(salt);
+        engine.input(&[0, 0, 0, i as u8 + 1]);
+        let t = Hmac::from_engine(engine);
+        let len = chunk.len();
+        chunk.copy_from_slice(&t[..len]);
+    }
+}
+
+fn salsa20_8(b: &mut [u32; 16]) {
+    fn quarter(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+        x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+        x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+        x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+        x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+    }
+
+    let mut x = *b;
+    for _ in 0..4 {
+        // columns
+        quarter(&mut x, 0, 4, 8, 12);
+        quarter(&mut x, 5, 9, 13, 1);
+        quarter(&mut x, 10, 14, 2, 6);
+        quarter(&mut x, 15, 3, 7, 11);
+        // rows
+        quarter(&mut x, 0, 1, 2, 3);
+        quarter(&mut x, 5, 6, 7, 4);
+        quarter(&mut x, 10, 11, 8, 9);
+        quarter(&mut x, 15, 12, 13, 14);
+    }
+    for (b, x) in b.iter_mut().zip(x.iter()) {
+        *b = b.wrapping_add(*x);
+    }
+}
+
+/// BlockMix with r = 1, on a block of two 64-byte halves
+fn block_mix(b: &mut [u32; 32]) {
+    let mut x = [0u32; 16];
+    x.copy_from_slice(&b[16..]);
+    for half in 0..2 {
+        for i in 0..16 {
+            x[i] ^= b[16 * half + i];
+        }
+        salsa20_8(&mut x);
+        b[16 * half..16 * half + 16].copy_from_slice(&x);
+    }
+}
+
+/// scrypt with N = 1024, r = 1, p = 1 and a 32-byte output, salted with the
+/// input itself, as used for proof-of-work
+pub fn scrypt_1024_1_1_256(input: &[u8]) -> [u8; 32] {
+    let mut bytes = [0u8; 128];
+    pbkdf2_sha256(input, input, &mut bytes);
+    let mut x = [0u32; 32];
+    LittleEndian::read_u32_into(&bytes, &mut x);
+
+    let mut v = vec![[0u32; 32]; 1024];
+    for v_i in v.iter_mut() {
+        *v_i = x;
+        block_mix(&mut x);
+    }
+    for _ in 0..1024 {
+        let j = (x[16] & 1023) as usize;
+        for k in 0..32 {
+            x[k] ^= v[j][k];
+        }
+        block_mix(&mut x);
+    }
+
+    LittleEndian::write_u32_into(&x, &mut bytes);
+    let mut ret = [0u8; 32];
+    pbkdf2_sha256(input, &bytes, &mut ret);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::decode as hex_decode;
+
+    use super::scrypt_1024_1_1_256;
+
+    fn scrypt_hex(input: &str) -> String {
+        let mut hash = scrypt_1024_1_1_256(&hex_decode(input).unwrap());
+        // shown byte-reversed, like block hashes
+        hash.reverse();
+        ::hex::encode(&hash[..])
+    }
+
+    #[test]
+    fn test_scrypt_headers() {
+        // Litecoin genesis block
+        assert_eq!(
+            scrypt_hex("010000000000000000000000000000000000000000000000000000000000000000000000d9ced4ed1130f7b7faad9be25323ffafa33232a17c3edf6cfd97bee6bafbdd97b9aa8e4ef0ff0f1ecd513f7c"),
+            "0000050c34a64b415b6b15b37f2216634b5b1669cb9a2e38d76f7213b0671e00"
+        );
+        // Viacoin genesis block
+        assert_eq!(
+            scrypt_hex("01000000000000000000000000000000000000000000000000000000000000000000000000522753002939c78659b4fdc6ed56c6b6aacdc7586facf2f6ada2012ed31703e61cc153ffff011ea1473d00"),
+            "000001a16729477595c7247e1b49b4ec93acca8345037177cabbe898ce8a5783"
+        );
+    }
+
+    #[test]
+    fn test_scrypt_short_input() {
+        // inputs other than 80-byte headers work too
+        assert_eq!(::hex::encode(&scrypt_1024_1_1_256(b"")[..]), "b34ab7cd1ce0c308146ab970fa75517bcf20f95c7ed7a34efc0d5f096469b2e1");
+        assert_eq!(::hex::encode(&scrypt_1024_1_1_256(b"abc")[..]), "e652c1c3b7a8cd99d2edc49d4509f545c80e4395765e7225c4dde5d80dd76519");
+    }
+}
@@ -0,0 +1,796 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//     Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Signature hashes
+//!
+//! Computation of the BIP341 taproot signature hash, with the per-transaction
+//! midstate hashes cached so that signing many inputs of a transaction does
+//! not rehash the whole transaction each time, and of the legacy one.
+//! Shortcuts on `Transaction` cover the common key-path case and expose the
+//! legacy signing preimage.
+
+use std::{error, fmt};
+
+use bitcoin_hashes::{sha256, sha256d, Hash};
+
+use blockdata::opcodes;
+use blockdata::script::Script;
+use blockdata::transaction::{SigHashType, Transaction, TxIn, TxOut};
+use consensus::encode::{self, serialize, Encodable};
+use util::hash::tagged_hash_engine;
+
+/// The signature hash flags allowed in taproot signatures
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub enum TapSighashType {
+    /// 0x0: Used when not explicitly specified, commits to everything like `All`
+    Default = 0x00,
+    /// 0x1: Sign all outputs
+    All = 0x01,
+    /// 0x2: Sign no outputs --- anyone can choose the destination
+    None = 0x02,
+    /// 0x3: Sign the output whose index matches this input's index
+    Single = 0x03,
+    /// 0x81: Sign all outputs but only this input
+    AllPlusAnyoneCanPay = 0x81,
+    /// 0x82: Sign no outputs and only this input
+    NonePlusAnyoneCanPay = 0x82,
+    /// 0x83: Sign one output and only this input
+    SinglePlusAnyoneCanPay = 0x83,
+}
+
+impl TapSighashType {
+    /// Reads a taproot sighash flag, returning `None` for undefined values
+    pub fn from_u8(n: u8) -> Option<TapSighashType> {
+        match n {
+            0x00 => Some(TapSighashType::Default),
+            0x01 => Some(TapSighashType::All),
+            0x02 => Some(TapSighashType::None),
+            0x03 => Some(TapSighashType::Single),
+            0x81 => Some(TapSighashType::AllPlusAnyoneCanPay),
+            0x82 => Some(TapSighashType::NonePlusAnyoneCanPay),
+            0x83 => Some(TapSighashType::SinglePlusAnyoneCanPay),
+            _ => None,
+        }
+    }
+
+    /// Converts to a u8
+    pub fn as_u8(&self) -> u8 { *self as u8 }
+
+    /// Whether only the input being signed is committed to
+    fn anyone_can_pay(&self) -> bool { self.as_u8() & 0x80 != 0 }
+
+    /// The output commitment mode, with `Default` treated as `All`
+    fn output_type(&self) -> u8 {
+        match *self {
+            TapSighashType::Default => 0x01,
+            other => other.as_u8() & 0x03,
+        }
+    }
+}
+
+/// The outputs spent by a transaction, which taproot signatures commit to
+#[derive(Copy, Clone, Debug)]
+pub enum Prevouts<'a> {
+    /// The outputs spent by every input, in input order. Required unless
+    /// signing with an `AnyoneCanPay` flag.
+    All(&'a [TxOut]),
+    /// Only the output spent by the input at the given index, which suffices
+    /// for `AnyoneCanPay` flags
+    One(usize, &'a TxOut),
+}
+
+/// An error computing a signature hash
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The input index (first) is out of bounds for a transaction with this many inputs (second)
+    IndexOutOfInputsBounds(usize, usize),
+    /// `Single` was used on an input with no output at the same index
+    SingleWithoutCorrespondingOutput(usize),
+    /// `Prevouts::All` did not contain one output per input
+    PrevoutsSize,
+    /// `Prevouts::One` was given for an input other than the one being signed
+    PrevoutsIndex,
+    /// `Prevouts::One` was given but the sighash flag commits to all prevouts
+    PrevoutsKind,
+    /// The annex was empty or did not start with 0x50
+    InvalidAnnex,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::IndexOutOfInputsBounds(idx, len) => write!(f, "input index {} out of bounds for {} inputs", idx, len),
+            Error::SingleWithoutCorrespondingOutput(idx) => write!(f, "SIGHASH_SINGLE on input {} with no corresponding output", idx),
+            Error::PrevoutsSize | Error::PrevoutsIndex | Error::PrevoutsKind | Error::InvalidAnnex =>
+                f.write_str(error::Error::description(self)),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> { None }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::IndexOutOfInputsBounds(..) => "input index out of bounds",
+            Error::SingleWithoutCorrespondingOutput(_) => "SIGHASH_SINGLE without corresponding output",
+            Error::PrevoutsSize => "number of prevouts does not match number of inputs",
+            Error::PrevoutsIndex => "single prevout given for a different input",
+            Error::PrevoutsKind => "single prevout given without ANYONECANPAY",
+            Error::InvalidAnnex => "annex must be non-empty and start with 0x50",
+        }
+    }
+}
+
+/// The single-SHA256 midstates shared by all taproot signatures of a transaction
+#[derive(Clone, Debug)]
+struct TaprootCache {
+    prevouts: sha256::Hash,
+    sequences: sha256::Hash,
+    outputs: sha256::Hash,
+}
+
+/// The midstates which depend on the prevouts as well as the transaction
+#[derive(Clone, Debug)]
+struct TaprootPrevoutsCache {
+    amounts: sha256::Hash,
+    script_pubkeys: sha256::Hash,
+}
+
+/// Computes signature hashes for the inputs of a transaction, caching the
+/// intermediate hashes which are common to all of them
+pub struct SighashCache<'a> {
+    tx: &'a Transaction,
+    taproot: Option<TaprootCache>,
+    // kept with the prevouts they were computed from, which later calls may change
+    taproot_prevouts: Option<(Vec<TxOut>, TaprootPrevoutsCache)>,
+}
+
+impl<'a> SighashCache<'a> {
+    /// Creates a cache for computing signature hashes of `tx`
+    pub fn new(tx: &'a Transaction) -> SighashCache<'a> {
+        SighashCache {
+            tx: tx,
+            taproot: None,
+            taproot_prevouts: None,
+        }
+    }
+
+    /// Computes the BIP341 signature hash of the input at `input_index`.
+    ///
+    /// For a key-path spend `leaf_hash_code_separator` is `None`; for a
+    /// script-path spend it holds the tapleaf hash of the executed script and
+    /// the opcode position of the last executed `OP_CODESEPARATOR`, or
+    /// `0xFFFFFFFF` if there was none. `annex` is the annex from the witness,
+    /// including its 0x50 prefix, if there is one.
+    pub fn taproot_signature_hash(
+        &mut self,
+        input_index: usize,
+        prevouts: &Prevouts,
+        annex: Option<&[u8]>,
+        leaf_hash_code_separator: Option<(sha256::Hash, u32)>,
+        sighash_type: TapSighashType,
+    ) -> Result<sha256::Hash, Error> {
+        let mut enc = tagged_hash_engine("TapSighash");
+        self.encode_taproot_signing_data(&mut enc, input_index, prevouts, annex, leaf_hash_code_separator, sighash_type)
+            .map_err(|e| match e {
+                TaprootEncodeError::Sighash(e) => e,
+                TaprootEncodeError::Io => unreachable!("hash engines don't error"),
+            })?;
+        Ok(sha256::Hash::from_engine(enc))
+    }
+
+    /// Computes the legacy, pre-segwit signature hash of the input at
+    /// `input_index`, with `sighash_type` as the u32 flag being signed.
+    ///
+    /// `script_code` is the script being executed from just after the last
+    /// `OP_CODESEPARATOR` executed before the signature check, or all of it if
+    /// none was. Choosing that point requires evaluating the script, so the
+    /// caller must trim it. Any `OP_CODESEPARATOR`s left in `script_code`,
+    /// such as unexecuted ones, are removed from it before hashing, as
+    /// consensus requires.
+    pub fn legacy_signature_hash(&self, input_index: usize, script_code: &Script, sighash_type: u32) -> Result<sha256d::Hash, Error> {
+        if input_index >= self.tx.input.len() {
+            return Err(Error::IndexOutOfInputsBounds(input_index, self.tx.input.len()));
+        }
+        Ok(self.tx.signature_hash(input_index, &remove_code_separators(script_code), sighash_type))
+    }
+
+    fn encode_taproot_signing_data<W: ::std::io::Write>(
+        &mut self,
+        mut writer: W,
+        input_index: usize,
+        prevouts: &Prevouts,
+        annex: Option<&[u8]>,
+        leaf_hash_code_separator: Option<(sha256::Hash, u32)>,
+        sighash_type: TapSighashType,
+    ) -> Result<(), TaprootEncodeError> {
+        if input_index >= self.tx.input.len() {
+            return Err(Error::IndexOutOfInputsBounds(input_index, self.tx.input.len()).into());
+        }
+        if let Some(annex) = annex {
+            if annex.is_empty() || annex[0] != 0x50 {
+                return Err(Error::InvalidAnnex.into());
+            }
+        }
+        let anyone_can_pay = sighash_type.anyone_can_pay();
+        let output_type = sighash_type.output_type();
+        let spent = match *prevouts {
+            Prevouts::All(outs) => {
+                if outs.len() != self.tx.input.len() {
+                    return Err(Error::PrevoutsSize.into());
+                }
+                &outs[input_index]
+            }
+            Prevouts::One(idx, out) => {
+                if !anyone_can_pay {
+                    return Err(Error::PrevoutsKind.into());
+                }
+                if idx != input_index {
+                    return Err(Error::PrevoutsIndex.into());
+                }
+                out
+            }
+        };
+        if output_type == 0x03 && input_index >= self.tx.output.len() {
+            return Err(Error::SingleWithoutCorrespondingOutput(input_index).into());
+        }
+
+        // epoch
+        0u8.consensus_encode(&mut writer)?;
+
+        // hash type, version and lock time
+        sighash_type.as_u8().consensus_encode(&mut writer)?;
+        self.tx.version.consensus_encode(&mut writer)?;
+        self.tx.lock_time.consensus_encode(&mut writer)?;
+
+        if !anyone_can_pay {
+            let common = self.taproot_cache();
+            let outs = match *prevouts {
+                Prevouts::All(outs) => outs,
+                Prevouts::One(..) => unreachable!("checked above"),
+            };
+            let by_prevouts = self.taproot_prevouts_cache(outs);
+            common.prevouts.consensus_encode(&mut writer)?;
+            by_prevouts.amounts.consensus_encode(&mut writer)?;
+            by_prevouts.script_pubkeys.consensus_encode(&mut writer)?;
+            common.sequences.consensus_encode(&mut writer)?;
+        }
+        if output_type != 0x02 && output_type != 0x03 {
+            self.taproot_cache().outputs.consensus_encode(&mut writer)?;
+        }
+
+        // spend type: the extension flag for script paths, plus whether there is an annex
+        let mut spend_type = 0u8;
+        if leaf_hash_code_separator.is_some() {
+            spend_type |= 2;
+        }
+        if annex.is_some() {
+            spend_type |= 1;
+        }
+        spend_type.consensus_encode(&mut writer)?;
+
+        if anyone_can_pay {
+            let txin = &self.tx.input[input_index];
+            txin.previous_output.consensus_encode(&mut writer)?;
+            spent.value.consensus_encode(&mut writer)?;
+            spent.script_pubkey.consensus_encode(&mut writer)?;
+            txin.sequence.consensus_encode(&mut writer)?;
+        } else {
+            (input_index as u32).consensus_encode(&mut writer)?;
+        }
+
+        if let Some(annex) = annex {
+            let mut enc = sha256::Hash::engine();
+            annex.to_vec().consensus_encode(&mut enc)?;
+            sha256::Hash::from_engine(enc).consensus_encode(&mut writer)?;
+        }
+
+        if output_type == 0x03 {
+            let mut enc = sha256::Hash::engine();
+            self.tx.output[input_index].consensus_encode(&mut enc)?;
+            sha256::Hash::from_engine(enc).consensus_encode(&mut writer)?;
+        }
+
+        if let Some((leaf_hash, code_separator_pos)) = leaf_hash_code_separator {
+            leaf_hash.consensus_encode(&mut writer)?;
+            // key version
+            0u8.consensus_encode(&mut writer)?;
+            code_separator_pos.consensus_encode(&mut writer)?;
+        }
+        Ok(())
+    }
+
+    fn taproot_cache(&mut self) -> TaprootCache {
+        if let Some(ref cache) = self.taproot {
+            return cache.clone();
+        }
+
+        let mut prevouts = sha256::Hash::engine();
+        let mut sequences = sha256::Hash::engine();
+        for txin in &self.tx.input {
+            txin.previous_output.consensus_encode(&mut prevouts).unwrap();
+            txin.sequence.consensus_encode(&mut sequences).unwrap();
+        }
+        let mut outputs = sha256::Hash::engine();
+        for txout in &self.tx.output {
+            txout.consensus_encode(&mut outputs).unwrap();
+        }
+
+        let cache = TaprootCache {
+            prevouts: sha256::Hash::from_engine(prevouts),
+            sequences: sha256::Hash::from_engine(sequences),
+            outputs: sha256::Hash::from_engine(outputs),
+        };
+        self.taproot = Some(cache.clone());
+        cache
+    }
+
+    fn taproot_prevouts_cache(&mut self, prevouts: &[TxOut]) -> TaprootPrevoutsCache {
+        if let Some((ref cached, ref cache)) = self.taproot_prevouts {
+            if cached[..] == prevouts[..] {
+                return cache.clone();
+            }
+        }
+
+        let mut amounts = sha256::Hash::engine();
+        let mut script_pubkeys = sha256::Hash::engine();
+        for txout in prevouts {
+            txout.value.consensus_encode(&mut amounts).unwrap();
+            txout.script_pubkey.consensus_encode(&mut script_pubkeys).unwrap();
+        }
+
+        let cache = TaprootPrevoutsCache {
+            amounts: sha256::Hash::from_engine(amounts),
+            script_pubkeys: sha256::Hash::from_engine(script_pubkeys),
+        };
+        self.taproot_prevouts = Some((prevouts.to_vec(), cache.clone()));
+        cache
+    }
+}
+
+impl Transaction {
+    /// Computes the BIP341 signature hash for a key-path spend of the input at
+    /// `input_index`, given the outputs spent by all inputs in input order.
+    /// For script-path spends, annexes or a single prevout use `SighashCache`.
+    pub fn taproot_key_spend_sighash(
+        &self,
+        input_index: usize,
+        prevouts: &[TxOut],
+        sighash_type: TapSighashType,
+    ) -> Result<sha256::Hash, Error> {
+        SighashCache::new(self).taproot_signature_hash(input_index, &Prevouts::All(prevouts), None, None, sighash_type)
+    }
+
+    /// The bytes whose double-SHA256 is the legacy signature hash of the input
+    /// at `input_index`, as `signature_hash` computes it: the transaction with
+    /// inputs and outputs blanked as `sighash_type` says, then the flag.
+    /// `script_code` is used as given. `SIGHASH_SINGLE` on an input with no
+    /// output at its index hashes to a constant instead, so has no preimage.
+    pub fn serialize_for_signature(&self, input_index: usize, script_code: &Script, sighash_type: u32) -> Result<Vec<u8>, Error> {
+        if input_index >= self.input.len() {
+            return Err(Error::IndexOutOfInputsBounds(input_index, self.input.len()));
+        }
+        let (sighash, anyone_can_pay) = SigHashType::from_u32(sighash_type).split_anyonecanpay_flag();
+        if sighash == SigHashType::Single && input_index >= self.output.len() {
+            return Err(Error::SingleWithoutCorrespondingOutput(input_index));
+        }
+
+        // Build tx to sign
+        let mut tx = Transaction {
+            version: self.version,
+            lock_time: self.lock_time,
+            input: vec![],
+            output: vec![],
+        };
+        // Add all inputs necessary..
+        if anyone_can_pay {
+            tx.input = vec![TxIn {
+                previous_output: self.input[input_index].previous_output,
+                script_sig: script_code.clone(),
+                sequence: self.input[input_index].sequence,
+                witness: vec![],
+            }];
+        } else {
+            tx.input = Vec::with_capacity(self.input.len());
+            for (n, input) in self.input.iter().enumerate() {
+                tx.input.push(TxIn {
+                    previous_output: input.previous_output,
+                    script_sig: if n == input_index { script_code.clone() } else { Script::new() },
+                    sequence: if n != input_index && (sighash == SigHashType::Single || sighash == SigHashType::None) { 0 } else { input.sequence },
+                    witness: vec![],
+                });
+            }
+        }
+        // ..then all outputs
+        tx.output = match sighash {
+            SigHashType::All => self.output.clone(),
+            SigHashType::Single => {
+                let output_iter = self.output.iter()
+                                      .take(input_index + 1)  // sign all outputs up to and including this one, but erase
+                                      .enumerate()            // all of them except for this one
+                                      .map(|(n, out)| if n == input_index { out.clone() } else { TxOut::default() });
+                output_iter.collect()
+            }
+            SigHashType::None => vec![],
+            _ => unreachable!()
+        };
+        let mut ret = serialize(&tx);
+        ret.extend(serialize(&sighash_type));
+        Ok(ret)
+    }
+}
+
+/// Drops every `OP_CODESEPARATOR` opcode from a script, keeping the bytes of
+/// pushes as they are. A truncated push at the end is kept as it is too.
+fn remove_code_separators(script: &Script) -> Script {
+    let bytes = &script[..];
+    let mut ret = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let opcode = bytes[index];
+        let (header, data) = match opcode {
+            0x01...0x4b => (1, opcode as usize),
+            0x4c if index + 2 <= bytes.len() => (2, bytes[index + 1] as usize),
+            0x4d if index + 3 <= bytes.len() => (3, bytes[index + 1] as usize | (bytes[index + 2] as usize) << 8),
+            0x4e if index + 5 <= bytes.len() => (5, bytes[index + 1] as usize | (bytes[index + 2] as usize) << 8 |
+                                                    (bytes[index + 3] as usize) << 16 | (bytes[index + 4] as usize) << 24),
+            0x4c...0x4e => break,
+            _ => (1, 0),
+        };
+        if index + header + data > bytes.len() {
+            break;
+        }
+        if opcode != opcodes::all::OP_CODESEPARATOR.into_u8() {
+            ret.extend_from_slice(&bytes[index..index + header + data]);
+        }
+        index += header + data;
+    }
+    ret.extend_from_slice(&bytes[index..]);
+    Script::from(ret)
+}
+
+/// An error while writing the signing data: either a problem with the inputs
+/// or an I/O error from the writer
+enum TaprootEncodeError {
+    Sighash(Error),
+    Io,
+}
+
+impl From<Error> for TaprootEncodeError {
+    fn from(e: Error) -> TaprootEncodeError {
+        TaprootEncodeError::Sighash(e)
+    }
+}
+
+impl From<encode::Error> for TaprootEncodeError {
+    fn from(_: encode::Error) -> TaprootEncodeError {
+        TaprootEncodeError::Io
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin_hashes::hex::FromHex;
+    use bitcoin_hashes::{sha256, sha256d};
+
+    use blockdata::script::Script;
+    use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+    use consensus::encode::{deserialize, serialize};
+    use util::hash::tagged_hash;
+    use util::misc::hex_bytes;
+    use super::*;
+
+    fn test_tx() -> (Transaction, Vec<TxOut>) {
+        let txin = |txid: &str, vout, sequence| TxIn {
+            previous_output: OutPoint {
+                txid: sha256d::Hash::from_hex(txid).unwrap(),
+                vout: vout,
+            },
+            script_sig: Script::new(),
+            sequence: sequence,
+            witness: vec![],
+        };
+        let txout = |value, script: &str| TxOut {
+            value: value,
+            script_pubkey: hex_script!(script),
+        };
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0x65cd1d,
+            input: vec![
+                txin("c6208ac8f416d6c8475039a08b40cc9e1159fc4d6ce084f5b9310b6f29b3a41c", 1, 0xfffffffd),
+                txin("ecbdb440b893da85c83d2537faa5fa56214ce3d10b1102be5266d00615c5da87", 0, 0xffffffff),
+            ],
+            output: vec![
+                txout(100_000, "0014343f0373c7089594aae43affb10dc1ba4cfcb41f"),
+                txout(50_000, "51206776128287ef2cb8ead79828e5b76f6eefbd55378c32a6f8a594785c5e0e5997"),
+            ],
+        };
+        let prevouts = vec![
+            txout(200_000, "512056850935935bdc968f2ec066f067935cd6c2ef1629ed99a202a4c53a5ca497fc"),
+            txout(120_000, "512025867795d9bf227480e912b0498fd5ba3e00403bf6543a3039404b0b610117f2"),
+        ];
+        (tx, prevouts)
+    }
+
+    // The unsigned transaction and spent outputs of the keyPathSpending test
+    // in the BIP341 wallet test vectors
+    fn bip341_tx() -> (Transaction, Vec<TxOut>) {
+        let tx: Transaction = deserialize(&hex_bytes("02000000097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f3b334e9c010000000000000000d7b7cab57b1393ace2d064f4d4a2cb8af6def61273e127517d44759b6dafdd990000000000fffffffff8e1f583384333689228c5d28eac13366be082dc57441760d957275419a418420000000000fffffffff0689180aa63b30cb162a73c6d2a38b7eeda2a83ece74310fda0843ad604853b0100000000feffffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e5bfd3c5a52ee1239e0ba6c0000000000feffffff956149bdc66faa968eb2be2d2faa29718acbfe3941215893a2a3446d32acd050000000000000000000e664b9773b88c09c32cb70a2a3e4da0ced63b7ba3b22f848531bbb1d5d5f4c94010000000000000000e9aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4eabf0000000000ffffffffa778eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b82af10100000000ffffffff0200ca9a3b000000001976a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac807840cb0000000020ac9a87f5594be208f8532db38cff670c450ed2fea8fcdefcc9a663f78bab962b0065cd1d").unwrap()).unwrap();
+        let txout = |value, script: &str| TxOut {
+            value: value,
+            script_pubkey: hex_script!(script),
+        };
+        let prevouts = vec![
+            txout(420000000, "512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343"),
+            txout(462000000, "5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3"),
+            txout(294000000, "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac"),
+            txout(504000000, "5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e"),
+            txout(630000000, "512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605"),
+            txout(378000000, "00147dd65592d0ab2fe0d0257d571abf032cd9db93dc"),
+            txout(672000000, "512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831"),
+            txout(546000000, "5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5"),
+            txout(588000000, "512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220"),
+        ];
+        (tx, prevouts)
+    }
+
+    // The sighash types and expected hashes of the key-path inputs of the
+    // same test
+    const BIP341_KEY_PATH: [(usize, TapSighashType, &str); 7] = [
+        (0, TapSighashType::Single, "2514a6272f85cfa0f45eb907fcb0d121b808ed37c6ea160a5a9046ed5526d555"),
+        (1, TapSighashType::SinglePlusAnyoneCanPay, "325a644af47e8a5a2591cda0ab0723978537318f10e6a63d4eed783b96a71a4d"),
+        (3, TapSighashType::All, "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669"),
+        (4, TapSighashType::Default, "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef"),
+        (6, TapSighashType::None, "15f25c298eb5cdc7eb1d638dd2d45c97c4c59dcaec6679cfc16ad84f30876b85"),
+        (7, TapSighashType::NonePlusAnyoneCanPay, "cd292de50313804dabe4685e83f923d2969577191a3e1d2882220dca88cbeb10"),
+        (8, TapSighashType::AllPlusAnyoneCanPay, "cccb739eca6c13a8a89e6e5cd317ffe55669bbda23f2fd37b0f18755e008edd2"),
+    ];
+
+    #[test]
+    fn test_taproot_sighash() {
+        let (tx, prevouts) = bip341_tx();
+        let all = Prevouts::All(&prevouts);
+        let mut cache = SighashCache::new(&tx);
+
+        for &(idx, ty, expected) in &BIP341_KEY_PATH {
+            let expected = sha256::Hash::from_hex(expected).unwrap();
+            assert_eq!(cache.taproot_signature_hash(idx, &all, None, None, ty).unwrap(), expected);
+            if ty.anyone_can_pay() {
+                let one = Prevouts::One(idx, &prevouts[idx]);
+                assert_eq!(cache.taproot_signature_hash(idx, &one, None, None, ty).unwrap(), expected);
+            }
+        }
+
+        // The vectors only cover key-path spends without an annex, and none
+        // of Core's vectors below has an OP_CODESEPARATOR. The extension of
+        // the message for a script path, an annex and a code separator is
+        // checked against the key-path message of input 4, which the vector
+        // pins.
+        let signing_data = |cache: &mut SighashCache, annex, leaf| {
+            let mut data = vec![];
+            assert!(cache.encode_taproot_signing_data(&mut data, 4, &all, annex, leaf, TapSighashType::Default).is_ok());
+            data
+        };
+        let key_path = signing_data(&mut cache, None, None);
+        assert_eq!(tagged_hash("TapSighash", &key_path), sha256::Hash::from_hex(BIP341_KEY_PATH[3].2).unwrap());
+        // epoch, hash type, version, lock time and five hashes, then the
+        // spend type and input index
+        let (head, tail) = key_path.split_at(1 + 1 + 4 + 4 + 5 * 32);
+        assert_eq!(tail, &[0, 4, 0, 0, 0]);
+
+        // the tapleaf of the single-leaf scriptPubKey vector of BIP341
+        let script = hex_script!("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac");
+        let mut leaf_data = vec![0xc0];
+        leaf_data.extend(serialize(&script));
+        let leaf = tagged_hash("TapLeaf", &leaf_data);
+        assert_eq!(leaf, sha256::Hash::from_hex("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21").unwrap());
+        let annex = [0x50, 0x01, 0x02];
+        let annex_hash = sha256::Hash::hash(&[0x03, 0x50, 0x01, 0x02]);
+
+        for &(annex, code_separator) in &[(None, 0xFFFFFFFF), (Some(&annex[..]), 0xFFFFFFFF), (None, 1), (Some(&annex[..]), 1)] {
+            for &script_path in &[false, true] {
+                let leaf = if script_path { Some((leaf, code_separator)) } else { None };
+                let mut expected = head.to_vec();
+                expected.push(if script_path { 2 } else { 0 } | if annex.is_some() { 1 } else { 0 });
+                expected.extend(&tail[1..]);
+                if annex.is_some() {
+                    expected.extend(&annex_hash[..]);
+                }
+                if let Some((leaf, code_separator)) = leaf {
+                    expected.extend(&leaf[..]);
+                    expected.push(0);
+                    expected.extend(&serialize(&code_separator));
+                }
+                assert_eq!(signing_data(&mut cache, annex, leaf), expected);
+                assert_eq!(cache.taproot_signature_hash(4, &all, annex, leaf, TapSighashType::Default).unwrap(),
+                           tagged_hash("TapSighash", &expected));
+            }
+        }
+    }
+
+    // Transactions, spent outputs and signature hashes from the taproot
+    // tests of Bitcoin Core's functional test framework: the input index,
+    // sighash type, annex and tapleaf script, if any, and the expected hash
+    type CoreTaprootSighash = (&'static str, &'static str, usize, TapSighashType, Option<&'static str>, Option<&'static str>, &'static str);
+    const CORE_TAPROOT_SIGHASHES: [CoreTaprootSighash; 7] = [
+        ("020000000164eb050a5e3da0c2a65e4786f26d753b7bc69691fabccafb11f7acef36641f1846010000003101b2b404392a22000000000017a9147f2bde86fe78bf68a0544a4f290e12f0b7e0a08c87580200000000000017a91425d11723074ecfb96a0a83c3956bfaf362ae0c908758020000000000001600147e20f938993641de67bb0cdd71682aa34c4d29ad5802000000000000160014c64984dc8761acfa99418bd6bedc79b9287d652d72000000",
+         "01365724000000000023542156b39dab4f8f3508e0432cfb41fab110170acaa2d4c42539cb90a4dc7c093bc500",
+         0, TapSighashType::Default, None, None, "33ca0ebfb4a945eeee9569fc0f5040221275f88690b7f8592ada88ce3bdf6703"),
+        ("0200000002fff49be59befe7566050737910f6ccdc5e749c7f8860ddc140386463d88c5ad0f3000000002cf68eb4a3d67f9d4c079249f7e4f27b8854815cb1ed13842d4fbf395f9e217fd605ee24090100000065235d9203f458520000000000160014b6d48333bb13b4c644e57c43a9a26df3a44b785e58020000000000001976a914eea9461a9e1e3f765d3af3e726162e0229fe3eb688ac58020000000000001976a9143a8869c9f2b5ea1d4ff3aeeb6a8fb2fffb1ad5fe88ac0ad7125c",
+         "02591f220000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea158274806ff4ece48fb310000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea158274806ff4ece",
+         1, TapSighashType::All, None, None, "626ab955d58c9a8a600a0c580549d06dc7da4e802eb2a531f62a588e430967a8"),
+        ("0200000001350005f65aa830ced2079df348e2d8c2bdb4f10e2dde6a161d8a07b40d1ad87dae000000001611d0d603d9dc0e000000000017a914459b6d7d6bbb4d8837b4bf7e9a4556f952da2f5c8758020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88ac58020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88aca71c1f4f",
+         "01c4811000000000002251201bf9297d0a2968ae6693aadd0fa514717afefd218087a239afb7418e2d22e65c",
+         0, TapSighashType::AllPlusAnyoneCanPay, None, None, "dfa9437f9c9a1d1f9af271f79f2f5482f287cdb0d2e03fa92c8a9b216cc6061c"),
+        ("020000000185bed1a6da2bffbd60ec681a1bfb71c5111d6395b99b3f8b2bf90167111bcb18f5010000007c83ace802ded24a00000000001600142c4698f9f7a773866879755aa78c516fb332af8e5802000000000000160014d38639dfbac4259323b98a472405db0c461b31fa61073747",
+         "0144c84d0000000000225120e3f2107989c88e67296ab2faca930efa2e3a5bd3ff0904835a11c9e807458621",
+         0, TapSighashType::None, None, None, "3129de36a5d05fff97ffca31eb75fcccbbbc27b3147a7a36a9e4b45d8b625067"),
+        ("eb93dbb901028c8515589dac980b6e7f8e4088b77ed866ca0d6d210a7218b6fd0f6b22dd6d7300000000eb4740a9047efc0e0000000000160014913da2128d8fcf292b3691db0e187414aa1783825802000000000000160014913da2128d8fcf292b3691db0e187414aa178382580200000000000017a9143dd27f01c6f7ef9bb9159937b17f17065ed01a0c875802000000000000160014d7630e19df70ada9905ede1722b800c0005f246641000000",
+         "013fed110000000000225120eb536ae8c33580290630fc495046e998086a64f8f33b93b07967d9029b265c55",
+         0, TapSighashType::NonePlusAnyoneCanPay, None, None, "2441e8b0e063a2083ee790f14f2045022f07258ddde5ee01de543c9e789d80ae"),
+        ("0200000001df8123752e8f37d132c4e9f1ff7e4f9b986ade9211267e9ebd5fd22a5e718dec6d01000000ce4023b903cb7b23000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787580200000000000017a914afd0d512a2c5c2b40e25669e9cc460303c325b8b87580200000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787f6020000",
+         "01ea49260000000000225120ab5e9800806bf18cb246edcf5fe63441208fe955a4b5a35bbff65f5db622a010",
+         0, TapSighashType::SinglePlusAnyoneCanPay,
+         Some("507b979802e62d397acb29f56743a791894b99372872fc5af06a4f6e8d242d0615cda53062bb20e6ec79756fe39183f0c128adfe85559a8fa042b042c018aa8010143799e44f0893c40e1e"),
+         None, "3b003000add359a364a156e73e02846782a59d0d95ca8c4638aaad99f2ef915c"),
+        ("020000000189fc651483f9296b906455dd939813bf086b1bbe7c77635e157c8e14ae29062195010000004445b5c7044561320000000000160014331414dbdada7fb578f700f38fb69995fc9b5ab958020000000000001976a914268db0a8104cc6d8afd91233cc8b3d1ace8ac3ef88ac580200000000000017a914ec00dcb368d6a693e11986d265f659d2f59e8be2875802000000000000160014c715799a49a0bae3956df9c17cb4440a673ac0df6f010000",
+         "011bec34000000000022512028055142ea437db73382e991861446040b61dd2185c4891d7daf6893d79f7182",
+         0, TapSighashType::All, None, Some("20cc4e1107aea1d170c5ff5b6817e1303010049724fb3caa7941792ea9d29b3e2bacab"),
+         "d66de5274a60400c7b08c86ba6b7f198f40660079edf53aca89d2a9501317f2e"),
+    ];
+
+    #[test]
+    fn test_taproot_sighash_core_vectors() {
+        for &(tx, prevouts, idx, ty, annex, script, expected) in &CORE_TAPROOT_SIGHASHES {
+            let tx: Transaction = deserialize(&hex_bytes(tx).unwrap()).unwrap();
+            let prevouts: Vec<TxOut> = deserialize(&hex_bytes(prevouts).unwrap()).unwrap();
+            let annex = annex.map(|annex| hex_bytes(annex).unwrap());
+            // none of the scripts has an OP_CODESEPARATOR
+            let leaf = script.map(|script| {
+                let mut leaf_data = vec![0xc0];
+                leaf_data.extend(serialize(&hex_script!(script)));
+                (tagged_hash("TapLeaf", &leaf_data), 0xFFFFFFFF)
+            });
+            let hash = SighashCache::new(&tx)
+                .taproot_signature_hash(idx, &Prevouts::All(&prevouts), annex.as_ref().map(|a| &a[..]), leaf, ty)
+                .unwrap();
+            assert_eq!(hash, sha256::Hash::from_hex(expected).unwrap());
+        }
+
+        // the tapleaf hash of the script-path vector
+        let (_, _, _, _, _, script, _) = CORE_TAPROOT_SIGHASHES[6];
+        let mut leaf_data = vec![0xc0];
+        leaf_data.extend(serialize(&hex_script!(script.unwrap())));
+        assert_eq!(tagged_hash("TapLeaf", &leaf_data),
+                   sha256::Hash::from_hex("15a2530514e399f8b5cf0b3d3112cf5b289eaa3e308ba2071b58392fdc6da68a").unwrap());
+    }
+
+    #[test]
+    fn test_taproot_sighash_prevouts_change() {
+        let (tx, prevouts) = bip341_tx();
+        let mut cache = SighashCache::new(&tx);
+        let expected = sha256::Hash::from_hex(BIP341_KEY_PATH[3].2).unwrap();
+        assert_eq!(cache.taproot_signature_hash(4, &Prevouts::All(&prevouts), None, None, TapSighashType::Default).unwrap(), expected);
+
+        // the same cache with other prevouts hashes those, not the first ones
+        let mut other = prevouts.clone();
+        other[0].value += 1;
+        let hash = cache.taproot_signature_hash(4, &Prevouts::All(&other), None, None, TapSighashType::Default).unwrap();
+        assert!(hash != expected);
+        assert_eq!(hash, SighashCache::new(&tx).taproot_signature_hash(4, &Prevouts::All(&other), None, None, TapSighashType::Default).unwrap());
+        assert_eq!(cache.taproot_signature_hash(4, &Prevouts::All(&prevouts), None, None, TapSighashType::Default).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_taproot_key_spend_sighash() {
+        // the keyPathSpending entries of the BIP341 wallet test vectors
+        let (tx, prevouts) = bip341_tx();
+        for &(idx, ty, expected) in &BIP341_KEY_PATH {
+            assert_eq!(tx.taproot_key_spend_sighash(idx, &prevouts, ty).unwrap(), sha256::Hash::from_hex(expected).unwrap());
+        }
+        assert_eq!(tx.taproot_key_spend_sighash(0, &prevouts[..1], TapSighashType::Default), Err(Error::PrevoutsSize));
+        assert_eq!(tx.taproot_key_spend_sighash(9, &prevouts, TapSighashType::Default), Err(Error::IndexOutOfInputsBounds(9, 9)));
+    }
+
+    #[test]
+    fn test_serialize_for_signature() {
+        let (tx, _) = test_tx();
+        let script_code = hex_script!("76a9140389035a9225b3839e2bbf32d826a1e222031fd888ac");
+
+        for &ty in &[0x01, 0x02, 0x03, 0x81, 0x82, 0x83, 0x00, 0x3025ac4f] {
+            for idx in 0..2 {
+                let preimage = tx.serialize_for_signature(idx, &script_code, ty).unwrap();
+                assert_eq!(sha256d::Hash::hash(&preimage), tx.signature_hash(idx, &script_code, ty));
+            }
+        }
+
+        // version, one input with our script code, no outputs, lock time, flag
+        let preimage = tx.serialize_for_signature(1, &script_code, 0x82).unwrap();
+        assert_eq!(preimage.len(), 4 + 1 + 36 + 1 + script_code.len() + 4 + 1 + 4 + 4);
+        assert_eq!(&preimage[preimage.len() - 4..], &[0x82, 0, 0, 0]);
+
+        let mut short = tx.clone();
+        short.output.pop();
+        assert_eq!(short.serialize_for_signature(1, &script_code, 0x03), Err(Error::SingleWithoutCorrespondingOutput(1)));
+        assert_eq!(tx.serialize_for_signature(2, &script_code, 0x01), Err(Error::IndexOutOfInputsBounds(2, 2)));
+    }
+
+    #[test]
+    fn test_taproot_sighash_errors() {
+        let (tx, prevouts) = test_tx();
+        let mut cache = SighashCache::new(&tx);
+        let all = Prevouts::All(&prevouts);
+
+        assert_eq!(cache.taproot_signature_hash(2, &all, None, None, TapSighashType::Default),
+                   Err(Error::IndexOutOfInputsBounds(2, 2)));
+        assert_eq!(cache.taproot_signature_hash(0, &all, Some(&[0x51]), None, TapSighashType::Default),
+                   Err(Error::InvalidAnnex));
+        assert_eq!(cache.taproot_signature_hash(0, &all, Some(&[]), None, TapSighashType::Default),
+                   Err(Error::InvalidAnnex));
+        assert_eq!(cache.taproot_signature_hash(0, &Prevouts::All(&prevouts[..1]), None, None, TapSighashType::Default),
+                   Err(Error::PrevoutsSize));
+        assert_eq!(cache.taproot_signature_hash(0, &Prevouts::One(0, &prevouts[0]), None, None, TapSighashType::All),
+                   Err(Error::PrevoutsKind));
+        assert_eq!(cache.taproot_signature_hash(0, &Prevouts::One(1, &prevouts[1]), None, None, TapSighashType::AllPlusAnyoneCanPay),
+                   Err(Error::PrevoutsIndex));
+
+        let mut short = tx.clone();
+        short.output.pop();
+        let mut cache = SighashCache::new(&short);
+        assert_eq!(cache.taproot_signature_hash(1, &all, None, None, TapSighashType::Single),
+                   Err(Error::SingleWithoutCorrespondingOutput(1)));
+    }
+
+    #[test]
+    fn test_legacy_sighash_code_separator() {
+        let (mut tx, _) = test_tx();
+        tx.input[0].script_sig = hex_script!("00");
+        let cache = SighashCache::new(&tx);
+
+        // `<pk> OP_CHECKSIGVERIFY OP_CODESEPARATOR <pk> OP_CHECKSIG`, signed
+        // for the second check, so from just after the executed separator
+        let pk = "21033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52";
+        let script = hex_script!(&format!("{}adab{}ac", pk, pk));
+        let script_code = Script::from(script[36..].to_vec());
+        assert_eq!(script_code, hex_script!(&format!("{}ac", pk)));
+        let hash = cache.legacy_signature_hash(0, &script_code, 1).unwrap();
+        assert_eq!(hash, tx.signature_hash(0, &script_code, 1));
+        assert!(hash != cache.legacy_signature_hash(0, &script, 1).unwrap());
+
+        // the first check signs the whole script, with the separator removed
+        let hash = cache.legacy_signature_hash(0, &script, 1).unwrap();
+        assert_eq!(hash, tx.signature_hash(0, &hex_script!(&format!("{}ad{}ac", pk, pk)), 1));
+
+        // separators inside pushes are data, and stay
+        let script = hex_script!("02abab4c02ababab");
+        assert_eq!(remove_code_separators(&script), hex_script!("02abab4c02abab"));
+        // as does a truncated push
+        assert_eq!(remove_code_separators(&hex_script!("ab4d01")), hex_script!("4d01"));
+        assert_eq!(remove_code_separators(&hex_script!("ab05abab")), hex_script!("05abab"));
+
+        assert_eq!(cache.legacy_signature_hash(2, &script, 1), Err(Error::IndexOutOfInputsBounds(2, 2)));
+    }
+
+    #[test]
+    fn test_tap_sighash_type() {
+        for &n in &[0x00, 0x01, 0x02, 0x03, 0x81, 0x82, 0x83] {
+            assert_eq!(TapSighashType::from_u8(n).unwrap().as_u8(), n);
+        }
+        assert_eq!(TapSighashType::from_u8(0x04), None);
+        assert_eq!(TapSighashType::from_u8(0x80), None);
+    }
+}
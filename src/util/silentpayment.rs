@@ -0,0 +1,146 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Silent payment outputs
+//!
+//! Output keys in the style of BIP352 silent payments: a sender pays a
+//! recipient's published scan and spend keys at a fresh key, tweaked from
+//! the spend key by the ECDH secret of the sender's ephemeral key and the
+//! scan key. The recipient finds the output by repeating the ECDH with the
+//! scan secret. Deriving the ephemeral key from the transaction inputs is
+//! left to the caller.
+
+use byteorder::{BigEndian, ByteOrder};
+use bitcoin_hashes::{sha256, Hash, HashEngine};
+use secp256k1::{self, Secp256k1};
+use secp256k1::key::{PublicKey, SecretKey};
+
+use util::hash::tagged_hash_engine;
+
+/// The ECDH point `sk * pk`, which the sender and recipient both compute
+pub fn shared_secret<C: secp256k1::Verification>(secp: &Secp256k1<C>, sk: &SecretKey, pk: &PublicKey) -> Result<PublicKey, secp256k1::Error> {
+    let mut ret = *pk;
+    ret.mul_assign(secp, &sk[..])?;
+    Ok(ret)
+}
+
+/// The tweak of the `k`th output paying a recipient, from the shared secret
+pub fn output_tweak(shared_secret: &PublicKey, k: u32) -> Result<SecretKey, secp256k1::Error> {
+    let mut engine = tagged_hash_engine("BIP0352/SharedSecret");
+    engine.input(&shared_secret.serialize()[..]);
+    let mut be_k = [0; 4];
+    BigEndian::write_u32(&mut be_k, k);
+    engine.input(&be_k);
+    SecretKey::from_slice(&sha256::Hash::from_engine(engine)[..])
+}
+
+/// The key of the `k`th output a sender with `ephemeral_secret` pays to the
+/// recipient's `scan_key` and `spend_key`
+pub fn sender_output_key<C: secp256k1::Verification>(
+    secp: &Secp256k1<C>,
+    ephemeral_secret: &SecretKey,
+    scan_key: &PublicKey,
+    spend_key: &PublicKey,
+    k: u32,
+) -> Result<PublicKey, secp256k1::Error> {
+    let tweak = output_tweak(&shared_secret(secp, ephemeral_secret, scan_key)?, k)?;
+    let mut ret = *spend_key;
+    ret.add_exp_assign(secp, &tweak[..])?;
+    Ok(ret)
+}
+
+/// The key of the `k`th output paid by the holder of `ephemeral_key`, as the
+/// recipient computes it from their scan secret
+pub fn recipient_output_key<C: secp256k1::Verification>(
+    secp: &Secp256k1<C>,
+    scan_secret: &SecretKey,
+    ephemeral_key: &PublicKey,
+    spend_key: &PublicKey,
+    k: u32,
+) -> Result<PublicKey, secp256k1::Error> {
+    sender_output_key(secp, scan_secret, ephemeral_key, spend_key, k)
+}
+
+/// The secret key the recipient spends the `k`th output paid by the holder
+/// of `ephemeral_key` with
+pub fn recipient_output_secret<C: secp256k1::Verification>(
+    secp: &Secp256k1<C>,
+    scan_secret: &SecretKey,
+    spend_secret: &SecretKey,
+    ephemeral_key: &PublicKey,
+    k: u32,
+) -> Result<SecretKey, secp256k1::Error> {
+    let tweak = output_tweak(&shared_secret(secp, scan_secret, ephemeral_key)?, k)?;
+    let mut ret = *spend_secret;
+    ret.add_assign(&tweak[..])?;
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, XorShiftRng};
+    use secp256k1::Secp256k1;
+    use secp256k1::key::{PublicKey, SecretKey};
+
+    use super::*;
+
+    #[test]
+    fn test_output_keys_match() {
+        let secp = Secp256k1::new();
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        for _ in 0..10 {
+            let ephemeral_secret = SecretKey::new(&mut rng);
+            let scan_secret = SecretKey::new(&mut rng);
+            let spend_secret = SecretKey::new(&mut rng);
+            let ephemeral_key = PublicKey::from_secret_key(&secp, &ephemeral_secret);
+            let scan_key = PublicKey::from_secret_key(&secp, &scan_secret);
+            let spend_key = PublicKey::from_secret_key(&secp, &spend_secret);
+
+            for k in 0..3 {
+                let sent = sender_output_key(&secp, &ephemeral_secret, &scan_key, &spend_key, k).unwrap();
+                let found = recipient_output_key(&secp, &scan_secret, &ephemeral_key, &spend_key, k).unwrap();
+                assert_eq!(sent, found);
+                let secret = recipient_output_secret(&secp, &scan_secret, &spend_secret, &ephemeral_key, k).unwrap();
+                assert_eq!(PublicKey::from_secret_key(&secp, &secret), sent);
+                assert!(sent != spend_key);
+            }
+            assert!(sender_output_key(&secp, &ephemeral_secret, &scan_key, &spend_key, 0).unwrap() !=
+                    sender_output_key(&secp, &ephemeral_secret, &scan_key, &spend_key, 1).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_output_key_spec() {
+        // BIP352's P_k = B_spend + hash_BIP0352/SharedSecret(ser_P(a * B_scan) || ser_32(k)) * G,
+        // assembled from the hash and curve primitives rather than this module
+        let secp = Secp256k1::new();
+        let ephemeral_secret = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let scan_key = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[0x22; 32]).unwrap());
+        let spend_key = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[0x33; 32]).unwrap());
+
+        let mut ecdh = scan_key;
+        ecdh.mul_assign(&secp, &ephemeral_secret[..]).unwrap();
+        let tag = sha256::Hash::hash(b"BIP0352/SharedSecret");
+        for &(k, ser_k) in [(0, [0, 0, 0, 0]), (1, [0, 0, 0, 1]), (256, [0, 0, 1, 0])].iter() {
+            let mut data = Vec::new();
+            data.extend_from_slice(&tag[..]);
+            data.extend_from_slice(&tag[..]);
+            data.extend_from_slice(&ecdh.serialize()[..]);
+            data.extend_from_slice(&ser_k);
+            let tweak = SecretKey::from_slice(&sha256::Hash::hash(&data)[..]).unwrap();
+            let expected = spend_key.combine(&PublicKey::from_secret_key(&secp, &tweak)).unwrap();
+            assert_eq!(sender_output_key(&secp, &ephemeral_secret, &scan_key, &spend_key, k).unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,562 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//   Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Taproot
+//!
+//! BIP341 script trees: leaf and branch hashes, the tweak of the internal
+//! key committing to a tree, and control blocks proving a script is in it,
+//! along with the BIP340 Schnorr signatures that spend its outputs. Keys
+//! are x-only, as 32-byte x coordinates of points with even y.
+
+use std::{error, fmt};
+
+use bitcoin_hashes::{sha256, Hash, HashEngine};
+use secp256k1::{self, Secp256k1, SecretKey};
+
+use blockdata::script::Script;
+use consensus::encode::Encodable;
+use util::hash::{tagged_hash, tagged_hash_engine};
+
+/// The leaf version of BIP342 tapscript
+pub const TAPROOT_LEAF_TAPSCRIPT: u8 = 0xc0;
+/// The size of a control block without any merkle branch
+pub const TAPROOT_CONTROL_BASE_SIZE: usize = 33;
+/// The size of each hash in the merkle branch of a control block
+pub const TAPROOT_CONTROL_NODE_SIZE: usize = 32;
+/// The deepest a script tree may be
+pub const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+
+/// The hash of a leaf or branch of a script tree
+pub type TapNodeHash = sha256::Hash;
+
+/// The order of the secp256k1 group, less one: multiplying by it negates
+const CURVE_ORDER_MINUS_ONE: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x40,
+];
+
+/// An error in taproot key or control block handling
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Error {
+    /// A control block was not 33 bytes plus at most 128 hashes long
+    InvalidControlBlockSize(usize),
+    /// An x-only key was not the x coordinate of any point
+    InvalidXOnlyKey,
+    /// The tweak was not a valid scalar, or tweaking gave the point at infinity
+    InvalidTweak,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidControlBlockSize(ell) => write!(f, "control block length {} invalid", ell),
+            Error::InvalidXOnlyKey | Error::InvalidTweak => f.write_str(error::Error::description(self)),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> { None }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::InvalidControlBlockSize(_) => "invalid control block length",
+            Error::InvalidXOnlyKey => "invalid x-only public key",
+            Error::InvalidTweak => "invalid taproot tweak",
+        }
+    }
+}
+
+/// The hash of a script tree leaf: `TapLeaf` tagged, over the leaf version
+/// and the length-prefixed script
+pub fn tap_leaf_hash(script: &Script, leaf_version: u8) -> TapNodeHash {
+    let mut engine = tagged_hash_engine("TapLeaf");
+    engine.input(&[leaf_version]);
+    script.consensus_encode(&mut engine).expect("engines don't error");
+    sha256::Hash::from_engine(engine)
+}
+
+/// The hash of a script tree branch, whose children are taken in sorted order
+pub fn tap_branch_hash(a: &TapNodeHash, b: &TapNodeHash) -> TapNodeHash {
+    let mut engine = tagged_hash_engine("TapBranch");
+    if a[..] < b[..] {
+        engine.input(&a[..]);
+        engine.input(&b[..]);
+    } else {
+        engine.input(&b[..]);
+        engine.input(&a[..]);
+    }
+    sha256::Hash::from_engine(engine)
+}
+
+/// The scalar an internal key is tweaked by to commit to the merkle root of
+/// its script tree, or to no scripts at all
+pub fn tap_tweak_hash(internal_key: &[u8; 32], merkle_root: Option<TapNodeHash>) -> sha256::Hash {
+    let mut engine = tagged_hash_engine("TapTweak");
+    engine.input(&internal_key[..]);
+    if let Some(root) = merkle_root {
+        engine.input(&root[..]);
+    }
+    sha256::Hash::from_engine(engine)
+}
+
+/// The point with the x-only key as its x coordinate and an even y
+pub fn lift_x(key: &[u8; 32]) -> Result<secp256k1::PublicKey, Error> {
+    let mut compressed = [0x02; 33];
+    compressed[1..].copy_from_slice(&key[..]);
+    secp256k1::PublicKey::from_slice(&compressed).map_err(|_| Error::InvalidXOnlyKey)
+}
+
+/// Tweaks an internal key into the output key committing to `merkle_root`,
+/// returning it with whether its y coordinate is odd
+pub fn tweak_internal_key<C: secp256k1::Verification>(
+    secp: &Secp256k1<C>,
+    internal_key: &[u8; 32],
+    merkle_root: Option<TapNodeHash>,
+) -> Result<([u8; 32], bool), Error> {
+    let mut point = lift_x(internal_key)?;
+    let tweak = tap_tweak_hash(internal_key, merkle_root);
+    point.add_exp_assign(secp, &tweak[..]).map_err(|_| Error::InvalidTweak)?;
+
+    let serialized = point.serialize();
+    let mut output_key = [0; 32];
+    output_key.copy_from_slice(&serialized[1..]);
+    Ok((output_key, serialized[0] == 0x03))
+}
+
+/// A secret key with its x-only public key, for BIP340 signing
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct KeyPair {
+    secret_key: SecretKey,
+    public_key: [u8; 32],
+    parity: bool,
+}
+
+impl KeyPair {
+    /// Computes the x-only public key of a secret key
+    pub fn from_secret_key<C: secp256k1::Signing>(secp: &Secp256k1<C>, secret_key: SecretKey) -> KeyPair {
+        let serialized = secp256k1::PublicKey::from_secret_key(secp, &secret_key).serialize();
+        let mut public_key = [0; 32];
+        public_key.copy_from_slice(&serialized[1..]);
+        KeyPair {
+            secret_key: secret_key,
+            public_key: public_key,
+            parity: serialized[0] == 0x03,
+        }
+    }
+
+    /// The secret key, as given: not negated for an odd public key
+    pub fn secret_key(&self) -> SecretKey {
+        self.secret_key
+    }
+
+    /// The x-only public key
+    pub fn public_key(&self) -> &[u8; 32] {
+        &self.public_key
+    }
+
+    /// Whether the y coordinate of the full public key is odd
+    pub fn parity(&self) -> bool {
+        self.parity
+    }
+
+    /// The secret key of the x-only public key, which has an even y
+    fn even_secret_key(&self) -> SecretKey {
+        let mut ret = self.secret_key;
+        if self.parity {
+            ret.mul_assign(&CURVE_ORDER_MINUS_ONE[..]).expect("nonzero times nonzero");
+        }
+        ret
+    }
+
+    /// Tweaks the keypair, as an internal key, into the keypair of the output
+    /// key committing to `merkle_root`, which signs key path spends
+    pub fn tap_tweak<C: secp256k1::Signing>(&self, secp: &Secp256k1<C>, merkle_root: Option<TapNodeHash>) -> TweakedKeyPair {
+        let tweak = tap_tweak_hash(&self.public_key, merkle_root);
+        let mut secret_key = self.even_secret_key();
+        // fails only if the tweak hash is at least the group order, or the
+        // discrete log of the internal key was found
+        secret_key.add_assign(&tweak[..]).expect("tweak hash in range");
+        TweakedKeyPair(KeyPair::from_secret_key(secp, secret_key))
+    }
+}
+
+/// The keypair of a taproot output key, tweaked from an internal key
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TweakedKeyPair(KeyPair);
+
+impl TweakedKeyPair {
+    /// The tweaked keypair, to sign with
+    pub fn keypair(&self) -> &KeyPair {
+        &self.0
+    }
+
+    /// The x-only output key
+    pub fn output_key(&self) -> &[u8; 32] {
+        &self.0.public_key
+    }
+
+    /// Unwraps the tweaked keypair
+    pub fn into_inner(self) -> KeyPair {
+        self.0
+    }
+}
+
+/// Reduces a hash modulo the group order, which it is almost never above
+fn scalar_from_hash(hash: sha256::Hash) -> Option<SecretKey> {
+    match SecretKey::from_slice(&hash[..]) {
+        Ok(sk) => Some(sk),
+        Err(_) => {
+            // `!(n - 1) = 2^256 - n`, which fits in 129 bits, so
+            // `hash - n = hash + !(n - 1)` wrapping
+            let mut ret = [0; 32];
+            let mut carry = 0u16;
+            for i in (0..32).rev() {
+                let sum = hash[i] as u16 + (!CURVE_ORDER_MINUS_ONE[i]) as u16 + carry;
+                ret[i] = sum as u8;
+                carry = sum >> 8;
+            }
+            SecretKey::from_slice(&ret).ok()
+        }
+    }
+}
+
+fn bip340_challenge(r: &[u8], public_key: &[u8; 32], msg: &[u8; 32]) -> Option<SecretKey> {
+    let mut data = Vec::with_capacity(96);
+    data.extend_from_slice(r);
+    data.extend_from_slice(&public_key[..]);
+    data.extend_from_slice(&msg[..]);
+    scalar_from_hash(tagged_hash("BIP0340/challenge", &data))
+}
+
+/// Signs a 32-byte message with BIP340 Schnorr, using `aux` as the
+/// auxiliary randomness of the nonce
+pub fn sign_schnorr<C: secp256k1::Signing>(secp: &Secp256k1<C>, msg: &[u8; 32], keypair: &KeyPair, aux: &[u8; 32]) -> [u8; 64] {
+    let secret_key = keypair.even_secret_key();
+
+    let aux_hash = tagged_hash("BIP0340/aux", &aux[..]);
+    let mut data = Vec::with_capacity(96);
+    data.extend(secret_key[..].iter().zip(aux_hash[..].iter()).map(|(a, b)| a ^ b));
+    data.extend_from_slice(&keypair.public_key[..]);
+    data.extend_from_slice(&msg[..]);
+    let mut nonce = scalar_from_hash(tagged_hash("BIP0340/nonce", &data)).expect("nonce hash nonzero");
+
+    let r = secp256k1::PublicKey::from_secret_key(secp, &nonce).serialize();
+    if r[0] == 0x03 {
+        nonce.mul_assign(&CURVE_ORDER_MINUS_ONE[..]).expect("nonzero times nonzero");
+    }
+
+    // s = k + e * d
+    let mut s = secret_key;
+    match bip340_challenge(&r[1..], &keypair.public_key, msg) {
+        Some(e) => {
+            s.mul_assign(&e[..]).expect("nonzero times nonzero");
+            s.add_assign(&nonce[..]).expect("s nonzero");
+        }
+        None => s = nonce,
+    }
+
+    let mut ret = [0; 64];
+    ret[..32].copy_from_slice(&r[1..]);
+    ret[32..].copy_from_slice(&s[..]);
+    ret
+}
+
+/// Verifies a BIP340 Schnorr signature of a 32-byte message by an x-only key
+pub fn verify_schnorr<C: secp256k1::Signing + secp256k1::Verification>(secp: &Secp256k1<C>, sig: &[u8; 64], msg: &[u8; 32], public_key: &[u8; 32]) -> bool {
+    let point = match lift_x(public_key) {
+        Ok(point) => point,
+        Err(_) => return false,
+    };
+    // a zero `s` is valid, but isn't a secret key
+    let s = if sig[32..].iter().all(|&b| b == 0) {
+        None
+    } else {
+        match SecretKey::from_slice(&sig[32..]) {
+            Ok(s) => Some(s),
+            Err(_) => return false,
+        }
+    };
+
+    // R = s * G - e * P
+    let neg_ep = match bip340_challenge(&sig[..32], public_key, msg) {
+        Some(mut e) => {
+            e.mul_assign(&CURVE_ORDER_MINUS_ONE[..]).expect("nonzero times nonzero");
+            let mut neg_ep = point;
+            if neg_ep.mul_assign(secp, &e[..]).is_err() {
+                return false;
+            }
+            Some(neg_ep)
+        }
+        None => None,
+    };
+    let r = match (s, neg_ep) {
+        (Some(s), Some(neg_ep)) => match secp256k1::PublicKey::from_secret_key(secp, &s).combine(&neg_ep) {
+            Ok(r) => r,
+            Err(_) => return false,
+        },
+        (Some(s), None) => secp256k1::PublicKey::from_secret_key(secp, &s),
+        (None, Some(neg_ep)) => neg_ep,
+        (None, None) => return false,
+    };
+    let r = r.serialize();
+    r[0] == 0x02 && r[1..] == sig[..32]
+}
+
+/// The control block of a script path spend, proving the script executed is
+/// a leaf of the tree the output key commits to
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ControlBlock {
+    leaf_version: u8,
+    output_key_parity: bool,
+    internal_key: [u8; 32],
+    merkle_branch: Vec<TapNodeHash>,
+}
+
+impl ControlBlock {
+    /// Parses a control block, the last witness element of a script path spend
+    pub fn from_slice(data: &[u8]) -> Result<ControlBlock, Error> {
+        if data.len() < TAPROOT_CONTROL_BASE_SIZE ||
+           (data.len() - TAPROOT_CONTROL_BASE_SIZE) % TAPROOT_CONTROL_NODE_SIZE != 0 ||
+           data.len() > TAPROOT_CONTROL_BASE_SIZE + TAPROOT_CONTROL_NODE_SIZE * TAPROOT_CONTROL_MAX_NODE_COUNT {
+            return Err(Error::InvalidControlBlockSize(data.len()));
+        }
+        let mut internal_key = [0; 32];
+        internal_key.copy_from_slice(&data[1..33]);
+        lift_x(&internal_key)?;
+        Ok(ControlBlock {
+            leaf_version: data[0] & 0xfe,
+            output_key_parity: data[0] & 1 == 1,
+            internal_key: internal_key,
+            merkle_branch: data[33..].chunks(TAPROOT_CONTROL_NODE_SIZE)
+                .map(|node| sha256::Hash::from_slice(node).expect("32 bytes"))
+                .collect(),
+        })
+    }
+
+    /// Serializes the control block
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(TAPROOT_CONTROL_BASE_SIZE + TAPROOT_CONTROL_NODE_SIZE * self.merkle_branch.len());
+        ret.push(self.leaf_version | self.output_key_parity as u8);
+        ret.extend_from_slice(&self.internal_key[..]);
+        for node in &self.merkle_branch {
+            ret.extend_from_slice(&node[..]);
+        }
+        ret
+    }
+
+    /// The leaf version of the script being spent
+    pub fn leaf_version(&self) -> u8 {
+        self.leaf_version
+    }
+
+    /// Whether the y coordinate of the output key is odd
+    pub fn output_key_parity(&self) -> bool {
+        self.output_key_parity
+    }
+
+    /// The x-only internal key
+    pub fn internal_key(&self) -> &[u8; 32] {
+        &self.internal_key
+    }
+
+    /// The hashes from the leaf's sibling up to the root's children
+    pub fn merkle_branch(&self) -> &[TapNodeHash] {
+        &self.merkle_branch
+    }
+
+    /// Checks that the control block proves `script` to be a leaf of the tree
+    /// committed to by the x-only `output_key`
+    pub fn verify<C: secp256k1::Verification>(&self, secp: &Secp256k1<C>, output_key: &[u8; 32], script: &Script) -> bool {
+        let leaf = tap_leaf_hash(script, self.leaf_version);
+        let root = self.merkle_branch.iter().fold(leaf, |node, sibling| tap_branch_hash(&node, sibling));
+        match tweak_internal_key(secp, &self.internal_key, Some(root)) {
+            Ok((key, parity)) => key == *output_key && parity == self.output_key_parity,
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin_hashes::{sha256, Hash};
+    use secp256k1::{Secp256k1, SecretKey};
+
+    use blockdata::script::Script;
+    use util::misc::hex_bytes;
+    use super::*;
+
+    fn key(hex: &str) -> [u8; 32] {
+        let mut ret = [0; 32];
+        ret.copy_from_slice(&hex_bytes(hex).unwrap());
+        ret
+    }
+
+    #[test]
+    fn test_tweak_internal_key() {
+        // key path only, from the BIP341 wallet test vectors
+        let secp = Secp256k1::verification_only();
+        let internal_key = key("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        let (output_key, parity) = tweak_internal_key(&secp, &internal_key, None).unwrap();
+        assert_eq!(output_key, key("53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343"));
+        assert!(parity);
+        assert_eq!(lift_x(&[0; 32]), Err(Error::InvalidXOnlyKey));
+    }
+
+    /// The BIP340 test vectors with 32-byte messages: secret key and aux
+    /// if signable, public key, message, signature and whether it's valid
+    const BIP340_VECTORS: [(&str, &str, &str, &str, &str, bool); 15] = [
+        ("0000000000000000000000000000000000000000000000000000000000000003", "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9", "0000000000000000000000000000000000000000000000000000000000000000", "0000000000000000000000000000000000000000000000000000000000000000",
+         "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0", true),
+        ("b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef", "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "0000000000000000000000000000000000000000000000000000000000000001", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+         "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de33418906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a", true),
+        ("c90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b14e5c9", "dd308afec5777e13121fa72b9cc1b7cc0139715309b086c960e18fd969774eb8", "c87aa53824b4d7ae2eb035a2b5bbbccc080e76cdc6d1692c4b0b62d798e6d906", "7e2d58d8b3bcdf1abadec7829054f90dda9805aab56c77333024b9d0a508b75c",
+         "5831aaeed7b44bb74e5eab94ba9d4294c49bcf2a60728d8b4c200f50dd313c1bab745879a5ad954a72c45a91c3a51d3c7adea98d82f8481e0e1e03674a6f3fb7", true),
+        ("0b432b2677937381aef05bb02a66ecd012773062cf3fa2549e44f58ed2401710", "25d1dff95105f5253c4022f628a996ad3a0d95fbf21d468a1b33f8c160d8f517", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+         "7eb0509757e246f19449885651611cb965ecc1a187dd51b64fda1edc9637d5ec97582b9cb13db3933705b32ba982af5af25fd78881ebb32771fc5922efc66ea3", true),
+        ("", "d69c3509bb99e412e68b0fe8544e72837dfa30746d8be2aa65975f29d22dc7b9", "", "4df3c3f68fcc83b27e9d42c90431a72499f17875c81a599b566c9889b9696703",
+         "00000000000000000000003b78ce563f89a0ed9414f5aa28ad0d96d6795f9c6376afb1548af603b3eb45c9f8207dee1060cb71c04e80f593060b07d28308d7f4", true),
+        ("", "eefdea4cdb677750a420fee807eacf21eb9898ae79b9768766e4faa04a2d4a34", "", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+         "6cff5c3ba86c69ea4b7376f31a9bcb4f74c1976089b2d9963da2e5543e17776969e89b4c5564d00349106b8497785dd7d1d713a8ae82b32fa79d5f7fc407d39b", false),
+        ("", "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+         "fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a14602975563cc27944640ac607cd107ae10923d9ef7a73c643e166be5ebeafa34b1ac553e2", false),
+        ("", "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+         "1fa62e331edbc21c394792d2ab1100a7b432b013df3f6ff4f99fcb33e0e1515f28890b3edb6e7189b630448b515ce4f8622a954cfe545735aaea5134fccdb2bd", false),
+        ("", "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+         "6cff5c3ba86c69ea4b7376f31a9bcb4f74c1976089b2d9963da2e5543e177769961764b3aa9b2ffcb6ef947b6887a226e8d7c93e00c5ed0c1834ff0d0c2e6da6", false),
+        ("", "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+         "0000000000000000000000000000000000000000000000000000000000000000123dda8328af9c23a94c1feecfd123ba4fb73476f0d594dcb65c6425bd186051", false),
+        ("", "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+         "00000000000000000000000000000000000000000000000000000000000000017615fbaf5ae28864013c099742deadb4dba87f11ac6754f93780d5a1837cf197", false),
+        ("", "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+         "4a298dacae57395a15d0795ddbfd1dcb564da82b0f269bc70a74f8220429ba1d69e89b4c5564d00349106b8497785dd7d1d713a8ae82b32fa79d5f7fc407d39b", false),
+        ("", "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+         "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f69e89b4c5564d00349106b8497785dd7d1d713a8ae82b32fa79d5f7fc407d39b", false),
+        ("", "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659", "", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+         "6cff5c3ba86c69ea4b7376f31a9bcb4f74c1976089b2d9963da2e5543e177769fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141", false),
+        ("", "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc30", "", "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+         "6cff5c3ba86c69ea4b7376f31a9bcb4f74c1976089b2d9963da2e5543e17776969e89b4c5564d00349106b8497785dd7d1d713a8ae82b32fa79d5f7fc407d39b", false),
+    ];
+
+    #[test]
+    fn test_schnorr() {
+        let secp = Secp256k1::new();
+        for &(sk, pk, aux, msg, sig, valid) in BIP340_VECTORS.iter() {
+            let (pk, msg) = (key(pk), key(msg));
+            let mut signature = [0; 64];
+            signature.copy_from_slice(&hex_bytes(sig).unwrap());
+            assert_eq!(verify_schnorr(&secp, &signature, &msg, &pk), valid, "{}", sig);
+            if !sk.is_empty() {
+                let keypair = KeyPair::from_secret_key(&secp, SecretKey::from_slice(&key(sk)).unwrap());
+                assert_eq!(*keypair.public_key(), pk);
+                assert_eq!(&sign_schnorr(&secp, &msg, &keypair, &key(aux))[..], &signature[..]);
+            }
+        }
+
+        // a zero `s` gets as far as the challenge, and fails against it
+        let (_, pk, _, msg, sig, _) = BIP340_VECTORS[1];
+        let mut signature = [0; 64];
+        signature[..32].copy_from_slice(&hex_bytes(sig).unwrap()[..32]);
+        assert!(!verify_schnorr(&secp, &signature, &key(msg), &key(pk)));
+
+        // hashes at or above the group order are reduced
+        let reduced = scalar_from_hash(sha256::Hash::from_slice(&[0xff; 32]).unwrap()).unwrap();
+        assert_eq!(&reduced[..], &hex_bytes("000000000000000000000000000000014551231950b75fc4402da1732fc9bebe").unwrap()[..]);
+    }
+
+    #[test]
+    fn test_tap_tweak() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&hex_bytes("b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef").unwrap()).unwrap();
+        let keypair = KeyPair::from_secret_key(&secp, sk);
+        let msg = key("243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89");
+        let aux = [0; 32];
+
+        let scripts = [
+            Script::from(hex_bytes("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac").unwrap()),
+            Script::from(hex_bytes("20b617298552a72ade070667e86ca63b8f5789a9fe8731ef91202a91c9f3459007ac").unwrap()),
+        ];
+        let root = tap_branch_hash(&tap_leaf_hash(&scripts[0], TAPROOT_LEAF_TAPSCRIPT), &tap_leaf_hash(&scripts[1], TAPROOT_LEAF_TAPSCRIPT));
+
+        for merkle_root in &[None, Some(root)] {
+            let tweaked = keypair.tap_tweak(&secp, *merkle_root);
+            let (output_key, parity) = tweak_internal_key(&secp, keypair.public_key(), *merkle_root).unwrap();
+            assert_eq!(*tweaked.output_key(), output_key);
+            assert_eq!(tweaked.keypair().parity(), parity);
+
+            let sig = sign_schnorr(&secp, &msg, tweaked.keypair(), &aux);
+            assert!(verify_schnorr(&secp, &sig, &msg, &output_key));
+            assert!(!verify_schnorr(&secp, &sig, &msg, keypair.public_key()));
+        }
+
+        // key path only, from the BIP341 wallet test vectors
+        let sk = SecretKey::from_slice(&hex_bytes("6b973d88838f27366ed61c9ad6367663045cb456e28335c109e30717ae0c6baa").unwrap()).unwrap();
+        let keypair = KeyPair::from_secret_key(&secp, sk);
+        assert_eq!(*keypair.public_key(), key("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d"));
+        assert_eq!(tap_tweak_hash(keypair.public_key(), None)[..], key("b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b64437d9c6c70")[..]);
+        let tweaked = keypair.tap_tweak(&secp, None);
+        assert_eq!(*tweaked.output_key(), key("53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343"));
+        assert_eq!(tweaked.keypair().secret_key()[..], key("2405b971772ad26915c8dcdf10f238753a9b837e5f8e6a86fd7c0cce5b7296d9")[..]);
+        assert!(tweaked.keypair().parity());
+    }
+
+    #[test]
+    fn test_control_block() {
+        let secp = Secp256k1::verification_only();
+        let script = Script::from(hex_bytes("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac").unwrap());
+        let other = Script::from(hex_bytes("20b617298552a72ade070667e86ca63b8f5789a9fe8731ef91202a91c9f3459007ac").unwrap());
+        let internal_key = key("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27");
+
+        // a single leaf, from the BIP341 wallet test vectors
+        let output_key = key("147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3");
+        let control_block = ControlBlock::from_slice(&hex_bytes("c1187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27").unwrap()).unwrap();
+        assert_eq!(control_block.leaf_version(), TAPROOT_LEAF_TAPSCRIPT);
+        assert!(control_block.output_key_parity());
+        assert_eq!(*control_block.internal_key(), internal_key);
+        assert!(control_block.merkle_branch().is_empty());
+        assert!(control_block.verify(&secp, &output_key, &script));
+        assert!(!control_block.verify(&secp, &output_key, &other));
+        assert!(!control_block.verify(&secp, &internal_key, &script));
+
+        // both leaves of a two-leaf tree, from the BIP341 wallet test vectors;
+        // the second has an unknown leaf version
+        let internal_key = key("ee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf3786592");
+        let output_key = key("712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5");
+        let script = Script::from(hex_bytes("20387671353e273264c495656e27e39ba899ea8fee3bb69fb2a680e22093447d48ac").unwrap());
+        let other = Script::from(hex_bytes("06424950333431").unwrap());
+        let data = hex_bytes("c0ee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf3786592f224a923cd0021ab202ab139cc56802ddb92dcfc172b9212261a539df79a112a").unwrap();
+        let control_block = ControlBlock::from_slice(&data).unwrap();
+        assert!(!control_block.output_key_parity());
+        assert_eq!(*control_block.internal_key(), internal_key);
+        assert_eq!(control_block.merkle_branch(), &[tap_leaf_hash(&other, 0xfa)]);
+        assert!(control_block.verify(&secp, &output_key, &script));
+        assert!(!control_block.verify(&secp, &output_key, &other));
+        assert_eq!(control_block.serialize(), data);
+
+        let other_data = hex_bytes("faee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf37865928ad69ec7cf41c2a4001fd1f738bf1e505ce2277acdcaa63fe4765192497f47a7").unwrap();
+        let other_block = ControlBlock::from_slice(&other_data).unwrap();
+        assert_eq!(other_block.leaf_version(), 0xfa);
+        assert_eq!(other_block.merkle_branch(), &[tap_leaf_hash(&script, TAPROOT_LEAF_TAPSCRIPT)]);
+        assert!(other_block.verify(&secp, &output_key, &other));
+        assert!(!other_block.verify(&secp, &output_key, &script));
+
+        // with the parity flipped the proof fails
+        let mut flipped = data.clone();
+        flipped[0] ^= 1;
+        assert!(!ControlBlock::from_slice(&flipped).unwrap().verify(&secp, &output_key, &script));
+
+        assert_eq!(ControlBlock::from_slice(&data[..32]), Err(Error::InvalidControlBlockSize(32)));
+        assert_eq!(ControlBlock::from_slice(&data[..34]), Err(Error::InvalidControlBlockSize(34)));
+    }
+}